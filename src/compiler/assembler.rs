@@ -0,0 +1,500 @@
+//! Assembles a preprocessed program into a `Program`
+
+use crate::{
+    compiler::{
+        data::{DebugInfo, InputFile, Inst, InstructionLocation},
+        instruction::{ApUpdate, MachineInstruction, Op1Source, Opcode, PcUpdate, ResLogic},
+        sema::{const_eval::eval_const, PreprocessedProgram, ScopedName},
+        Program,
+    },
+    error::{CairoError, Result},
+    parser::ast::{
+        Call, Expr, ExprAssignment, Identifier, Instruction, Jmp, LineIndex, Loc, RValue, Register,
+    },
+};
+use ethers::types::U256;
+
+/// Encodes all instructions of the program
+///
+/// Label targets are resolved via `PreprocessedProgram::labels`, so the label offsets must be
+/// computed beforehand. Hints and references are not supported yet. Values are encoded as field
+/// elements of `PreprocessedProgram::prime`.
+///
+/// If `debug_info` is set, the source location of every instruction is recorded for the modules
+/// whose code is known.
+pub fn assemble(prg: &PreprocessedProgram, debug_info: bool) -> Result<Program> {
+    let mut assembler = Assembler {
+        prg,
+        pc: 0,
+        scopes: Vec::new(),
+        data: Vec::new(),
+        debug_info: debug_info.then(DebugInfo::default),
+        source: None,
+    };
+    for module in prg.modules.iter() {
+        assembler.source = None;
+        if let (Some(debug_info), Some(content)) = (&mut assembler.debug_info, &module.content) {
+            debug_info.file_contents.insert(content.path.clone(), content.code.clone());
+            let filename = content.path.to_string_lossy().into_owned();
+            assembler.source = Some((LineIndex::new(&content.code), filename));
+        }
+        assembler.scopes.push(module.module_name.as_ref().clone());
+        assembler.assemble_instructions(&module.cairo_file.0)?;
+        assembler.scopes.pop();
+    }
+    Ok(Program {
+        attributes: Default::default(),
+        builtins: prg.builtin_names(),
+        compiler_version: None,
+        data: assembler.data.iter().map(|word| format!("{:#x}", word)).collect(),
+        debug_info: assembler.debug_info,
+        hints: Default::default(),
+        identifiers: Default::default(),
+        main_scope: prg.main_scope.name(),
+        prime: format!("{:#x}", prg.prime),
+        reference_manager: serde_json::json!({ "references": [] }),
+    })
+}
+
+struct Assembler<'a> {
+    prg: &'a PreprocessedProgram,
+    /// the program counter of the next instruction
+    pc: usize,
+    scopes: Vec<ScopedName>,
+    data: Vec<U256>,
+    /// the collected debug info, if requested
+    debug_info: Option<DebugInfo>,
+    /// the line index and file name of the current module, if its locations are recorded
+    source: Option<(LineIndex, String)>,
+}
+
+impl<'a> Assembler<'a> {
+    fn assemble_instructions(&mut self, instructions: &[Instruction]) -> Result<()> {
+        for ins in instructions {
+            self.assemble_instruction(ins)?;
+        }
+        Ok(())
+    }
+
+    fn assemble_scoped(&mut self, name: &str, instructions: &[Instruction]) -> Result<()> {
+        self.scopes.push(self.current_scope().appended(name));
+        self.assemble_instructions(instructions)?;
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn assemble_instruction(&mut self, ins: &Instruction) -> Result<()> {
+        match ins {
+            Instruction::Function(f) => self.assemble_scoped(&f.name, &f.instructions),
+            Instruction::Namespace(ns) => self.assemble_scoped(&ns.name, &ns.instructions),
+            Instruction::WithStatement(w) => self.assemble_instructions(&w.instructions),
+            Instruction::WithAttrStatement(w) => self.assemble_instructions(&w.instructions),
+            Instruction::DataWord(expr, loc) => {
+                // data words are folded by the preprocessor, but may still be constant expressions
+                // if it didn't run
+                let value =
+                    eval_const(expr, &self.prg.prime, &self.prg.identifiers).map_err(|_| {
+                        CairoError::Assemble(format!(
+                            "Expected a constant value, found {} {}",
+                            expr, loc
+                        ))
+                    })?;
+                self.record_location(*loc);
+                self.data.push(value);
+                self.pc += 1;
+                Ok(())
+            }
+            ins => {
+                if let Some(machine_ins) = self.lower(ins)? {
+                    if let Some(loc) = instruction_loc(ins) {
+                        self.record_location(loc);
+                    }
+                    self.pc += machine_ins.size();
+                    self.data.extend(machine_ins.encode());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Records the source location of the instruction at the current pc
+    fn record_location(&mut self, loc: Loc) {
+        let (debug_info, (index, filename)) = match (&mut self.debug_info, &self.source) {
+            (Some(debug_info), Some(source)) => (debug_info, source),
+            _ => return,
+        };
+        let (start_line, start_col) = index.locate(loc.0);
+        let (end_line, end_col) = index.locate(loc.1);
+        let location = InstructionLocation {
+            accessible_scopes: self.scopes.iter().map(ScopedName::name).collect(),
+            flow_tracking_data: Default::default(),
+            hints: Vec::new(),
+            inst: Inst {
+                end_col: end_col as i64,
+                end_line: end_line as i64,
+                input_file: InputFile { filename: filename.clone() },
+                start_col: start_col as i64,
+                start_line: start_line as i64,
+                parent_location: None,
+            },
+        };
+        debug_info.instruction_locations.insert(self.pc.to_string(), location);
+    }
+
+    /// Converts the instruction into a machine instruction, statements that don't emit code yield
+    /// `None`
+    fn lower(&self, ins: &Instruction) -> Result<Option<MachineInstruction>> {
+        let ins = match ins {
+            Instruction::Assign(dst, res, _) => {
+                let (dst_register, off_dst) = self.mem_ref(dst)?;
+                MachineInstruction {
+                    dst_register,
+                    off_dst,
+                    ..self.res(MachineInstruction::new(Opcode::AssertEq), res)?
+                }
+            }
+            Instruction::Jmp(jmp, _) => self.jmp(jmp)?,
+            Instruction::CallInstruction(call, _) => {
+                let ins = MachineInstruction {
+                    off_dst: 0,
+                    dst_register: Register::Ap,
+                    off_op0: 1,
+                    op0_register: Register::Ap,
+                    ..MachineInstruction::new(Opcode::Call)
+                };
+                match call {
+                    Call::Rel(expr) => {
+                        MachineInstruction { pc_update: PcUpdate::JumpRel, ..self.op1(ins, expr)? }
+                    }
+                    Call::Abs(expr) => {
+                        MachineInstruction { pc_update: PcUpdate::JumpAbs, ..self.op1(ins, expr)? }
+                    }
+                    Call::Id(id) => MachineInstruction {
+                        pc_update: PcUpdate::JumpRel,
+                        ..ins.with_imm(self.relative_label(id)?)
+                    },
+                }
+            }
+            Instruction::Ret(_) => MachineInstruction {
+                off_dst: -2,
+                pc_update: PcUpdate::JumpAbs,
+                ..MachineInstruction::new(Opcode::Ret)
+            },
+            Instruction::ApAddAssign(expr, _) => MachineInstruction {
+                ap_update: ApUpdate::Add,
+                ..self.op1(MachineInstruction::new(Opcode::Nop), expr)?
+            },
+            Instruction::ApAdd(inner, loc) => {
+                let ins = self.lower(inner)?.filter(|ins| {
+                    ins.ap_update == ApUpdate::Regular &&
+                        matches!(ins.opcode, Opcode::AssertEq | Opcode::Nop)
+                });
+                match ins {
+                    Some(ins) => MachineInstruction { ap_update: ApUpdate::Add1, ..ins },
+                    None => {
+                        return Err(CairoError::Assemble(format!(
+                            "ap++ is not supported for `{}` {}",
+                            inner, loc
+                        )))
+                    }
+                }
+            }
+            Instruction::Let(_, rvalue, _) if matches!(**rvalue, RValue::Expr(_)) => {
+                return Ok(None)
+            }
+            Instruction::Const(_) |
+            Instruction::Member(_, _) |
+            Instruction::StaticAssert(_, _, _) |
+            Instruction::Label(_, _) |
+            Instruction::Struct(_) |
+            Instruction::Hint(_, _) |
+            Instruction::Directive(_) |
+            Instruction::Import(_) => return Ok(None),
+            ins => return Err(CairoError::Assemble(format!("Cannot assemble `{}` yet", ins))),
+        };
+        Ok(Some(ins))
+    }
+
+    fn jmp(&self, jmp: &Jmp) -> Result<MachineInstruction> {
+        let ins = MachineInstruction::new(Opcode::Nop);
+        let ins = match jmp {
+            Jmp::Rel(expr) => {
+                MachineInstruction { pc_update: PcUpdate::JumpRel, ..self.op1(ins, expr)? }
+            }
+            Jmp::Abs(expr) => {
+                MachineInstruction { pc_update: PcUpdate::JumpAbs, ..self.op1(ins, expr)? }
+            }
+            Jmp::Id(id) => MachineInstruction {
+                pc_update: PcUpdate::JumpRel,
+                ..ins.with_imm(self.relative_label(id)?)
+            },
+            Jmp::RelIf(_, cond, n) | Jmp::IdIf(_, cond, n) => {
+                if *n != 0 {
+                    return Err(CairoError::Assemble(format!(
+                        "Conditional jumps must compare to 0, found {}",
+                        n
+                    )))
+                }
+                let (dst_register, off_dst) = self.mem_ref(cond)?;
+                let ins = match jmp {
+                    Jmp::IdIf(id, _, _) => ins.with_imm(self.relative_label(id)?),
+                    Jmp::RelIf(expr, _, _) => self.op1(ins, expr)?,
+                    _ => unreachable!(),
+                };
+                MachineInstruction { dst_register, off_dst, pc_update: PcUpdate::Jnz, ..ins }
+            }
+        };
+        Ok(ins)
+    }
+
+    /// Sets the operands for the `res` of an `assert_eq`
+    fn res(&self, ins: MachineInstruction, res: &Expr) -> Result<MachineInstruction> {
+        let (lhs, rhs, res_logic) = match res {
+            Expr::Add(lhs, rhs, _) => (lhs, rhs, ResLogic::Add),
+            Expr::Mul(lhs, rhs, _) => (lhs, rhs, ResLogic::Mul),
+            Expr::Sub(lhs, rhs, loc) => {
+                // `x - c` is encoded as `x + (-c)`
+                let imm = self.immediate(rhs).ok_or_else(|| {
+                    CairoError::Assemble(format!(
+                        "Expected a constant value, found {} {}",
+                        rhs, loc
+                    ))
+                })?;
+                let (op0_register, off_op0) = self.mem_ref(lhs)?;
+                return Ok(MachineInstruction {
+                    op0_register,
+                    off_op0,
+                    res: ResLogic::Add,
+                    ..ins.with_imm(self.negate(imm))
+                })
+            }
+            res => {
+                if let Some((op0_register, off_op0, off_op1)) = double_deref(res) {
+                    return Ok(MachineInstruction {
+                        op0_register,
+                        off_op0,
+                        off_op1,
+                        op1_source: Op1Source::Op0,
+                        ..ins
+                    })
+                }
+                return self.op1(ins, res)
+            }
+        };
+        let (op0_register, off_op0) = self.mem_ref(lhs)?;
+        Ok(MachineInstruction { op0_register, off_op0, res: res_logic, ..self.op1(ins, rhs)? })
+    }
+
+    /// Sets `op1` to either an immediate value or a memory reference
+    fn op1(&self, ins: MachineInstruction, expr: &Expr) -> Result<MachineInstruction> {
+        if let Some(imm) = self.immediate(expr) {
+            return Ok(ins.with_imm(imm))
+        }
+        let (register, off_op1) = self.mem_ref(expr)?;
+        let op1_source = match register {
+            Register::Ap => Op1Source::Ap,
+            Register::Fp => Op1Source::Fp,
+        };
+        Ok(MachineInstruction { op1_source, off_op1, ..ins })
+    }
+
+    fn mem_ref(&self, expr: &Expr) -> Result<(Register, i16)> {
+        match expr {
+            Expr::Deref(inner, _) => register_offset(inner),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            CairoError::Assemble(format!(
+                "Expected a memory reference like [fp + 1], found {}",
+                expr
+            ))
+        })
+    }
+
+    /// Returns the value of a constant expression as field element
+    fn immediate(&self, expr: &Expr) -> Option<U256> {
+        match expr {
+            Expr::Int(_, _) | Expr::HexInt(_, _) => {
+                expr.as_u256().map(|value| value % self.prg.prime)
+            }
+            Expr::Neg(expr, _) => self.immediate(expr).map(|value| self.negate(value)),
+            Expr::Parentheses(exprs, _) => match exprs.as_slice() {
+                [ExprAssignment::Expr(expr, _)] => self.immediate(expr),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn negate(&self, value: U256) -> U256 {
+        if value.is_zero() {
+            value
+        } else {
+            self.prg.prime - value
+        }
+    }
+
+    fn current_scope(&self) -> ScopedName {
+        self.scopes.last().cloned().unwrap_or_else(ScopedName::root)
+    }
+
+    /// Returns the distance from the current pc to the label as field element
+    ///
+    /// The label is looked up in the current scope and all of its parents.
+    fn relative_label(&self, id: &Identifier) -> Result<U256> {
+        let label = ScopedName::new(id.clone());
+        let mut scope = Some(self.current_scope());
+        while let Some(current) = scope {
+            if let Some(pc) = self.prg.labels.get(&current.clone().extended(label.clone())) {
+                return Ok(if *pc >= self.pc {
+                    U256::from(pc - self.pc)
+                } else {
+                    self.negate(U256::from(self.pc - pc))
+                })
+            }
+            scope = current.parent();
+        }
+        Err(CairoError::MissingIdentifier(label))
+    }
+}
+
+/// Returns the location of an instruction that emits code
+fn instruction_loc(ins: &Instruction) -> Option<Loc> {
+    match ins {
+        Instruction::Assign(_, _, loc) |
+        Instruction::Jmp(_, loc) |
+        Instruction::CallInstruction(_, loc) |
+        Instruction::Ret(loc) |
+        Instruction::ApAddAssign(_, loc) |
+        Instruction::ApAdd(_, loc) => Some(*loc),
+        _ => None,
+    }
+}
+
+/// Matches `reg`, `reg + off` and `reg - off`
+fn register_offset(expr: &Expr) -> Option<(Register, i16)> {
+    match expr {
+        Expr::Register(register, _) => Some((*register, 0)),
+        Expr::Add(lhs, rhs, _) => {
+            let (register, off) = register_offset(lhs)?;
+            Some((register, off.checked_add(offset(rhs)?)?))
+        }
+        Expr::Sub(lhs, rhs, _) => {
+            let (register, off) = register_offset(lhs)?;
+            Some((register, off.checked_sub(offset(rhs)?)?))
+        }
+        Expr::Parentheses(exprs, _) => match exprs.as_slice() {
+            [ExprAssignment::Expr(expr, _)] => register_offset(expr),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Matches `[[reg + off0] + off1]`
+fn double_deref(expr: &Expr) -> Option<(Register, i16, i16)> {
+    let inner = match expr {
+        Expr::Deref(inner, _) => inner,
+        _ => return None,
+    };
+    let (base, off) = match inner.as_ref() {
+        Expr::Deref(base, _) => (base, 0),
+        Expr::Add(lhs, rhs, _) => match lhs.as_ref() {
+            Expr::Deref(base, _) => (base, offset(rhs)?),
+            _ => return None,
+        },
+        Expr::Sub(lhs, rhs, _) => match lhs.as_ref() {
+            Expr::Deref(base, _) => (base, offset(rhs)?.checked_neg()?),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let (register, base_off) = register_offset(base)?;
+    Some((register, base_off, off))
+}
+
+/// Returns the value of a small integer literal that fits into an offset
+fn offset(expr: &Expr) -> Option<i16> {
+    match expr {
+        Expr::Int(value, _) if *value <= U256::from(i16::MAX) => Some(value.as_u32() as i16),
+        Expr::Neg(expr, _) => offset(expr)?.checked_neg(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compiler::{
+            constants::DEFAULT_PRIME,
+            sema::{
+                passes::{LabelOffsetPass, Pass},
+                CairoModule,
+            },
+        },
+        CairoFile,
+    };
+
+    fn assemble_code(code: &str) -> Result<Program> {
+        let module = CairoModule::new(ScopedName::main_scope(), CairoFile::parse(code).unwrap());
+        let mut prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        LabelOffsetPass::default().run(&mut prg)?;
+        assemble(&prg, false)
+    }
+
+    #[test]
+    fn can_assemble_ret() {
+        let program = assemble_code("ret\n").unwrap();
+        assert_eq!(program.data, ["0x208b7fff7fff7ffe"]);
+        assert_eq!(
+            program.prime,
+            "0x800000000000011000000000000000000000000000000000000000000000001"
+        );
+        assert_eq!(program.main_scope, "__main__");
+        assert!(program.builtins.is_empty());
+        assert!(program.hints.is_empty());
+    }
+
+    #[test]
+    fn can_assemble_data_words() {
+        let program = assemble_code("dw 0x1234\ndw 5 + 3\ndw -1\n").unwrap();
+        let minus_one = format!("{:#x}", DEFAULT_PRIME - 1);
+        assert_eq!(program.data, ["0x1234", "0x8", minus_one.as_str()]);
+
+        let err = assemble_code("dw [ap]\n").unwrap_err();
+        assert!(err.to_string().starts_with("Expected a constant value, found [ap]"), "{}", err);
+    }
+
+    #[test]
+    fn can_assemble_instructions() {
+        let program = assemble_code(
+            r#"
+start:
+[ap] = 5; ap++
+[ap] = [fp + -3] + [ap - 1]
+[fp] = [[ap - 2] + 1]
+jmp start
+ap += 2
+"#,
+        )
+        .unwrap();
+        let minus_four = format!("{:#x}", DEFAULT_PRIME - 4);
+        assert_eq!(
+            program.data,
+            [
+                "0x480680017fff8000",
+                "0x5",
+                "0x40327fff7ffd8000",
+                "0x400180017ffe8000",
+                "0x10780017fff7fff",
+                minus_four.as_str(),
+                "0x40780017fff7fff",
+                "0x2",
+            ]
+        );
+
+        let err = assemble_code("[ap] = x\n").unwrap_err();
+        assert!(err.to_string().starts_with("Expected a memory reference"), "{}", err);
+    }
+}
@@ -6,6 +6,19 @@ pub const START_FILE_NAME: &str = "<start>";
 
 pub const CAIRO_FILE_EXTENSION: &str = ".cairo";
 
+/// The builtins that can appear in a `%builtins` directive
+pub const SUPPORTED_BUILTINS: &[&str] = &[
+    "output",
+    "pedersen",
+    "range_check",
+    "ecdsa",
+    "bitwise",
+    "ec_op",
+    "keccak",
+    "poseidon",
+    "segment_arena",
+];
+
 pub const N_LOCALS_CONSTANT: &str = "SIZEOF_LOCALS";
 
 pub const ARG_SCOPE: &str = "Args";
@@ -16,7 +16,7 @@ pub struct Abi {
     pub outputs: Vec<Item>,
     #[serde(rename = "type")]
     pub abi_type: AbiType,
-    #[serde(rename = "stateMutability")]
+    #[serde(rename = "stateMutability", skip_serializing_if = "Option::is_none")]
     pub state_mutability: Option<String>,
 }
 
@@ -57,13 +57,13 @@ pub struct InstructionLocation {
     pub inst: Inst,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub struct FlowTrackingData {
     pub ap_tracking: ApTracking,
     pub reference_ids: BTreeMap<String, i64>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub struct ApTracking {
     pub group: i64,
     pub offset: i64,
@@ -82,6 +82,7 @@ pub struct Inst {
     pub input_file: InputFile,
     pub start_col: i64,
     pub start_line: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub parent_location: Option<Vec<ParentLocation>>,
 }
 
@@ -115,4 +116,6 @@ pub enum ParentLocation {
 pub enum AbiType {
     #[serde(rename = "function")]
     Function,
+    #[serde(rename = "constructor")]
+    Constructor,
 }
@@ -1 +1,177 @@
-pub enum Opcode {}
+//! The binary representation of cairo instructions
+
+use crate::parser::ast::Register;
+use ethers::types::U256;
+
+/// Offsets are stored with a bias of `2**15`, so they fit into 16 bits
+const OFFSET_BIAS: i32 = 1 << 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op1Source {
+    Op0,
+    Imm,
+    Fp,
+    Ap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResLogic {
+    Op1,
+    Add,
+    Mul,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcUpdate {
+    Regular,
+    JumpAbs,
+    JumpRel,
+    Jnz,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApUpdate {
+    Regular,
+    Add,
+    Add1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Nop,
+    AssertEq,
+    Call,
+    Ret,
+}
+
+/// A single machine instruction and its optional immediate value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachineInstruction {
+    pub off_dst: i16,
+    pub off_op0: i16,
+    pub off_op1: i16,
+    pub dst_register: Register,
+    pub op0_register: Register,
+    pub op1_source: Op1Source,
+    pub res: ResLogic,
+    pub pc_update: PcUpdate,
+    pub ap_update: ApUpdate,
+    pub opcode: Opcode,
+    pub imm: Option<U256>,
+}
+
+impl MachineInstruction {
+    /// Returns a new instruction for the given opcode
+    ///
+    /// Unused operands default to `[fp - 1]`, like the python assembler does.
+    pub fn new(opcode: Opcode) -> Self {
+        Self {
+            off_dst: -1,
+            off_op0: -1,
+            off_op1: -1,
+            dst_register: Register::Fp,
+            op0_register: Register::Fp,
+            op1_source: Op1Source::Fp,
+            res: ResLogic::Op1,
+            pc_update: PcUpdate::Regular,
+            ap_update: ApUpdate::Regular,
+            opcode,
+            imm: None,
+        }
+    }
+
+    /// Uses `imm` as the `op1` operand, which is located right after the instruction
+    pub fn with_imm(mut self, imm: U256) -> Self {
+        self.op1_source = Op1Source::Imm;
+        self.off_op1 = 1;
+        self.imm = Some(imm);
+        self
+    }
+
+    /// The number of memory cells the instruction occupies
+    pub fn size(&self) -> usize {
+        1 + self.imm.is_some() as usize
+    }
+
+    /// Returns the instruction word followed by the immediate value, if any
+    pub fn encode(&self) -> Vec<U256> {
+        let mut flags = 0u64;
+        if self.dst_register == Register::Fp {
+            flags |= 1;
+        }
+        if self.op0_register == Register::Fp {
+            flags |= 1 << 1;
+        }
+        flags |= match self.op1_source {
+            Op1Source::Op0 => 0,
+            Op1Source::Imm => 1 << 2,
+            Op1Source::Fp => 1 << 3,
+            Op1Source::Ap => 1 << 4,
+        };
+        flags |= match self.res {
+            ResLogic::Op1 => 0,
+            ResLogic::Add => 1 << 5,
+            ResLogic::Mul => 1 << 6,
+        };
+        flags |= match self.pc_update {
+            PcUpdate::Regular => 0,
+            PcUpdate::JumpAbs => 1 << 7,
+            PcUpdate::JumpRel => 1 << 8,
+            PcUpdate::Jnz => 1 << 9,
+        };
+        flags |= match self.ap_update {
+            ApUpdate::Regular => 0,
+            ApUpdate::Add => 1 << 10,
+            ApUpdate::Add1 => 1 << 11,
+        };
+        flags |= match self.opcode {
+            Opcode::Nop => 0,
+            Opcode::Call => 1 << 12,
+            Opcode::Ret => 1 << 13,
+            Opcode::AssertEq => 1 << 14,
+        };
+
+        let biased = |off: i16| (off as i32 + OFFSET_BIAS) as u64;
+        let word = biased(self.off_dst) |
+            biased(self.off_op0) << 16 |
+            biased(self.off_op1) << 32 |
+            flags << 48;
+        std::iter::once(U256::from(word)).chain(self.imm).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(ins: MachineInstruction) -> Vec<String> {
+        ins.encode().into_iter().map(|word| format!("{:#x}", word)).collect()
+    }
+
+    #[test]
+    fn can_encode_instructions() {
+        // ret
+        let ret = MachineInstruction {
+            off_dst: -2,
+            pc_update: PcUpdate::JumpAbs,
+            ..MachineInstruction::new(Opcode::Ret)
+        };
+        assert_eq!(encode(ret), ["0x208b7fff7fff7ffe"]);
+
+        // [ap] = 5; ap++
+        let assign = MachineInstruction {
+            off_dst: 0,
+            dst_register: Register::Ap,
+            ap_update: ApUpdate::Add1,
+            ..MachineInstruction::new(Opcode::AssertEq).with_imm(5.into())
+        };
+        assert_eq!(encode(assign), ["0x480680017fff8000", "0x5"]);
+
+        // jmp rel 0
+        let jmp = MachineInstruction {
+            pc_update: PcUpdate::JumpRel,
+            ..MachineInstruction::new(Opcode::Nop).with_imm(0.into())
+        };
+        assert_eq!(encode(jmp), ["0x10780017fff7fff", "0x0"]);
+    }
+}
@@ -1,13 +1,26 @@
-pub use crate::compiler::{data::DebugInfo, module_reader::ModuleReader};
+pub use crate::compiler::{
+    data::{Abi, AbiType, DebugInfo, Item},
+    module_reader::{CodeReader, InMemoryReader, ModuleReader},
+};
 use crate::{
-    compiler::sema::{PreprocessedProgram, ScopedName},
+    compiler::{
+        constants::{CAIRO_FILE_EXTENSION, START_CODE, START_FILE_NAME},
+        sema::{
+            passes::{LabelOffsetPass, Pass},
+            PreprocessedProgram, ScopedName,
+        },
+    },
     error::Result,
+    CairoFile,
 };
 use std::{
+    collections::HashSet,
     fs, io,
     path::{Path, PathBuf},
 };
 
+mod assembler;
+pub use assembler::assemble;
 /// compiler builtins
 mod builtins;
 pub mod constants;
@@ -17,14 +30,13 @@ mod module_reader;
 mod program;
 pub use program::Program;
 pub mod sema;
-use crate::compiler::constants::{START_CODE, START_FILE_NAME};
 pub use sema::{
-    ast::{VResult, Visitable, Visitor},
-    passes::PassManager,
+    ast::{RefVisitor, VResult, Visitable, Visitor, Walk},
+    passes::{PassManager, PassManagerBuilder},
 };
 
 /// Utility struct to compile a list of cairo files
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct CairoCompiler {
     /// files to compile
     files: Vec<PathBuf>,
@@ -32,18 +44,86 @@ pub struct CairoCompiler {
     debug_info: bool,
 
     main_scope: Option<ScopedName>,
+    /// how to resolve imported modules
+    module_reader: ModuleReader,
 }
 
 impl CairoCompiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compiles all cairo files of the directory and its subdirectories, hidden directories are
+    /// skipped
+    ///
+    /// Imported modules are resolved relative to the directory first, then via the `CAIRO_PATH`
+    /// env var. Files that are imported by another file of the directory, like `a/b.cairo` for
+    /// `from a.b import x`, are compiled as that module instead of as part of the main scope.
+    pub fn from_dir(root: impl AsRef<Path>) -> io::Result<Self> {
+        let root = root.as_ref();
+        let mut files = Vec::new();
+        find_cairo_files(root, &mut files)?;
+        files.sort();
+
+        let mut imported = HashSet::new();
+        for file in files.iter() {
+            // files that fail to parse are kept, so the error is reported when compiling
+            if let Ok(cairo_file) = CairoFile::parse(&fs::read_to_string(file)?) {
+                imported.extend(cairo_file.imports().map(|import| import.path.join(".")));
+            }
+        }
+        files.retain(|file| {
+            let module = file.strip_prefix(root).unwrap_or(file).with_extension("");
+            let module: Vec<_> = module.iter().map(|s| s.to_string_lossy()).collect();
+            !imported.contains(&module.join("."))
+        });
+
+        Ok(Self {
+            files,
+            module_reader: ModuleReader::default().with_cwd(root),
+            ..Default::default()
+        })
+    }
+
+    /// Adds a file to compile
+    pub fn with_file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.files.push(file.into());
+        self
+    }
+
+    /// Whether to include debug info in the compiled program
+    pub fn debug_info(mut self, debug_info: bool) -> Self {
+        self.debug_info = debug_info;
+        self
+    }
+
+    /// Use a custom main scope instead of `__main__`
+    pub fn main_scope(mut self, main_scope: ScopedName) -> Self {
+        self.main_scope = Some(main_scope);
+        self
+    }
+
+    /// Compiles all files, modules are resolved via the `CAIRO_PATH` env var and, for compilers
+    /// created with `from_dir`, relative to the directory
     pub fn compile(&self) -> Result<Program> {
-        todo!()
+        let mut module_reader = self.module_reader.clone();
+        let pass_manager =
+            PassManagerBuilder::default().module_reader(module_reader.clone()).build();
+        compile_cairo(
+            self.files.clone(),
+            self.debug_info,
+            false,
+            pass_manager,
+            &mut module_reader,
+            self.main_scope.clone(),
+        )
     }
 }
 
 /// Compiles a list of cairo files
 pub fn compile_cairo<I, P>(
     files: I,
-    _debug_info: bool,
+    debug_info: bool,
     add_start: bool,
     pass_manager: impl Into<PassManager>,
     _module_reader: &mut ModuleReader,
@@ -58,13 +138,6 @@ where
         codes.insert(0, start_code());
     }
 
-    let mut debug_info = DebugInfo::default();
-    if let Some((content, file)) =
-        codes.get(0).filter(|(_, file)| file == Path::new(START_FILE_NAME))
-    {
-        debug_info.file_contents.insert(file.clone(), content.clone());
-    }
-
     let mut pass_manager = pass_manager.into();
     let main_scope = main_scope.unwrap_or_else(ScopedName::main_scope);
 
@@ -72,8 +145,17 @@ where
     let mut prg = PreprocessedProgram::new(main_scope, codes);
     // execute all compiler passes
     pass_manager.run_on(&mut prg)?;
-    // TODO assemble the cairo program
-    todo!()
+    LabelOffsetPass::default().run(&mut prg)?;
+
+    let mut program = assemble(&prg, debug_info)?;
+    if !debug_info {
+        // the start code is always included, because it's not backed by a file
+        if let Some(content) = prg.codes.iter().find(|c| c.path == Path::new(START_FILE_NAME)) {
+            let debug_info = program.debug_info.get_or_insert_with(Default::default);
+            debug_info.file_contents.insert(content.path.clone(), content.code.clone());
+        }
+    }
+    Ok(program)
 }
 
 /// Reads all given files and returns them zipped with their content
@@ -87,6 +169,211 @@ fn read_files(
         .collect()
 }
 
+/// Recursively collects all cairo files in the directory, skipping hidden directories
+fn find_cairo_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            let hidden =
+                path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if !hidden {
+                find_cairo_files(&path, files)?;
+            }
+        } else if path.to_string_lossy().ends_with(CAIRO_FILE_EXTENSION) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
 fn start_code() -> (String, PathBuf) {
     (START_CODE.to_string(), START_FILE_NAME.into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::constants::DEFAULT_PRIME;
+
+    /// A temporary directory that is unique to the test and removed when dropped
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+            let id = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            let dir = std::env::temp_dir().join(format!(
+                "cairo-lang-rs-{}-{}-{}",
+                name,
+                std::process::id(),
+                id
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl std::ops::Deref for TestDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn can_configure_compiler() {
+        let compiler = CairoCompiler::new()
+            .with_file("a.cairo")
+            .with_file("b.cairo")
+            .debug_info(true)
+            .main_scope(ScopedName::from_str("main"));
+        assert_eq!(compiler.files, vec![PathBuf::from("a.cairo"), PathBuf::from("b.cairo")]);
+        assert!(compiler.debug_info);
+        assert_eq!(compiler.main_scope, Some(ScopedName::from_str("main")));
+    }
+
+    #[test]
+    fn can_compile_directories() {
+        let dir = TestDir::new("from-dir");
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::create_dir_all(dir.join(".hidden")).unwrap();
+        fs::write(dir.join("lib/math.cairo"), "func helper():\n    ret\nend\n").unwrap();
+        fs::write(
+            dir.join("main.cairo"),
+            "from lib.math import helper\nfunc main():\n    [ap] = 1; ap++\n    ret\nend\n",
+        )
+        .unwrap();
+        fs::write(dir.join(".hidden/broken.cairo"), "func {").unwrap();
+        fs::write(dir.join("notes.txt"), "not cairo").unwrap();
+
+        let compiler = CairoCompiler::from_dir(&*dir).unwrap();
+        assert_eq!(compiler.files, vec![dir.join("main.cairo")]);
+        let program = compiler.compile().unwrap();
+        // `helper` is compiled once, as part of the `lib.math` module
+        assert_eq!(program.data.len(), 4);
+
+        assert!(CairoCompiler::from_dir(dir.join("missing")).is_err());
+    }
+
+    #[test]
+    fn can_collect_debug_info() {
+        let dir = TestDir::new("debug-info");
+        let file = dir.join("debug.cairo");
+        fs::write(&file, "func main():\n    [ap] = 1; ap++\n    call main\n    ret\nend\n")
+            .unwrap();
+
+        let compile = |debug_info| {
+            compile_cairo(
+                [&file],
+                debug_info,
+                false,
+                PassManagerBuilder::default().build(),
+                &mut ModuleReader::default(),
+                None,
+            )
+            .unwrap()
+        };
+        let program = compile(false);
+        assert!(program.debug_info.is_none());
+
+        let program = compile(true);
+        let debug_info = program.debug_info.unwrap();
+        let locations = &debug_info.instruction_locations;
+        assert_eq!(locations.keys().collect::<Vec<_>>(), ["0", "2", "4"]);
+        let assign = &locations["0"];
+        assert_eq!(assign.accessible_scopes, ["__main__", "__main__.main"]);
+        assert_eq!(assign.inst.input_file.filename, file.to_string_lossy());
+        assert_eq!((assign.inst.start_line, assign.inst.start_col), (2, 5));
+        assert_eq!((assign.inst.end_line, assign.inst.end_col), (2, 19));
+        let call = &locations["2"];
+        assert_eq!((call.inst.start_line, call.inst.start_col), (3, 5));
+        assert_eq!((call.inst.end_line, call.inst.end_col), (3, 14));
+        assert_eq!(locations["4"].inst.start_line, 4);
+        assert!(debug_info.file_contents.contains_key(&file));
+    }
+
+    #[test]
+    fn can_require_main_function() {
+        let dir = TestDir::new("require-main");
+        let file = dir.join("no_main.cairo");
+        fs::write(&file, "func foo():\n    ret\nend\n").unwrap();
+
+        for prime in [None, Some(DEFAULT_PRIME)] {
+            let mut builder = PassManagerBuilder::default();
+            if let Some(prime) = prime {
+                builder = builder.prime(prime);
+            }
+            let err = compile_cairo(
+                [&file],
+                false,
+                true,
+                builder.build(),
+                &mut ModuleReader::default(),
+                None,
+            )
+            .unwrap_err();
+            assert_eq!(err.to_string(), "Function 'main' not found");
+        }
+
+        // `main` is only required with the start code
+        let compile = |file: &Path, add_start| {
+            compile_cairo(
+                [file],
+                false,
+                add_start,
+                PassManagerBuilder::default().build(),
+                &mut ModuleReader::default(),
+                None,
+            )
+        };
+        compile(&file, false).unwrap();
+
+        let file = dir.join("main.cairo");
+        fs::write(&file, "func main():\n    ret\nend\n").unwrap();
+        let program = compile(&file, true).unwrap();
+        assert_eq!(program.data.len(), 7);
+    }
+
+    #[test]
+    fn can_compile_start_code() {
+        let dir = TestDir::new("start-code");
+        let file = dir.join("main.cairo");
+        fs::write(
+            &file,
+            "struct Point:\n    member x : felt\n    member y : felt\nend\n\nfunc main{output_ptr}(p : Point, n : felt):\n    ap += Point.SIZE\n    ret\nend\n",
+        )
+        .unwrap();
+        let program = compile_cairo(
+            [&file],
+            false,
+            true,
+            PassManagerBuilder::default().build(),
+            &mut ModuleReader::default(),
+            None,
+        )
+        .unwrap();
+        // `ap += 4` for `main.Args.SIZE + main.ImplicitArgs.SIZE` and `call rel 4` to `main`
+        assert_eq!(
+            program.data,
+            [
+                "0x40780017fff7fff",
+                "0x4",
+                "0x1104800180018000",
+                "0x4",
+                "0x10780017fff7fff",
+                "0x0",
+                "0x40780017fff7fff",
+                "0x2",
+                "0x208b7fff7fff7ffe",
+            ]
+        );
+    }
+}
@@ -4,18 +4,38 @@ use crate::{
         sema::ScopedName,
     },
     error::{CairoError, Result},
+    CairoFile,
 };
-use std::{collections::HashMap, fs, path::PathBuf};
+use std::{
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, HashMap},
+    fmt, fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// Resolved module files, keyed by the `cwd` they were resolved in and the module name
+type ResolvedModules = HashMap<(Option<PathBuf>, String), PathBuf>;
 
 /// Helper types that's used to read module files based their names
 ///
 /// In oder to be able to properly resolve the modules, they must be stored under one of the allowed
 /// paths.
+///
+/// Parsed modules are cached by the hash of their content, so compiling the same project again only
+/// re-parses the files that changed. Clones of a reader share this cache and the cache of resolved
+/// modules.
 #[derive(Debug, Clone)]
 pub struct ModuleReader {
     /// where to look for paths
     paths: Vec<PathBuf>,
-    resolved_modules: HashMap<String, ()>,
+    /// the directory that is searched before all paths, see `with_cwd`
+    cwd: Option<PathBuf>,
+    /// cache of already resolved modules and their files
+    resolved_modules: Rc<RefCell<ResolvedModules>>,
+    /// cache of parsed modules and the hash of the content they were parsed from
+    parsed_modules: Rc<RefCell<HashMap<String, (u64, CairoFile)>>>,
 }
 
 impl ModuleReader {
@@ -26,15 +46,47 @@ impl ModuleReader {
     {
         Self {
             paths: paths.into_iter().map(Into::into).collect(),
+            cwd: None,
             resolved_modules: Default::default(),
+            parsed_modules: Default::default(),
         }
     }
 
+    /// Drops the cached parse tree of the module, so it is parsed again on the next read
+    pub fn invalidate(&self, module: &str) {
+        self.parsed_modules.borrow_mut().remove(module);
+    }
+
+    /// Searches the given directory before all other paths, replacing the previous `cwd`
+    ///
+    /// This is used to resolve modules relative to the directory of the importing file.
+    pub fn with_cwd(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
     /// Attempts to find the corresponding file for the given module
+    ///
+    /// The module `a.b.c` is looked up as `a/b/c.cairo` under each of the paths first, and as just
+    /// `c.cairo` if no such file exists.
+    ///
+    /// Resolved modules are cached, so subsequent lookups don't hit the filesystem
     pub fn find(&self, module: impl AsRef<str>) -> Option<PathBuf> {
-        let scope = ScopedName::from_str(module);
+        let key = (self.cwd.clone(), module.as_ref().to_string());
+        if let Some(path) = self.resolved_modules.borrow().get(&key) {
+            return Some(path.clone())
+        }
+        let scope = key.1.parse::<ScopedName>().ok()?;
         let file_name = format!("{}{}", scope.last()?, CAIRO_FILE_EXTENSION);
-        self.paths.iter().map(|p| p.join(&file_name)).find(|path| path.exists())
+        let nested: PathBuf =
+            scope.segments()[..scope.len() - 1].iter().chain(std::iter::once(&file_name)).collect();
+        let paths = || self.cwd.iter().chain(self.paths.iter());
+        let path = paths()
+            .map(|p| p.join(&nested))
+            .chain(paths().map(|p| p.join(&file_name)))
+            .find(|path| path.exists())?;
+        self.resolved_modules.borrow_mut().insert(key, path.clone());
+        Some(path)
     }
 }
 
@@ -44,20 +96,198 @@ impl Default for ModuleReader {
             paths: std::env::var(LIBS_DIR_ENVVAR)
                 .map(|p| vec![PathBuf::from(p)])
                 .unwrap_or_default(),
+            cwd: None,
             resolved_modules: Default::default(),
+            parsed_modules: Default::default(),
         }
     }
 }
 
-pub(crate) trait CodeReader {
+/// Provides the code of modules based on their names
+pub trait CodeReader: fmt::Debug {
+    /// Returns the code of the module and the path it was read from
     fn read(&self, module: &str) -> Result<(String, PathBuf)>;
+
+    /// Returns a reader that looks up modules relative to the given directory first, if the reader
+    /// supports this
+    fn with_cwd(&self, _cwd: &Path) -> Option<Box<dyn CodeReader>> {
+        None
+    }
+
+    /// Parses the code of the module, readers may return a cached tree if the code is unchanged
+    fn parse(&self, _module: &str, code: &str) -> Result<CairoFile> {
+        Ok(CairoFile::parse(code)?)
+    }
+}
+
+impl<'a, T: CodeReader + ?Sized> CodeReader for &'a T {
+    fn read(&self, module: &str) -> Result<(String, PathBuf)> {
+        (**self).read(module)
+    }
+
+    fn with_cwd(&self, cwd: &Path) -> Option<Box<dyn CodeReader>> {
+        (**self).with_cwd(cwd)
+    }
+
+    fn parse(&self, module: &str, code: &str) -> Result<CairoFile> {
+        (**self).parse(module, code)
+    }
 }
 
-impl<'a> CodeReader for &'a ModuleReader {
+impl CodeReader for ModuleReader {
     /// Finds the module's file and read its content
     fn read(&self, module: &str) -> Result<(String, PathBuf)> {
         let file =
             self.find(module).ok_or_else(|| CairoError::ModuleNotFound(module.to_string()))?;
         Ok(fs::read_to_string(&file).map(|c| (c, file))?)
     }
+
+    fn with_cwd(&self, cwd: &Path) -> Option<Box<dyn CodeReader>> {
+        Some(Box::new(self.clone().with_cwd(cwd)))
+    }
+
+    fn parse(&self, module: &str, code: &str) -> Result<CairoFile> {
+        let mut hasher = DefaultHasher::new();
+        code.hash(&mut hasher);
+        let hash = hasher.finish();
+        if let Some((cached, file)) = self.parsed_modules.borrow().get(module) {
+            if *cached == hash {
+                return Ok(file.clone())
+            }
+        }
+        let file = CairoFile::parse(code)?;
+        self.parsed_modules.borrow_mut().insert(module.to_string(), (hash, file.clone()));
+        Ok(file)
+    }
+}
+
+/// A `CodeReader` for modules that are held in memory, like unsaved buffers
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryReader {
+    /// the code of the modules and their paths, keyed by module name
+    modules: HashMap<String, (String, PathBuf)>,
+}
+
+impl InMemoryReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a module, its path is derived from the module name, `a.b` is stored as `a/b.cairo`
+    pub fn with_module(self, module: impl Into<String>, code: impl Into<String>) -> Self {
+        let module = module.into();
+        let path = PathBuf::from(format!("{}{}", module.replace('.', "/"), CAIRO_FILE_EXTENSION));
+        self.with_module_at(module, code, path)
+    }
+
+    /// Adds a module with the path it should be reported with
+    pub fn with_module_at(
+        mut self,
+        module: impl Into<String>,
+        code: impl Into<String>,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        self.modules.insert(module.into(), (code.into(), path.into()));
+        self
+    }
+}
+
+impl CodeReader for InMemoryReader {
+    fn read(&self, module: &str) -> Result<(String, PathBuf)> {
+        self.modules
+            .get(module)
+            .cloned()
+            .ok_or_else(|| CairoError::ModuleNotFound(module.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_cache_resolved_modules() {
+        let root = Path::new(&env!("CARGO_MANIFEST_DIR"));
+        let reader = ModuleReader::new([root.join("common")]);
+        let path = reader.find("starkware.cairo.common.math").unwrap();
+        assert_eq!(path, root.join("common/math.cairo"));
+        let key = |module: &str| (None, module.to_string());
+        assert!(reader.resolved_modules.borrow().contains_key(&key("starkware.cairo.common.math")));
+        assert_eq!(reader.find("starkware.cairo.common.math").unwrap(), path);
+
+        let err = reader.read("starkware.cairo.common.missing").unwrap_err();
+        assert!(matches!(err, CairoError::ModuleNotFound(_)));
+        let missing = key("starkware.cairo.common.missing");
+        assert!(!reader.resolved_modules.borrow().contains_key(&missing));
+    }
+
+    #[test]
+    fn can_cache_resolved_modules_per_cwd() {
+        let root = Path::new(&env!("CARGO_MANIFEST_DIR")).join("test-data");
+        let reader = ModuleReader::new([root.join("module-reader")]);
+        assert_eq!(reader.find("c").unwrap(), root.join("module-reader/c.cairo"));
+
+        let relative = reader.clone().with_cwd(root.join("relative-imports"));
+        assert_eq!(relative.find("c").unwrap(), root.join("relative-imports/c.cairo"));
+        // resolving in another directory doesn't affect the other readers
+        assert_eq!(reader.find("c").unwrap(), root.join("module-reader/c.cairo"));
+        // the previous cwd is replaced
+        let nested = relative.with_cwd(root.join("module-reader/a/b"));
+        assert_eq!(nested.find("c").unwrap(), root.join("module-reader/a/b/c.cairo"));
+        assert!(nested.find("sibling").is_none());
+        assert_eq!(reader.resolved_modules.borrow().len(), 3);
+    }
+
+    #[test]
+    fn can_reuse_parsed_modules() {
+        let reader = ModuleReader::new(Vec::<PathBuf>::new());
+        let code = "const X = 1\n";
+        let file = reader.parse("a", code).unwrap();
+        assert_eq!(file, CairoFile::parse(code).unwrap());
+
+        // replace the cached tree, so reusing it becomes observable
+        let marker = CairoFile::parse("const CACHED = 1\n").unwrap();
+        reader.parsed_modules.borrow_mut().get_mut("a").unwrap().1 = marker.clone();
+        assert_eq!(reader.parse("a", code).unwrap(), marker);
+        // the cache is shared with derived readers
+        let derived = CodeReader::with_cwd(&reader, Path::new("b")).unwrap();
+        assert_eq!(derived.parse("a", code).unwrap(), marker);
+
+        // changed content is parsed again
+        let changed = "const X = 2\n";
+        assert_eq!(reader.parse("a", changed).unwrap(), CairoFile::parse(changed).unwrap());
+
+        reader.parsed_modules.borrow_mut().get_mut("a").unwrap().1 = marker;
+        reader.invalidate("a");
+        assert_eq!(reader.parse("a", changed).unwrap(), CairoFile::parse(changed).unwrap());
+    }
+
+    #[test]
+    fn can_read_modules_from_memory() {
+        let reader = InMemoryReader::new().with_module("a.b", "const X = 1\n").with_module_at(
+            "c",
+            "const Y = 2\n",
+            "<buffer>",
+        );
+        assert_eq!(reader.read("a.b").unwrap(), ("const X = 1\n".to_string(), "a/b.cairo".into()));
+        assert_eq!(reader.read("c").unwrap().1, PathBuf::from("<buffer>"));
+        assert!(matches!(reader.read("d").unwrap_err(), CairoError::ModuleNotFound(_)));
+        assert!(reader.with_cwd(Path::new("a")).is_none());
+    }
+
+    #[test]
+    fn can_resolve_nested_modules() {
+        let root = Path::new(&env!("CARGO_MANIFEST_DIR")).join("test-data/module-reader");
+        let reader = ModuleReader::new([root.clone()]);
+        assert_eq!(reader.find("a.b.c").unwrap(), root.join("a/b/c.cairo"));
+        // falls back to the file name
+        assert_eq!(reader.find("d.e.c").unwrap(), root.join("c.cairo"));
+        assert_eq!(reader.find("c").unwrap(), root.join("c.cairo"));
+        assert!(reader.find("a.b.d").is_none());
+        // names that aren't modules are not found
+        for invalid in ["", "foo-bar", "a..c", "../c"] {
+            assert!(reader.find(invalid).is_none(), "{}", invalid);
+        }
+        assert!(matches!(reader.read("foo-bar").unwrap_err(), CairoError::ModuleNotFound(_)));
+    }
 }
@@ -1,15 +1,192 @@
-use crate::compiler::data::{DebugInfo, ProgramHint};
+use crate::{
+    compiler::data::{DebugInfo, ProgramHint},
+    error::Result,
+};
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use serde_json::ser::{Formatter, PrettyFormatter, Serializer};
+use std::{collections::BTreeMap, io};
 
+/// A compiled program, laid out like cairo-lang's `Program`
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Program {
+    #[serde(default)]
+    pub attributes: Vec<serde_json::Value>,
     pub builtins: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compiler_version: Option<String>,
     pub data: Vec<String>,
-    pub debug_info: DebugInfo,
+    /// `null` if the program was compiled without debug info
+    #[serde(default)]
+    pub debug_info: Option<DebugInfo>,
     pub hints: BTreeMap<String, Vec<ProgramHint>>,
     pub identifiers: BTreeMap<String, serde_json::Value>,
     pub main_scope: String,
     pub prime: String,
     pub reference_manager: serde_json::Value,
 }
+
+impl Program {
+    /// Returns the program as json in the layout `cairo-compile` writes it
+    ///
+    /// `cairo-compile` dumps all objects with sorted keys. The keys are sorted here as well, since
+    /// `serde_json::Map` keeps the insertion order if its `preserve_order` feature is enabled.
+    pub fn to_compiled_json(&self) -> serde_json::Value {
+        sort_keys(serde_json::to_value(self).expect("program is valid json"))
+    }
+
+    /// Returns the program as the exact text `cairo-compile` writes to its output file
+    ///
+    /// That is `json.dump(program, indent=4, sort_keys=True)` followed by a newline.
+    pub fn to_compiled_string(&self) -> String {
+        let mut out = Vec::new();
+        let formatter = PythonFormatter(PrettyFormatter::with_indent(b"    "));
+        self.to_compiled_json()
+            .serialize(&mut Serializer::with_formatter(&mut out, formatter))
+            .expect("program is valid json");
+        out.push(b'\n');
+        String::from_utf8(out).expect("output is ascii")
+    }
+
+    /// Loads a program from the json output of `cairo-compile`
+    pub fn from_compiled_json(value: serde_json::Value) -> Result<Self> {
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+/// Sorts the keys of all objects in the json value
+fn sort_keys(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => serde_json::Value::Object(
+            map.into_iter()
+                .map(|(key, value)| (key, sort_keys(value)))
+                .collect::<BTreeMap<_, _>>()
+                .into_iter()
+                .collect(),
+        ),
+        serde_json::Value::Array(values) => {
+            serde_json::Value::Array(values.into_iter().map(sort_keys).collect())
+        }
+        value => value,
+    }
+}
+
+/// A json formatter that escapes all non ASCII characters, like python's `json.dump` does by
+/// default
+struct PythonFormatter<'a>(PrettyFormatter<'a>);
+
+impl<'a> Formatter for PythonFormatter<'a> {
+    fn begin_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.0.begin_array(writer)
+    }
+
+    fn end_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.0.end_array(writer)
+    }
+
+    fn begin_array_value<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.0.begin_array_value(writer, first)
+    }
+
+    fn end_array_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.0.end_array_value(writer)
+    }
+
+    fn begin_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.0.begin_object(writer)
+    }
+
+    fn end_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.0.end_object(writer)
+    }
+
+    fn begin_object_key<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.0.begin_object_key(writer, first)
+    }
+
+    fn begin_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.0.begin_object_value(writer)
+    }
+
+    fn end_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.0.end_object_value(writer)
+    }
+
+    fn write_string_fragment<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        fragment: &str,
+    ) -> io::Result<()> {
+        for c in fragment.chars() {
+            if c.is_ascii() {
+                writer.write_all(&[c as u8])?;
+            } else {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    write!(writer, "\\u{:04x}", unit)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn can_roundtrip_compiled_program() {
+        let path = Path::new(&env!("CARGO_MANIFEST_DIR")).join("test-data/compiled/program.json");
+        let compiled = std::fs::read_to_string(path).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&compiled).unwrap();
+        let program = Program::from_compiled_json(json.clone()).unwrap();
+        assert_eq!(program.main_scope, "__main__");
+        assert_eq!(program.compiler_version.as_deref(), Some("0.12.2"));
+        assert_eq!(program.data.len(), 182);
+        assert_eq!(program.hints.len(), 6);
+        assert!(program.debug_info.is_none());
+        assert_eq!(program.to_compiled_json(), json);
+        assert_eq!(program.to_compiled_string(), compiled);
+
+        // the keys are written in the same order as cairo-lang writes them
+        let keys: Vec<_> = match program.to_compiled_json() {
+            serde_json::Value::Object(map) => map.keys().cloned().collect(),
+            json => panic!("unexpected json {}", json),
+        };
+        assert_eq!(
+            keys,
+            [
+                "attributes",
+                "builtins",
+                "compiler_version",
+                "data",
+                "debug_info",
+                "hints",
+                "identifiers",
+                "main_scope",
+                "prime",
+                "reference_manager"
+            ]
+        );
+    }
+
+    #[test]
+    fn can_escape_non_ascii_like_cairo_compile() {
+        let path = Path::new(&env!("CARGO_MANIFEST_DIR")).join("test-data/compiled/program.json");
+        let json = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        let mut program = Program::from_compiled_json(json).unwrap();
+        let debug_info = program.debug_info.get_or_insert_with(Default::default);
+        debug_info.file_contents.insert("main.cairo".into(), "# é 𝔽\n".into());
+        let compiled = program.to_compiled_string();
+        assert!(compiled.contains(r##""main.cairo": "# \u00e9 \ud835\udd3d\n""##), "{}", compiled);
+        assert!(compiled.is_ascii());
+    }
+}
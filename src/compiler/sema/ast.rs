@@ -11,11 +11,21 @@ pub type VResult = Result<()>;
 /// A trait intended to be implemented by compiler passes that make it easier to traverse the AST
 /// and only do operations on specific nodes.
 pub trait Visitor {
-    fn visit_lang(&mut self, _: &mut Identifier) -> VResult {
+    /// Called for every directive in source order, before its specific callback
+    ///
+    /// The default dispatches to `visit_lang` or `visit_builtins`.
+    fn visit_directive(&mut self, directive: &mut Directive) -> VResult {
+        match directive {
+            Directive::Lang(loc, id) => self.visit_lang(id, *loc),
+            Directive::Builtins(loc, builtins) => self.visit_builtins(builtins, *loc),
+        }
+    }
+
+    fn visit_lang(&mut self, _: &mut Identifier, _loc: Loc) -> VResult {
         Ok(())
     }
 
-    fn visit_hint(&mut self, _: &mut String) -> VResult {
+    fn visit_hint(&mut self, _: &mut String, _loc: Loc) -> VResult {
         Ok(())
     }
 
@@ -30,6 +40,33 @@ pub trait Visitor {
         Ok(())
     }
 
+    fn visit_with_attr(&mut self, _: &mut WithAttrStatement) -> VResult {
+        Ok(())
+    }
+
+    fn visit_ap_add_assign(&mut self, _: &mut Expr, _loc: Loc) -> VResult {
+        Ok(())
+    }
+
+    /// Called for `dw expr`
+    fn visit_data_word(&mut self, _: &mut Expr, _loc: Loc) -> VResult {
+        Ok(())
+    }
+
+    /// Called for `assert lhs = rhs`
+    fn visit_assert(&mut self, _lhs: &mut Expr, _rhs: &mut Expr, _loc: Loc) -> VResult {
+        Ok(())
+    }
+
+    /// Called for `static_assert lhs == rhs`
+    fn visit_static_assert(&mut self, _lhs: &mut Expr, _rhs: &mut Expr, _loc: Loc) -> VResult {
+        Ok(())
+    }
+
+    fn visit_return(&mut self, _: &mut [ExprAssignment], _loc: Loc) -> VResult {
+        Ok(())
+    }
+
     fn visit_label(&mut self, _: &mut Identifier, _loc: Loc) -> VResult {
         Ok(())
     }
@@ -76,6 +113,10 @@ pub trait Visitor {
         Ok(())
     }
 
+    fn visit_function_call(&mut self, _: &mut FunctionCall) -> VResult {
+        Ok(())
+    }
+
     fn enter_namespace(&mut self, _: &mut Namespace) -> VResult {
         Ok(())
     }
@@ -146,6 +187,68 @@ impl<T: Visitable> Visitable for Vec<T> {
     }
 }
 
+/// A read-only counterpart of `Visitor` for analyses that don't modify the AST
+pub trait RefVisitor {
+    fn visit_instruction(&mut self, _: &Instruction) -> VResult {
+        Ok(())
+    }
+
+    fn visit_import(&mut self, _: &ImportDirective) -> VResult {
+        Ok(())
+    }
+
+    fn visit_const_def(&mut self, _: &ConstantDef) -> VResult {
+        Ok(())
+    }
+
+    fn visit_struct_def(&mut self, _: &StructDef) -> VResult {
+        Ok(())
+    }
+
+    fn visit_function_call(&mut self, _: &FunctionCall) -> VResult {
+        Ok(())
+    }
+
+    /// Called for every type in source order, nested types are not visited separately
+    fn visit_type(&mut self, _: &CairoType) -> VResult {
+        Ok(())
+    }
+
+    fn visit_expr_identifier(&mut self, _: &Identifier, _: Loc) -> VResult {
+        Ok(())
+    }
+
+    fn enter_function(&mut self, _: &FunctionDef) -> VResult {
+        Ok(())
+    }
+
+    fn exit_function(&mut self, _: &FunctionDef) -> VResult {
+        Ok(())
+    }
+
+    fn enter_namespace(&mut self, _: &Namespace) -> VResult {
+        Ok(())
+    }
+
+    fn exit_namespace(&mut self, _: &Namespace) -> VResult {
+        Ok(())
+    }
+}
+
+/// Like `Visitable` but walks the AST by shared reference with a `RefVisitor`
+pub trait Walk {
+    fn walk(&self, v: &mut dyn RefVisitor) -> VResult;
+}
+
+impl<T: Walk> Walk for Vec<T> {
+    fn walk(&self, v: &mut dyn RefVisitor) -> VResult {
+        for t in self {
+            t.walk(v)?;
+        }
+        Ok(())
+    }
+}
+
 /// A visitor that returns the %lang directive of a cairo file
 #[derive(Default)]
 pub struct LangVisitor(Option<String>);
@@ -158,10 +261,10 @@ impl LangVisitor {
     }
 }
 impl Visitor for LangVisitor {
-    fn visit_lang(&mut self, id: &mut Identifier) -> VResult {
+    fn visit_lang(&mut self, id: &mut Identifier, loc: Loc) -> VResult {
         let id = id.join(".");
         if self.0.is_some() {
-            return Err(CairoError::msg(format!("Found two %lang directives {}", id)))
+            return Err(CairoError::msg(format!("Found two %lang directives {}", id)).spanned(loc))
         }
         self.0 = Some(id);
         Ok(())
@@ -185,6 +288,11 @@ impl ScopeTracker {
         self.accessible_scopes.pop();
     }
 
+    /// Exits all scopes that were entered after the tracker had `depth` accessible scopes
+    pub fn exit_scopes_to(&mut self, depth: usize) {
+        self.accessible_scopes.truncate(depth);
+    }
+
     pub fn enter_lang(&mut self, lang: Option<String>) {
         self.tmp_lang = lang;
         std::mem::swap(&mut self.file_lang, &mut self.tmp_lang)
@@ -284,3 +392,40 @@ pub struct StructDefinition {
     pub size: u64,
     pub loc: Loc,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct DirectiveRecorder(Vec<String>);
+
+    impl Visitor for DirectiveRecorder {
+        fn visit_directive(&mut self, directive: &mut Directive) -> VResult {
+            self.0.push(directive.to_string());
+            match directive {
+                Directive::Lang(loc, id) => self.visit_lang(id, *loc),
+                Directive::Builtins(loc, builtins) => self.visit_builtins(builtins, *loc),
+            }
+        }
+
+        fn visit_builtins(&mut self, builtins: &mut [Builtin], _: Loc) -> VResult {
+            self.0.push(format!("{} builtins", builtins.len()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn can_visit_directives_in_order() {
+        let mut file = CairoFile::parse(
+            "%lang starknet\n%builtins output pedersen\nfunc main():\n    ret\nend\n",
+        )
+        .unwrap();
+        let mut recorder = DirectiveRecorder::default();
+        file.visit(&mut recorder).unwrap();
+        assert_eq!(recorder.0, ["%lang starknet", "%builtins output pedersen", "2 builtins"]);
+
+        // the default still dispatches to the specific callbacks
+        assert_eq!(LangVisitor::lang(&mut file).unwrap(), Some("starknet".to_string()));
+    }
+}
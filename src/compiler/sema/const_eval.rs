@@ -0,0 +1,266 @@
+//! Evaluation of constant expressions
+use crate::{
+    compiler::sema::{
+        identifiers::{IdentifierDefinitionType, Identifiers},
+        ScopedName,
+    },
+    error::{CairoError, Result},
+    parser::ast::{Expr, ExprAssignment, Identifier, Loc},
+};
+use ethers::types::{Sign, I256, U256, U512};
+use std::convert::TryFrom;
+
+/// Folds the integer arithmetic of a constant expression into a single value modulo `prime`
+///
+/// Identifiers are resolved in the current accessible scopes and must refer to constants that were
+/// already evaluated.
+pub fn eval_const(expr: &Expr, prime: &U256, identifiers: &Identifiers) -> Result<U256> {
+    let value = match expr {
+        Expr::Int(_, loc) | Expr::HexInt(_, loc) => {
+            expr.as_u256().ok_or_else(|| {
+                CairoError::Preprocess(format!("Invalid number {} {}", expr, loc)).spanned(*loc)
+            })? % prime
+        }
+        Expr::ShortString(_, _) => expr.short_string_value()? % prime,
+        Expr::Id(id, loc) => const_value(id, *loc, identifiers)?,
+        Expr::Parentheses(exprs, loc) => match exprs.as_slice() {
+            [ExprAssignment::Expr(expr, _)] => eval_const(expr, prime, identifiers)?,
+            _ => {
+                return Err(CairoError::Preprocess(format!(
+                    "Tuples are not supported in constant expressions {}",
+                    loc
+                ))
+                .spanned(*loc))
+            }
+        },
+        Expr::Neg(expr, _) => neg_mod(eval_const(expr, prime, identifiers)?, prime),
+        Expr::Add(lhs, rhs, _) => {
+            let (lhs, rhs) =
+                (eval_const(lhs, prime, identifiers)?, eval_const(rhs, prime, identifiers)?);
+            add_mod(lhs, rhs, prime)
+        }
+        Expr::Sub(lhs, rhs, _) => {
+            let (lhs, rhs) =
+                (eval_const(lhs, prime, identifiers)?, eval_const(rhs, prime, identifiers)?);
+            add_mod(lhs, neg_mod(rhs, prime), prime)
+        }
+        Expr::Mul(lhs, rhs, _) => {
+            let (lhs, rhs) =
+                (eval_const(lhs, prime, identifiers)?, eval_const(rhs, prime, identifiers)?);
+            mul_mod(lhs, rhs, prime)
+        }
+        Expr::Div(lhs, rhs, loc) => {
+            let (lhs, rhs) =
+                (eval_const(lhs, prime, identifiers)?, eval_const(rhs, prime, identifiers)?);
+            if rhs.is_zero() {
+                return Err(CairoError::Preprocess(format!("Division by zero {}", loc)).spanned(*loc))
+            }
+            mul_mod(lhs, inv_mod(rhs, prime), prime)
+        }
+        Expr::Pow(lhs, rhs, loc) => {
+            let base = eval_const(lhs, prime, identifiers)?;
+            let exp = eval_exponent(rhs, identifiers)?;
+            if exp.is_negative() {
+                if base.is_zero() {
+                    return Err(
+                        CairoError::Preprocess(format!("Division by zero {}", loc)).spanned(*loc)
+                    )
+                }
+                pow_mod(inv_mod(base, prime), exp.unsigned_abs(), prime)
+            } else {
+                pow_mod(base, exp.unsigned_abs(), prime)
+            }
+        }
+        expr => {
+            return Err(CairoError::Preprocess(format!(
+                "Expected a constant expression but found `{}`",
+                expr
+            )))
+        }
+    };
+    Ok(value)
+}
+
+/// Returns the value of the constant the identifier refers to
+///
+/// `<struct>.SIZE` is the size of the struct.
+fn const_value(id: &Identifier, loc: Loc, identifiers: &Identifiers) -> Result<U256> {
+    let name = ScopedName::new(id.clone());
+    let resolved = identifiers.search_current_scopes(&name)?;
+    match resolved.ty.as_ref() {
+        IdentifierDefinitionType::ConstDef(Some(val)) if resolved.rem.is_none() => Ok(*val),
+        IdentifierDefinitionType::Struct(Some(def))
+            if resolved.rem.as_ref() == Some(&ScopedName::size()) =>
+        {
+            Ok(def.size.into())
+        }
+        _ => Err(CairoError::Preprocess(format!("Identifier {} is not a constant {}", name, loc))
+            .spanned(loc)),
+    }
+}
+
+/// Evaluates the exponent of a `**` as a signed integer, without reducing it modulo the prime
+///
+/// Constants are used with their value in the field, so a constant defined as `-1` is `prime - 1`
+/// in an exponent.
+fn eval_exponent(expr: &Expr, identifiers: &Identifiers) -> Result<I256> {
+    let out_of_range = || CairoError::Preprocess(format!("Exponent `{}` is out of range", expr));
+    let value = match expr {
+        Expr::Int(_, _) | Expr::HexInt(_, _) | Expr::Id(_, _) => {
+            let value = match expr {
+                Expr::Id(id, loc) => const_value(id, *loc, identifiers)?,
+                _ => expr.as_u256().ok_or_else(out_of_range)?,
+            };
+            I256::checked_from_sign_and_abs(Sign::Positive, value).ok_or_else(out_of_range)?
+        }
+        Expr::Parentheses(exprs, _) => match exprs.as_slice() {
+            [ExprAssignment::Expr(expr, _)] => eval_exponent(expr, identifiers)?,
+            _ => return Err(CairoError::Preprocess(format!("Invalid exponent `{}`", expr))),
+        },
+        Expr::Neg(expr, _) => {
+            eval_exponent(expr, identifiers)?.checked_neg().ok_or_else(out_of_range)?
+        }
+        Expr::Add(lhs, rhs, _) => eval_exponent(lhs, identifiers)?
+            .checked_add(eval_exponent(rhs, identifiers)?)
+            .ok_or_else(out_of_range)?,
+        Expr::Sub(lhs, rhs, _) => eval_exponent(lhs, identifiers)?
+            .checked_sub(eval_exponent(rhs, identifiers)?)
+            .ok_or_else(out_of_range)?,
+        Expr::Mul(lhs, rhs, _) => eval_exponent(lhs, identifiers)?
+            .checked_mul(eval_exponent(rhs, identifiers)?)
+            .ok_or_else(out_of_range)?,
+        Expr::Pow(lhs, rhs, _) => {
+            let exp = eval_exponent(rhs, identifiers)?;
+            let exp = u32::try_from(exp).map_err(|_| out_of_range())?;
+            eval_exponent(lhs, identifiers)?.checked_pow(exp).ok_or_else(out_of_range)?
+        }
+        _ => return Err(CairoError::Preprocess(format!("Invalid exponent `{}`", expr))),
+    };
+    Ok(value)
+}
+
+/// `-val mod prime` for `val < prime`
+fn neg_mod(val: U256, prime: &U256) -> U256 {
+    if val.is_zero() {
+        val
+    } else {
+        *prime - val
+    }
+}
+
+/// `lhs + rhs mod prime` for `lhs, rhs < prime`
+fn add_mod(lhs: U256, rhs: U256, prime: &U256) -> U256 {
+    let (sum, overflow) = lhs.overflowing_add(rhs);
+    if overflow || sum >= *prime {
+        sum.overflowing_sub(*prime).0
+    } else {
+        sum
+    }
+}
+
+/// `lhs * rhs mod prime`
+fn mul_mod(lhs: U256, rhs: U256, prime: &U256) -> U256 {
+    let product = lhs.full_mul(rhs) % U512::from(*prime);
+    U256::try_from(product).expect("is less than prime")
+}
+
+/// The inverse of a non zero `val` in the field, `val^(prime - 2)` since `prime` is prime
+fn inv_mod(val: U256, prime: &U256) -> U256 {
+    pow_mod(val, *prime - 2, prime)
+}
+
+/// `base ^ exp mod prime` by repeated squaring
+fn pow_mod(mut base: U256, mut exp: U256, prime: &U256) -> U256 {
+    let mut result = U256::one() % prime;
+    while !exp.is_zero() {
+        if exp.bit(0) {
+            result = mul_mod(result, base, prime);
+        }
+        base = mul_mod(base, base, prime);
+        exp >>= 1;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compiler::constants::DEFAULT_PRIME, parser::ast::CairoFile};
+
+    fn eval(expr: &str) -> Result<U256> {
+        let file = CairoFile::parse(&format!("const X = {}\n", expr)).unwrap();
+        match &file.0[0] {
+            crate::parser::ast::Instruction::Const(c) => {
+                eval_const(&c.init, &DEFAULT_PRIME, &Identifiers::default())
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn can_fold_arithmetic() {
+        assert_eq!(eval("(1 + 2) * 3").unwrap(), U256::from(9));
+        assert_eq!(eval("2 ** 10 - 24").unwrap(), U256::from(1000));
+        assert_eq!(eval("0x10 + 'a'").unwrap(), U256::from(16 + 97));
+        assert_eq!(eval("-1").unwrap(), DEFAULT_PRIME - 1);
+        assert_eq!(eval("0 - 2 + 3").unwrap(), U256::one());
+        assert_eq!(eval("6 / 3").unwrap(), U256::from(2));
+    }
+
+    #[test]
+    fn can_fold_hex_literals() {
+        assert_eq!(eval("0xff").unwrap(), U256::from(255));
+        assert_eq!(eval("0x0").unwrap(), U256::zero());
+        assert_eq!(eval("0x10 * 2").unwrap(), U256::from(32));
+        assert_eq!(eval("-0x1").unwrap(), DEFAULT_PRIME - 1);
+        // values larger than the prime are reduced
+        let prime_plus_one = format!("{:#x}", DEFAULT_PRIME + 1);
+        assert_eq!(eval(&prime_plus_one).unwrap(), U256::one());
+
+        assert_eq!(Expr::parse("0xff").unwrap().as_u256(), Some(U256::from(255)));
+        assert_eq!(
+            Expr::HexInt("0x1_00".to_string(), Default::default()).as_u256(),
+            Some(256.into())
+        );
+        assert_eq!(Expr::Int(255.into(), Default::default()).as_u256(), Some(255.into()));
+        let too_large = format!("0x1{}", "0".repeat(64));
+        assert_eq!(Expr::HexInt(too_large, Default::default()).as_u256(), None);
+    }
+
+    #[test]
+    fn can_divide_in_field() {
+        assert_eq!(eval("1 / 2").unwrap(), (DEFAULT_PRIME + 1) / 2);
+        assert_eq!(eval("(1 / 3) * 3").unwrap(), U256::one());
+        let err = eval("1 / 0").unwrap_err();
+        assert!(err.to_string().starts_with("Division by zero"), "{}", err);
+        let err = eval("1 / (2 - 2)").unwrap_err();
+        assert!(err.to_string().starts_with("Division by zero"), "{}", err);
+    }
+
+    #[test]
+    fn can_raise_to_large_powers() {
+        assert_eq!(eval("2 ** 251").unwrap(), U256::one() << 251);
+        // negative exponents raise the inverse of the base
+        let inv_7 = eval("1 / 7").unwrap();
+        assert_eq!(eval("7 ** (-1)").unwrap(), inv_7);
+        assert_eq!(eval("7 ** -1").unwrap(), inv_7);
+        assert_eq!(eval("7 ** -2").unwrap(), mul_mod(inv_7, inv_7, &DEFAULT_PRIME));
+        assert_eq!(eval("7 ** (1 - 2) * 7").unwrap(), U256::one());
+        // the exponent is not reduced modulo the prime
+        let p_minus_one = format!("{:#x}", DEFAULT_PRIME - 1);
+        assert_eq!(eval(&format!("7 ** {}", p_minus_one)).unwrap(), U256::one());
+        let err = eval("0 ** -1").unwrap_err();
+        assert!(err.to_string().starts_with("Division by zero"), "{}", err);
+        assert!(eval("2 ** [ap]").is_err());
+        assert!(eval("2 ** (2 ** 255)").is_err());
+        // `**` is right associative and binds stronger than unary minus
+        assert_eq!(eval("2 ** 3 ** 2").unwrap(), 512.into());
+        assert_eq!(eval("-2 ** 2").unwrap(), DEFAULT_PRIME - 4);
+        assert_eq!(eval("(-2) ** 2").unwrap(), 4.into());
+        assert_eq!(eval("+5").unwrap(), 5.into());
+        assert_eq!(
+            eval("3 ** (2 ** 200)").unwrap(),
+            pow_mod(3.into(), U256::one() << 200, &DEFAULT_PRIME)
+        );
+    }
+}
@@ -1,10 +1,14 @@
+use ethers::types::U256;
+use serde_json::json;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
     rc::Rc,
 };
 
 use crate::{
     compiler::{
+        constants::{ARG_SCOPE, IMPLICIT_ARG_SCOPE, N_LOCALS_CONSTANT, RETURN_SCOPE},
         sema::{
             ast::{ScopeTracker, StructDefinition},
             ScopedName,
@@ -12,25 +16,94 @@ use crate::{
         VResult, Visitor,
     },
     error::{CairoError, Result},
-    parser::ast::{CairoType, FunctionDef, Loc, Namespace, PointerType, TypeStruct},
+    parser::ast::{CairoType, Decorator, FunctionDef, Loc, Namespace, PointerType, TypeStruct},
 };
 
 /// Manages a list of identifiers and types
-#[derive(Debug, Default)]
+///
+/// Cloning is cheap-ish since all definitions are shared.
+#[derive(Debug, Clone)]
 pub struct Identifiers {
     /// keeps track of the scopes while traversing the AST
     pub(crate) scope_tracker: ScopeTracker,
     pub(crate) root: Scope,
     pub(crate) identifiers: HashMap<ScopedName, Rc<IdentifierDefinitionType>>,
+    /// where identifiers were defined
+    pub(crate) locations: HashMap<ScopedName, Loc>,
+    /// the decorators of all functions
+    pub(crate) decorators: HashMap<ScopedName, Vec<Decorator>>,
+    /// how many aliases to follow at most when resolving an identifier
+    pub(crate) max_alias_depth: usize,
 }
 
 impl Identifiers {
+    /// The default limit for the number of aliases resolved in a row
+    pub const DEFAULT_MAX_ALIAS_DEPTH: usize = 100;
+
+    /// Sets the maximum number of aliases that are followed when resolving an identifier
+    pub fn with_max_alias_depth(mut self, max_alias_depth: usize) -> Self {
+        self.max_alias_depth = max_alias_depth;
+        self
+    }
+
     pub fn resolved_identifiers(
         &self,
     ) -> impl Iterator<Item = (&ScopedName, &Rc<IdentifierDefinitionType>)> {
         self.identifiers.iter().filter(|(_, id)| !id.is_unresolved())
     }
 
+    /// Returns the identifiers in the format of the `identifiers` of a compiled program
+    ///
+    /// `labels` are the program counters of functions and labels, see `LabelOffsetPass`. Constants
+    /// and structs are left out until they are resolved.
+    ///
+    /// NOTE: references only have their `full_name`, the `cairo_type` and `references` cairo-lang
+    /// emits are missing, since the compiler does not track the types and the flow of references.
+    pub fn to_program_identifiers(
+        &self,
+        labels: &HashMap<ScopedName, usize>,
+    ) -> BTreeMap<String, serde_json::Value> {
+        self.identifiers
+            .iter()
+            .filter(|(_, ty)| {
+                !matches!(
+                    ty.as_unresolved(),
+                    Some(
+                        IdentifierDefinitionType::ConstDef(_) | IdentifierDefinitionType::Struct(_)
+                    )
+                )
+            })
+            .map(|(name, ty)| {
+                let ty = ty.as_unresolved().unwrap_or(ty);
+                let mut json = ty.to_json();
+                if ty.is_function() {
+                    json["decorators"] =
+                        self.function_decorators(name).iter().map(Decorator::name).collect();
+                }
+                if ty.is_function() || ty.is_label() {
+                    if let Some(pc) = labels.get(name) {
+                        json["pc"] = (*pc).into();
+                    }
+                }
+                if ty.is_reference() || ty.is_local_var() || ty.is_temp_var() || ty.is_rvalue_ref()
+                {
+                    json["full_name"] = name.name().into();
+                }
+                (name.name(), json)
+            })
+            .collect()
+    }
+
+    /// Sets the decorators of the function with the given full name
+    pub fn set_function_decorators(&mut self, function: ScopedName, decorators: Vec<Decorator>) {
+        self.decorators.insert(function, decorators);
+    }
+
+    /// Returns the decorators of the function with the given full name
+    pub fn function_decorators(&self, function: &ScopedName) -> &[Decorator] {
+        self.decorators.get(function).map(Vec::as_slice).unwrap_or_default()
+    }
+
     /// adds the given identifier def with the name to the current scope
     pub fn add_identifier(&mut self, name: ScopedName, ty: IdentifierDefinitionType) {
         let ty = Rc::new(ty);
@@ -38,8 +111,30 @@ impl Identifiers {
         self.identifiers.insert(dest, ty);
     }
 
+    /// Same as `add_identifier` but also records where the identifier was defined
+    pub fn add_identifier_at(&mut self, name: ScopedName, ty: IdentifierDefinitionType, loc: Loc) {
+        self.locations.insert(name.clone(), loc);
+        self.add_identifier(name, ty)
+    }
+
+    /// Returns the location of the definition of the identifier with the given full name
+    ///
+    /// NOTE: no aliasing at this point
+    pub fn definition_loc_by_full_name(&self, name: &ScopedName) -> Option<Loc> {
+        self.locations.get(name).copied()
+    }
+
+    /// Returns the location where the identifier is declared, following aliases
+    pub fn definition_location(&self, name: &ScopedName) -> Option<Loc> {
+        let resolved = self.get(name).ok()?;
+        if !resolved.is_fully_parsed() {
+            return None
+        }
+        self.definition_loc_by_full_name(&resolved.canonical_name)
+    }
+
     /// Resolves a `CairoType` to a fully qualified name
-    pub fn resolve_type(&mut self, cairo_type: CairoType) -> Result<CairoType> {
+    pub fn resolve_type(&self, cairo_type: CairoType) -> Result<CairoType> {
         let ty = match cairo_type {
             CairoType::Felt => CairoType::Felt,
             CairoType::Id(ty) => {
@@ -59,11 +154,11 @@ impl Identifiers {
             CairoType::Tuple(tuple) => CairoType::Tuple(
                 tuple.into_iter().map(|ty| self.resolve_type(ty)).collect::<Result<_>>()?,
             ),
-            CairoType::Pointer(ty) => {
-                let is_single = ty.is_single();
-                let ty = self.resolve_type(ty.into_pointee())?;
-                let pointer =
-                    if is_single { PointerType::Single(ty) } else { PointerType::Double(ty) };
+            CairoType::Pointer(ptr) => {
+                let pointer = match *ptr {
+                    PointerType::Single(ty) => PointerType::Single(self.resolve_type(ty)?),
+                    PointerType::Double(ty) => PointerType::Double(self.resolve_type(ty)?),
+                };
                 CairoType::Pointer(Box::new(pointer))
             }
         };
@@ -78,7 +173,7 @@ impl Identifiers {
             Ok(def.canonical_name)
         } else {
             Err(CairoError::Preprocess(format!(
-                "Expected {} to a a struct, found {:?}",
+                "Expected {} to be a struct, found {}",
                 def.canonical_name, def.ty
             )))
         }
@@ -89,7 +184,7 @@ impl Identifiers {
         let def = self.search(struct_name, self.scope_tracker.accessible_scopes())?;
         if !def.is_fully_parsed() {
             return Err(CairoError::Identifier(format!(
-                "Unexpected remainder {:?} for {} of ty {:?}",
+                "Unexpected remainder {:?} for {} of type {}",
                 def.rem, def.canonical_name, def.ty
             )))
         }
@@ -97,7 +192,7 @@ impl Identifiers {
             Ok(struct_def)
         } else {
             Err(CairoError::Preprocess(format!(
-                "Expected {} to be a struct definition but found {:?}",
+                "Expected {} to be a struct definition but found {}",
                 def.canonical_name, def.ty
             )))
         }
@@ -123,14 +218,54 @@ impl Identifiers {
         }
     }
 
+    /// Returns the signature of the function with the given name, resolving aliases
+    ///
+    /// The argument and return structs must be collected already.
+    pub fn get_function(&self, name: &ScopedName) -> Result<FunctionSignature> {
+        let def = self.get(name)?;
+        // functions remain unresolved until their body was preprocessed
+        let is_function =
+            def.ty.is_function() || def.ty.as_unresolved().is_some_and(|ty| ty.is_function());
+        if !def.is_fully_parsed() || !is_function {
+            return Err(CairoError::Definition(
+                name.clone(),
+                IdentifierDefinitionType::Function,
+                def.ty.as_ref().clone(),
+            ))
+        }
+        let full_name = def.canonical_name;
+        let implicit_args = full_name.clone().appended(IMPLICIT_ARG_SCOPE);
+        let implicit_args = if self.get_by_full_name(&implicit_args).is_some() {
+            Some(self.get_struct_definition_no_alias(&implicit_args)?)
+        } else {
+            None
+        };
+        let sizeof_locals = self
+            .get_by_full_name(&full_name.clone().appended(N_LOCALS_CONSTANT))
+            .and_then(|ty| match ty.as_ref() {
+                IdentifierDefinitionType::ConstDef(value) => *value,
+                _ => None,
+            });
+        Ok(FunctionSignature {
+            args: self.get_struct_definition_no_alias(&full_name.clone().appended(ARG_SCOPE))?,
+            implicit_args,
+            return_values: self
+                .get_struct_definition_no_alias(&full_name.clone().appended(RETURN_SCOPE))?,
+            sizeof_locals,
+            full_name,
+        })
+    }
+
     pub fn get_struct_size(&self, struct_name: &ScopedName) -> Result<u64> {
         Ok(self.get_struct_definition(struct_name)?.size)
     }
 
     /// Returns the size of the given type
     pub fn get_size(&self, cairo_type: &CairoType) -> Result<u64> {
+        if let Some(size) = cairo_type.builtin_size() {
+            return Ok(size)
+        }
         match cairo_type {
-            CairoType::Felt => Ok(CairoType::FELT_SIZE),
             CairoType::Id(type_struct) => {
                 let scope = ScopedName::new(type_struct.name.clone());
                 if type_struct.is_fully_resolved {
@@ -141,13 +276,17 @@ impl Identifiers {
                 }
             }
             CairoType::Tuple(tuple) => {
-                let mut size = 0;
+                let mut size = 0u64;
                 for ty in tuple {
-                    size += self.get_size(ty)?;
+                    size = size
+                        .checked_add(self.get_size(ty)?)
+                        .ok_or_else(|| CairoError::TypeTooLarge(cairo_type.clone()))?;
                 }
                 Ok(size)
             }
-            CairoType::Pointer(_) => Ok(CairoType::POINTER_SIZE),
+            CairoType::Felt | CairoType::Pointer(_) => {
+                unreachable!("types without structs have a builtin size")
+            }
         }
     }
 
@@ -169,7 +308,7 @@ impl Identifiers {
                     // check whether if we're currently at the first item in the name of in the
                     // scope itself, in which case continue to the next accessible scope
                     let (name, _) = name.clone().rev_split();
-                    if scope.as_ref().clone().extended(name).name().starts_with(&err.name()) {
+                    if scope.as_ref().clone().extended(name).starts_with(&err) {
                         continue
                     } else {
                         return Err(CairoError::MissingIdentifier(err))
@@ -196,7 +335,7 @@ impl Identifiers {
                     // check whether if we're currently at the first item in the name of in the
                     // scope itself, in which case continue to the next accessible scope
                     let (name, _) = name.clone().rev_split();
-                    if scope.as_ref().clone().extended(name).name().starts_with(&err.name()) {
+                    if scope.as_ref().clone().extended(name).starts_with(&err) {
                         continue
                     } else {
                         return Err(CairoError::MissingIdentifier(err))
@@ -221,12 +360,16 @@ impl Identifiers {
             if let Some(unresolved) = def.as_unresolved() {
                 if !ty.has_matching_type(unresolved) {
                     return Err(CairoError::Preprocess(format!(
-                        "Expected Identifier {} to be a {:?} but is {:?}",
+                        "Expected Identifier {} to be a {} but is {}",
                         name, unresolved, ty
                     )))
                 }
             } else {
-                return Err(CairoError::Redefinition(name.clone(), loc))
+                return Err(CairoError::Redefinition {
+                    first: self.definition_loc_by_full_name(&name).unwrap_or_default(),
+                    name,
+                    second: loc,
+                })
             }
         } else if require_registered_type {
             return Err(CairoError::Preprocess(format!("Identifier {} not found", name)))
@@ -249,6 +392,9 @@ impl Identifiers {
             if let Some(rem) = resolved.rem {
                 current_identifier = current_identifier.extended(rem);
             }
+            if visited_identifiers.len() > self.max_alias_depth {
+                return Err(CairoError::AliasDepthExceeded(name.clone()))
+            }
             // check for cycles
             if visited_identifiers.contains(&current_identifier) {
                 return Err(CairoError::Identifier(format!(
@@ -270,6 +416,9 @@ impl Identifiers {
             if visited_identifiers.contains(&current_identifier) {
                 break
             }
+            if visited_identifiers.len() > self.max_alias_depth {
+                return Err(CairoError::AliasDepthExceeded(name.clone()))
+            }
             visited_identifiers.insert(current_identifier.clone());
 
             match self.root.get_scope(&current_identifier) {
@@ -294,6 +443,44 @@ impl Identifiers {
         )))
     }
 
+    /// Returns the identifiers that are defined directly in the given scope, sorted by name
+    ///
+    /// Aliases to the scope are followed. Subscopes without a definition of their own, like the
+    /// namespaces of a `ScopedName` that was added as a whole, are listed as
+    /// `IdentifierDefinitionType::Namespace`.
+    pub fn members_of(&self, name: &ScopedName) -> Result<Vec<(String, IdentifierDefinitionType)>> {
+        let scope = self.get_scope(name)?;
+        let mut members = scope
+            .identifiers
+            .iter()
+            .map(|(name, ty)| (name.clone(), ty.as_ref().clone()))
+            .chain(
+                scope
+                    .subscopes
+                    .keys()
+                    .filter(|name| !scope.identifiers.contains_key(*name))
+                    .map(|name| (name.clone(), IdentifierDefinitionType::Namespace)),
+            )
+            .collect::<Vec<_>>();
+        members.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(members)
+    }
+
+    /// Returns the names of all identifiers of `other` that don't exist or are defined differently
+    /// in `self`, sorted by name
+    ///
+    /// This can be used to compare snapshots taken before and after a pass.
+    pub fn diff(&self, other: &Self) -> Vec<ScopedName> {
+        let mut names = other
+            .identifiers
+            .iter()
+            .filter(|(name, ty)| self.identifiers.get(*name) != Some(*ty))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        names.sort_by_key(ScopedName::name);
+        names
+    }
+
     /// Returns the definition of an identifier
     ///
     /// NOTE: no aliasing at this point
@@ -312,6 +499,10 @@ impl Identifiers {
         self.scope_tracker.current_scope()
     }
 
+    pub fn accessible_scopes(&self) -> &[Rc<ScopedName>] {
+        self.scope_tracker.accessible_scopes()
+    }
+
     pub fn scope_tracker_mut(&mut self) -> &mut ScopeTracker {
         &mut self.scope_tracker
     }
@@ -335,8 +526,22 @@ impl Visitor for Identifiers {
     }
 }
 
+/// The resolved argument and return value structs of a function
+#[derive(Debug, Clone)]
+pub struct FunctionSignature {
+    pub full_name: ScopedName,
+    /// the `Args` struct
+    pub args: Rc<StructDefinition>,
+    /// the `ImplicitArgs` struct, if the function has implicit arguments
+    pub implicit_args: Option<Rc<StructDefinition>>,
+    /// the `Return` struct
+    pub return_values: Rc<StructDefinition>,
+    /// the value of `SIZEOF_LOCALS` once evaluated
+    pub sizeof_locals: Option<U256>,
+}
+
 /// A scope of identifiers
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Scope {
     /// name of the scope
     pub full_name: ScopedName,
@@ -392,9 +597,7 @@ impl Scope {
 
         let (name, rem) = name.clone().split();
         if let Some(scope) = self.get_single_scope(&name) {
-            if let Some(rem) = rem {
-                return scope.get_scope(&rem)
-            }
+            return if let Some(rem) = rem { scope.get_scope(&rem) } else { Ok(scope) }
         }
 
         let full_name = self.full_name.clone().appended(name.clone());
@@ -430,6 +633,19 @@ impl Scope {
     }
 }
 
+impl Default for Identifiers {
+    fn default() -> Self {
+        Self {
+            scope_tracker: Default::default(),
+            root: Default::default(),
+            identifiers: Default::default(),
+            locations: Default::default(),
+            decorators: Default::default(),
+            max_alias_depth: Identifiers::DEFAULT_MAX_ALIAS_DEPTH,
+        }
+    }
+}
+
 impl Default for Scope {
     fn default() -> Self {
         Scope::new(ScopedName::root())
@@ -445,7 +661,8 @@ pub struct IdentifierDef {
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum IdentifierDefinitionType {
-    ConstDef,
+    /// A constant and its value once evaluated
+    ConstDef(Option<U256>),
     Label,
     Reference,
     LocalVar,
@@ -459,8 +676,52 @@ pub enum IdentifierDefinitionType {
 }
 
 impl IdentifierDefinitionType {
+    /// Returns the json representation cairo-lang uses for identifiers in a compiled program
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            IdentifierDefinitionType::ConstDef(None) => json!({ "type": "const" }),
+            IdentifierDefinitionType::ConstDef(Some(value)) => {
+                let value = value.to_string().parse::<serde_json::Number>().expect("valid number");
+                json!({ "type": "const", "value": value })
+            }
+            IdentifierDefinitionType::Label => json!({ "type": "label" }),
+            IdentifierDefinitionType::Function => json!({ "type": "function" }),
+            IdentifierDefinitionType::Namespace => json!({ "type": "namespace" }),
+            IdentifierDefinitionType::Reference |
+            IdentifierDefinitionType::LocalVar |
+            IdentifierDefinitionType::TempVar |
+            IdentifierDefinitionType::RValueRef => json!({ "type": "reference" }),
+            IdentifierDefinitionType::Struct(None) => json!({ "type": "struct" }),
+            IdentifierDefinitionType::Struct(Some(def)) => {
+                let members = def
+                    .members
+                    .iter()
+                    .map(|member| {
+                        (
+                            member.name.clone(),
+                            json!({
+                                "cairo_type": member.cairo_type.to_string(),
+                                "offset": member.offset
+                            }),
+                        )
+                    })
+                    .collect::<serde_json::Map<_, _>>();
+                json!({
+                    "type": "struct",
+                    "full_name": def.full_name.name(),
+                    "members": members,
+                    "size": def.size
+                })
+            }
+            IdentifierDefinitionType::Alias(destination) => {
+                json!({ "type": "alias", "destination": destination.name() })
+            }
+            IdentifierDefinitionType::Unresolved(inner) => inner.to_json(),
+        }
+    }
+
     pub fn is_const(&self) -> bool {
-        matches!(self, IdentifierDefinitionType::ConstDef)
+        matches!(self, IdentifierDefinitionType::ConstDef(_))
     }
     pub fn is_label(&self) -> bool {
         matches!(self, IdentifierDefinitionType::Label)
@@ -535,7 +796,7 @@ impl IdentifierDefinitionType {
 
     pub fn has_matching_type(&self, other: &IdentifierDefinitionType) -> bool {
         match self {
-            IdentifierDefinitionType::ConstDef => other.is_const(),
+            IdentifierDefinitionType::ConstDef(_) => other.is_const(),
             IdentifierDefinitionType::Label => other.is_label(),
             IdentifierDefinitionType::Reference => other.is_reference(),
             IdentifierDefinitionType::LocalVar => other.is_local_var(),
@@ -550,6 +811,24 @@ impl IdentifierDefinitionType {
     }
 }
 
+impl fmt::Display for IdentifierDefinitionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdentifierDefinitionType::ConstDef(_) => f.write_str("const"),
+            IdentifierDefinitionType::Label => f.write_str("label"),
+            IdentifierDefinitionType::Reference => f.write_str("reference"),
+            IdentifierDefinitionType::LocalVar => f.write_str("local"),
+            IdentifierDefinitionType::Function => f.write_str("function"),
+            IdentifierDefinitionType::Namespace => f.write_str("namespace"),
+            IdentifierDefinitionType::Struct(_) => f.write_str("struct"),
+            IdentifierDefinitionType::TempVar => f.write_str("tempvar"),
+            IdentifierDefinitionType::RValueRef => f.write_str("rvalue reference"),
+            IdentifierDefinitionType::Alias(dest) => write!(f, "alias to {}", dest),
+            IdentifierDefinitionType::Unresolved(ty) => write!(f, "unresolved {}", ty),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ResolvedIdentifier {
     pub ty: Rc<IdentifierDefinitionType>,
@@ -562,3 +841,173 @@ impl ResolvedIdentifier {
         self.rem.is_none()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ast::Expr;
+
+    /// creates `a0 -> a1 -> ... -> a{len}` where the last alias points to `target`
+    fn alias_chain(identifiers: &mut Identifiers, len: usize, target: &str) {
+        for i in 0..len {
+            let dest = if i + 1 == len { target.to_string() } else { format!("a{}", i + 1) };
+            identifiers.add_identifier(
+                ScopedName::from_str(format!("a{}", i)),
+                IdentifierDefinitionType::Alias(ScopedName::from_str(dest)),
+            );
+        }
+    }
+
+    #[test]
+    fn can_report_identifiers_that_are_not_scopes() {
+        let mut identifiers = Identifiers::default();
+        identifiers.add_identifier(
+            ScopedName::from_str("a.b"),
+            IdentifierDefinitionType::ConstDef(Some(1.into())),
+        );
+        let err = identifiers.get_scope(&ScopedName::from_str("a.b.c")).unwrap_err();
+        assert!(
+            matches!(&err, CairoError::NotScope(name, Some(rem), _) if name.name() == "a.b" && rem.name() == "c")
+        );
+        assert_eq!(err.to_string(), "`a.b` is a const, not a scope (while resolving `.c`)");
+
+        let err = identifiers.get_scope(&ScopedName::from_str("a.b")).unwrap_err();
+        assert_eq!(err.to_string(), "`a.b` is a const, not a scope");
+    }
+
+    #[test]
+    fn can_list_members_of_scopes() {
+        let mut identifiers = Identifiers::default();
+        let mut add = |name: &str, ty| identifiers.add_identifier(ScopedName::from_str(name), ty);
+        add("foo", IdentifierDefinitionType::Namespace);
+        add("foo.f", IdentifierDefinitionType::Function);
+        add("foo.f.Args", IdentifierDefinitionType::Struct(None));
+        add("foo.g", IdentifierDefinitionType::Function);
+        add("foo.Point", IdentifierDefinitionType::Struct(None));
+        add("foo.inner.c", IdentifierDefinitionType::ConstDef(None));
+        add("bar", IdentifierDefinitionType::Alias(ScopedName::from_str("foo")));
+
+        let members = identifiers.members_of(&ScopedName::from_str("foo")).unwrap();
+        assert_eq!(
+            members,
+            [
+                ("Point".to_string(), IdentifierDefinitionType::Struct(None)),
+                ("f".to_string(), IdentifierDefinitionType::Function),
+                ("g".to_string(), IdentifierDefinitionType::Function),
+                ("inner".to_string(), IdentifierDefinitionType::Namespace),
+            ]
+        );
+        assert_eq!(identifiers.members_of(&ScopedName::from_str("bar")).unwrap(), members);
+        assert!(identifiers.members_of(&ScopedName::from_str("foo.g")).is_err());
+        assert!(identifiers.members_of(&ScopedName::from_str("baz")).is_err());
+    }
+
+    #[test]
+    fn can_resolve_pointer_types() {
+        let mut identifiers = Identifiers::default();
+        identifiers.scope_tracker.enter_scope(ScopedName::root().into());
+        identifiers
+            .add_identifier(ScopedName::from_str("a.S"), IdentifierDefinitionType::Struct(None));
+        identifiers.add_identifier(
+            ScopedName::from_str("S"),
+            IdentifierDefinitionType::Alias(ScopedName::from_str("a.S")),
+        );
+
+        let resolve = |ty: &str| match Expr::parse(&format!("cast(0, {})", ty)).unwrap() {
+            Expr::Cast(_, ty, _) => identifiers.resolve_type(ty).unwrap(),
+            expr => unreachable!("{}", expr),
+        };
+        let s = CairoType::Id(TypeStruct {
+            name: vec!["a".to_string(), "S".to_string()],
+            is_fully_resolved: true,
+            loc: Default::default(),
+        });
+
+        let ty = resolve("S**");
+        assert_eq!(ty.pointer_depth(), 2);
+        match &ty {
+            CairoType::Pointer(ptr) => {
+                assert!(ptr.is_double());
+                assert_eq!(ptr.base(), &s);
+                assert_eq!(
+                    ptr.pointee(),
+                    CairoType::Pointer(Box::new(PointerType::Single(s.clone())))
+                );
+            }
+            ty => panic!("unexpected type {}", ty),
+        }
+        assert_eq!(ty.to_string(), "a.S**");
+        assert_eq!(resolve("S*").pointer_depth(), 1);
+        assert_eq!(resolve("S***").pointer_depth(), 3);
+        assert_eq!(s.pointer_depth(), 0);
+
+        // a pointer to a single pointer is the same type as a double pointer
+        assert_ne!(resolve("S*"), ty);
+        assert_ne!(resolve("felt**"), ty);
+        assert_eq!(CairoType::Pointer(Box::new(PointerType::Single(resolve("S*")))), ty);
+    }
+
+    #[test]
+    fn can_display_definition_types() {
+        assert_eq!(IdentifierDefinitionType::ConstDef(Some(1.into())).to_string(), "const");
+        assert_eq!(IdentifierDefinitionType::Struct(None).to_string(), "struct");
+        assert_eq!(
+            IdentifierDefinitionType::Alias(ScopedName::from_str("a.b")).to_string(),
+            "alias to a.b"
+        );
+        assert_eq!(
+            IdentifierDefinitionType::Unresolved(Box::new(IdentifierDefinitionType::Function))
+                .to_string(),
+            "unresolved function"
+        );
+
+        let err = CairoError::Definition(
+            ScopedName::from_str("x"),
+            IdentifierDefinitionType::Namespace,
+            IdentifierDefinitionType::Label,
+        );
+        assert_eq!(err.to_string(), "Expected `x` to be a namespace, found label");
+    }
+
+    #[test]
+    fn can_limit_alias_depth() {
+        let mut identifiers = Identifiers::default();
+        identifiers.add_identifier(
+            ScopedName::from_str("ns.x"),
+            IdentifierDefinitionType::ConstDef(Some(1.into())),
+        );
+
+        alias_chain(&mut identifiers, 3, "ns");
+        let resolved = identifiers.get(&ScopedName::from_str("a0.x")).unwrap();
+        assert_eq!(*resolved.ty, IdentifierDefinitionType::ConstDef(Some(1.into())));
+        assert_eq!(
+            identifiers.get_scope(&ScopedName::from_str("a0")).unwrap().full_name,
+            "ns".into()
+        );
+
+        alias_chain(&mut identifiers, Identifiers::DEFAULT_MAX_ALIAS_DEPTH + 1, "ns");
+        let err = identifiers.get(&ScopedName::from_str("a0.x")).unwrap_err();
+        assert!(matches!(err, CairoError::AliasDepthExceeded(ref name) if name == &"a0.x".into()));
+        let err = identifiers.get_scope(&ScopedName::from_str("a0")).unwrap_err();
+        assert!(matches!(err, CairoError::AliasDepthExceeded(_)), "{:?}", err);
+
+        let identifiers =
+            identifiers.with_max_alias_depth(Identifiers::DEFAULT_MAX_ALIAS_DEPTH + 1);
+        assert!(identifiers.get(&ScopedName::from_str("a0.x")).is_ok());
+        assert!(identifiers.get_scope(&ScopedName::from_str("a0")).is_ok());
+    }
+
+    #[test]
+    fn can_convert_const_to_json_number() {
+        // values exceed the range of `f64`
+        let felt = U256::MAX >> 5;
+        assert_eq!(
+            IdentifierDefinitionType::ConstDef(Some(felt)).to_json().to_string(),
+            format!(r#"{{"type":"const","value":{}}}"#, felt)
+        );
+        assert_eq!(
+            IdentifierDefinitionType::ConstDef(Some(7.into())).to_json().to_string(),
+            r#"{"type":"const","value":7}"#
+        );
+    }
+}
@@ -1,14 +1,20 @@
 use crate::{
-    compiler::sema::{ast::LangVisitor, identifiers::Identifiers},
-    error::Result,
+    compiler::{
+        constants::{CAIRO_FILE_EXTENSION, DEFAULT_PRIME, START_FILE_NAME},
+        sema::{ast::LangVisitor, identifiers::Identifiers, passes::AuxiliaryInfo},
+    },
+    error::{CairoError, Result},
     parser::ast::{Builtin, Identifier},
     CairoFile,
 };
-use std::{borrow::Cow, fmt, path::PathBuf, rc::Rc};
+use ethers::types::U256;
+use std::{borrow::Cow, collections::HashMap, fmt, path::PathBuf, rc::Rc, str::FromStr};
 
 pub mod ast;
+pub mod const_eval;
 pub mod identifiers;
 pub mod passes;
+pub mod type_inference;
 
 #[derive(Debug, Clone)]
 pub struct CairoContent {
@@ -20,29 +26,47 @@ pub struct CairoContent {
 
 impl CairoContent {
     pub fn new(code: String, path: PathBuf) -> Self {
-        debug_assert!(path.file_stem().is_some(), "File must have a name");
         Self { code, path }
     }
 
-    /// Returns the file stem of the source file
+    /// Returns the name of the module the code belongs to
+    ///
+    /// This is the file name without the `.cairo` extension, so `a.b.cairo` is `a.b`. Only the
+    /// `.cairo` extension is stripped, module style names like `a.b.c` and names without an
+    /// extension are returned as they are. A path without a file name, like `/`, is returned whole.
     pub fn name(&self) -> Cow<'_, str> {
-        self.path.file_stem().unwrap().to_string_lossy()
+        let file_name = match self.path.file_name() {
+            Some(file_name) => file_name.to_string_lossy(),
+            None => return self.path.to_string_lossy(),
+        };
+        match file_name.strip_suffix(CAIRO_FILE_EXTENSION) {
+            Some(stem) if !stem.is_empty() => Cow::Owned(stem.to_string()),
+            _ => file_name,
+        }
     }
 }
 
 /// When assembling a cairo file, this holds all the resolved info.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PreprocessedProgram {
     /// input code content
     pub codes: Vec<CairoContent>,
     pub main_scope: ScopedName,
     pub modules: Vec<CairoModule>,
+    /// The prime the program is compiled for, `DEFAULT_PRIME` unless set by the `PassManager`
+    pub prime: U256,
     /// various cairo builtins
     pub builtins: Option<Vec<Builtin>>,
     /// Manages identifiers for
     // TODO(mattssee): ideally this should be merged with the AST so that we have everything in one
     // place
     pub identifiers: Identifiers,
+    /// Extra information collected during preprocessing
+    pub auxiliary_info: AuxiliaryInfo,
+    /// Program counter offsets of all labels
+    pub labels: HashMap<ScopedName, usize>,
+    /// Scope names to all the identifiers they use, see `DependencyGraphPass`
+    pub dependency_graph: HashMap<ScopedName, Vec<ScopedName>>,
 }
 
 impl PreprocessedProgram {
@@ -55,8 +79,12 @@ impl PreprocessedProgram {
             codes: codes.into_iter().map(|(c, p)| CairoContent::new(c, p)).collect(),
             main_scope,
             modules: Default::default(),
+            prime: DEFAULT_PRIME,
             builtins: None,
             identifiers: Default::default(),
+            auxiliary_info: Default::default(),
+            labels: Default::default(),
+            dependency_graph: Default::default(),
         }
     }
 
@@ -68,9 +96,41 @@ impl PreprocessedProgram {
             codes: vec![],
             main_scope,
             modules: modules.into_iter().collect(),
+            prime: DEFAULT_PRIME,
             builtins: None,
             identifiers: Default::default(),
+            auxiliary_info: Default::default(),
+            labels: Default::default(),
+            dependency_graph: Default::default(),
+        }
+    }
+
+    /// Whether the start code, which calls `main`, was added to the program
+    pub fn has_start_code(&self) -> bool {
+        self.codes
+            .iter()
+            .any(|content| content.path.as_path() == std::path::Path::new(START_FILE_NAME))
+    }
+
+    /// Ensures that `<main_scope>.main` is a function if the program contains the start code
+    pub fn check_main_function(&self) -> Result<()> {
+        if !self.has_start_code() {
+            return Ok(())
+        }
+        let main = self.main_scope.clone().appended("main");
+        let is_function = self.identifiers.get(&main).is_ok_and(|resolved| {
+            resolved.rem.is_none() &&
+                resolved.ty.as_unresolved().unwrap_or(&resolved.ty).is_function()
+        });
+        if !is_function {
+            return Err(CairoError::Preprocess("Function 'main' not found".to_string()))
         }
+        Ok(())
+    }
+
+    /// Returns the names of the builtins in the format of the `builtins` of a compiled program
+    pub fn builtin_names(&self) -> Vec<String> {
+        self.builtins.iter().flatten().map(|builtin| builtin.to_string()).collect()
     }
 }
 
@@ -78,11 +138,19 @@ impl PreprocessedProgram {
 pub struct CairoModule {
     pub module_name: Rc<ScopedName>,
     pub cairo_file: CairoFile,
+    /// the code the module was parsed from, if known
+    pub content: Option<CairoContent>,
 }
 
 impl CairoModule {
     pub fn new(module_name: ScopedName, cairo_file: CairoFile) -> Self {
-        Self { module_name: Rc::new(module_name), cairo_file }
+        Self { module_name: Rc::new(module_name), cairo_file, content: None }
+    }
+
+    /// Sets the code the module was parsed from
+    pub fn with_content(mut self, content: CairoContent) -> Self {
+        self.content = Some(content);
+        self
     }
 
     pub fn lang(&mut self) -> Result<Option<String>> {
@@ -90,6 +158,16 @@ impl CairoModule {
     }
 }
 
+/// A dot separated name like `a.b.c`
+///
+/// | operation                 | on `a.b.c`                 | on `a`           |
+/// |---------------------------|----------------------------|------------------|
+/// | `split()`                 | `("a", Some(b.c))`         | `("a", None)`    |
+/// | `rev_split()`             | `(a.b, Some("c"))`         | `(a, None)`      |
+/// | `extended(d.e)`           | `a.b.c.d.e`                | `a.d.e`          |
+/// | `appended("d")`           | `a.b.c.d`                  | `a.d`            |
+/// | `parent()`                | `Some(a.b)`                | `Some(<root>)`   |
+/// | `into_inner()`            | `["a", "b", "c"]`          | `["a"]`          |
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct ScopedName(Identifier);
 
@@ -111,10 +189,13 @@ impl ScopedName {
         ScopedName(Default::default())
     }
 
+    /// Parses a dot separated name
+    ///
+    /// # Panics
+    ///
+    /// if the name is empty or any segment is not a valid identifier, see `FromStr`
     pub fn from_str(s: impl AsRef<str>) -> Self {
-        let s = s.as_ref();
-        assert!(!s.is_empty(), "scope named requires >=1 identifier");
-        ScopedName(s.split('.').map(str::to_string).collect())
+        s.as_ref().parse().unwrap_or_else(|err| panic!("{}", err))
     }
 
     pub fn last(&self) -> Option<&String> {
@@ -129,10 +210,21 @@ impl ScopedName {
         self.0.join(".")
     }
 
+    /// Returns the identifiers of the name
     pub fn into_inner(self) -> Identifier {
         self.0
     }
 
+    /// Returns the identifiers of the name by reference
+    pub fn segments(&self) -> &[String] {
+        &self.0
+    }
+
+    /// Iterates over the identifiers of the name
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(String::as_str)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
@@ -152,12 +244,16 @@ impl ScopedName {
         }
     }
 
+    /// Appends all identifiers of `scope` to this name
     pub fn extended(mut self, scope: ScopedName) -> Self {
         self.0.extend(scope.0.into_iter());
         self
     }
 
-    /// Removes the last item in the list of identifiers
+    /// Removes the last identifier of the name and returns it alongside the remaining name
+    ///
+    /// A name with a single identifier is not split and returned as is, so the returned name is
+    /// never empty.
     pub fn rev_split(mut self) -> (ScopedName, Option<String>) {
         debug_assert!(!self.is_empty());
         let mut rem = self.0.pop();
@@ -168,10 +264,52 @@ impl ScopedName {
         (self, rem)
     }
 
+    /// Appends a single identifier to this name
     pub fn appended(mut self, id: impl Into<String>) -> Self {
         self.0.push(id.into());
         self
     }
+
+    /// Returns the enclosing scope, if any
+    pub fn parent(&self) -> Option<ScopedName> {
+        if self.is_empty() {
+            return None
+        }
+        Some(ScopedName(self.0[..self.len() - 1].to_vec()))
+    }
+
+    /// Whether `other` is a prefix of this name, compared by identifier and not by string
+    pub fn starts_with(&self, other: &ScopedName) -> bool {
+        self.0.starts_with(&other.0)
+    }
+}
+
+impl FromStr for ScopedName {
+    type Err = CairoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.is_empty() {
+            return Err(CairoError::Identifier("Scoped name requires >=1 identifier".to_string()))
+        }
+        let is_identifier = |id: &str| {
+            let mut chars = id.chars();
+            matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_') &&
+                chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+        s.split('.')
+            .map(|id| {
+                if is_identifier(id) {
+                    Ok(id.to_string())
+                } else {
+                    Err(CairoError::Identifier(format!(
+                        "Invalid identifier `{}` in scoped name `{}`",
+                        id, s
+                    )))
+                }
+            })
+            .collect::<Result<_>>()
+            .map(ScopedName)
+    }
 }
 
 impl<T: AsRef<str>> From<T> for ScopedName {
@@ -185,3 +323,92 @@ impl fmt::Display for ScopedName {
         write!(f, "{}", self.name())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_name_contents() {
+        let name = |path: &str| CairoContent::new(String::new(), path.into()).name().into_owned();
+        assert_eq!(name("math.cairo"), "math");
+        assert_eq!(name("src/math.cairo"), "math");
+        assert_eq!(name("a.b.cairo"), "a.b");
+        assert_eq!(name("a.b.c"), "a.b.c");
+        assert_eq!(name("math"), "math");
+        assert_eq!(name(".cairo"), ".cairo");
+        assert_eq!(name(START_FILE_NAME), START_FILE_NAME);
+        assert_eq!(name("/"), "/");
+    }
+
+    #[test]
+    fn can_display_dotted_names() {
+        assert_eq!(format!("{}", ScopedName::from_str("a.b")), "a.b");
+        assert_eq!(ScopedName::from_str("a.b.c").to_string(), ScopedName::from_str("a.b.c").name());
+        let err = crate::error::CairoError::MissingIdentifier(ScopedName::from_str("foo.bar"));
+        assert_eq!(err.to_string(), "Missing identifier: foo.bar");
+    }
+
+    #[test]
+    fn can_iter_segments() {
+        let name = ScopedName::from_str("a.b.c");
+        assert_eq!(name.iter().collect::<Vec<_>>(), ["a", "b", "c"]);
+        assert_eq!(name.segments(), ["a", "b", "c"]);
+        assert_eq!(ScopedName::root().iter().count(), 0);
+    }
+
+    #[test]
+    fn can_parse_scoped_names() {
+        assert_eq!("a.b".parse::<ScopedName>().unwrap().segments(), ["a", "b"]);
+        assert_eq!("_a1.B_2".parse::<ScopedName>().unwrap().segments(), ["_a1", "B_2"]);
+        assert!("".parse::<ScopedName>().is_err());
+        let err = "a..b".parse::<ScopedName>().unwrap_err();
+        assert_eq!(err.to_string(), "Invalid identifier `` in scoped name `a..b`");
+        assert!("a.1b".parse::<ScopedName>().is_err());
+        assert!("a.b-c".parse::<ScopedName>().is_err());
+    }
+
+    #[test]
+    fn can_split_names() {
+        let (first, rem) = ScopedName::from("a.b.c").split();
+        assert_eq!((first.as_str(), rem), ("a", Some(ScopedName::from("b.c"))));
+        let (first, rem) = ScopedName::from("a").split();
+        assert_eq!((first.as_str(), rem), ("a", None));
+
+        let (name, last) = ScopedName::from("a.b.c").rev_split();
+        assert_eq!((name, last), (ScopedName::from("a.b"), Some("c".to_string())));
+        // a single identifier is kept
+        let (name, last) = ScopedName::from("a").rev_split();
+        assert_eq!((name, last), (ScopedName::from("a"), None));
+    }
+
+    #[test]
+    fn can_extend_names() {
+        let name = ScopedName::from("a.b");
+        assert_eq!(name.clone().extended(ScopedName::from("c.d")), ScopedName::from("a.b.c.d"));
+        assert_eq!(name.clone().extended(ScopedName::root()), name);
+        assert_eq!(ScopedName::root().extended(name.clone()), name);
+        assert_eq!(name.clone().appended("c"), ScopedName::from("a.b.c"));
+        assert_eq!(ScopedName::root().appended("a"), ScopedName::from("a"));
+        assert_eq!(name.into_inner(), vec!["a".to_string(), "b".to_string()]);
+    }
+
+    #[test]
+    fn can_get_parent_scope() {
+        assert_eq!(ScopedName::from("a.b.c").parent(), Some(ScopedName::from("a.b")));
+        assert_eq!(ScopedName::from("a").parent(), Some(ScopedName::root()));
+        assert_eq!(ScopedName::root().parent(), None);
+    }
+
+    #[test]
+    fn can_match_scope_prefix() {
+        let name = ScopedName::from("foo.bar.baz");
+        assert!(name.starts_with(&ScopedName::from("foo")));
+        assert!(name.starts_with(&ScopedName::from("foo.bar")));
+        assert!(name.starts_with(&name));
+        assert!(name.starts_with(&ScopedName::root()));
+        assert!(!name.starts_with(&ScopedName::from("bar")));
+        assert!(!ScopedName::from("foo_bar").starts_with(&ScopedName::from("foo")));
+        assert!(!ScopedName::from("foo").starts_with(&name));
+    }
+}
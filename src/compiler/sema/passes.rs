@@ -3,37 +3,65 @@ use crate::compiler::sema::PreprocessedProgram;
 use crate::{
     compiler::{
         sema::passes::{
-            directives::DirectivesCollectorPass, identifier::IdentifierCollectorPass,
-            import::ModuleCollectorPass, label::UniqueLabelPass,
+            dependencygraph::DependencyGraphPass,
+            directives::DirectivesCollectorPass,
+            identifier::IdentifierCollectorPass,
+            import::ModuleCollectorPass,
+            label::UniqueLabelPass,
+            locals::AllocLocalsPass,
+            preprocess::PreprocessPass,
+            starknet::{EntryPointInstructionsPass, StarknetPreprocessPass},
             struct_collect::StructCollectorPass,
         },
-        ModuleReader,
+        CodeReader, ModuleReader,
     },
     error::Result,
 };
-use ethers::core::k256::U256;
+use ethers::types::U256;
 use std::fmt;
 
+pub use label::LabelOffsetPass;
+pub use preprocess::{AuxiliaryInfo, HintInfo};
+pub use starknet::build_abi;
+pub use unused_imports::unused_imports;
+pub use unused_variables::UnusedVariablesPass;
+
+mod decorators;
 mod dependencygraph;
 mod directives;
 mod identifier;
 mod import;
 mod label;
+mod locals;
 mod preprocess;
+mod starknet;
 mod struct_collect;
+mod unused_imports;
+mod unused_variables;
 
 /// A manager for running passes
 #[derive(Debug)]
 pub struct PassManager {
     passes: Vec<Box<dyn Pass + 'static>>,
+    /// the prime that is set on the program before the passes run
+    prime: Option<U256>,
 }
 
 impl PassManager {
+    /// Returns the default passes plus the StarkNet specific ones
     pub fn starknet_pass_manager() -> Self {
-        todo!()
+        let mut pm = PassManagerBuilder::default()
+            .supported_decorators(starknet::starknet_supported_decorators())
+            .build();
+        pm.passes.push(Box::new(StarknetPreprocessPass::default()));
+        pm.passes.push(Box::new(EntryPointInstructionsPass::default()));
+        pm
     }
 
     pub fn run_on(&mut self, prg: &mut PreprocessedProgram) -> Result<()> {
+        if let Some(prime) = self.prime {
+            prg.prime = prime;
+        }
         for t in self.passes.iter_mut() {
             t.run(prg)?;
         }
@@ -43,26 +71,106 @@ impl PassManager {
 
 #[derive(Debug, Default)]
 pub struct PassManagerBuilder {
-    module_reader: Option<ModuleReader>,
+    code_reader: Option<Box<dyn CodeReader>>,
+    prime: Option<U256>,
+    supported_decorators: Option<Vec<String>>,
+    /// whether `%builtins` may contain builtins that are not known to the compiler
+    allow_unknown_builtins: bool,
+    /// replaces the standard passes if set
+    passes: Option<Vec<Box<dyn Pass + 'static>>>,
+    /// custom passes that run after the standard passes
+    custom_passes: Vec<Box<dyn Pass + 'static>>,
 }
 
 impl PassManagerBuilder {
     /// Use a custom `ModuleReader`
-    pub fn module_reader(mut self, module_reader: ModuleReader) -> Self {
-        self.module_reader = Some(module_reader);
+    pub fn module_reader(self, module_reader: ModuleReader) -> Self {
+        self.code_reader(module_reader)
+    }
+
+    /// Read modules with a custom `CodeReader`, like an `InMemoryReader`
+    pub fn code_reader(mut self, code_reader: impl CodeReader + 'static) -> Self {
+        self.code_reader = Some(Box::new(code_reader));
+        self
+    }
+
+    /// Run the preprocessor for the given prime
+    ///
+    /// # Panics
+    ///
+    /// if the prime is zero
+    pub fn prime(mut self, prime: U256) -> Self {
+        assert!(!prime.is_zero(), "prime must be non-zero");
+        self.prime = Some(prime);
+        self
+    }
+
+    /// Replace the decorators the preprocessor accepts
+    pub fn supported_decorators<I, S>(mut self, decorators: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.supported_decorators = Some(decorators.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Accept builtins in `%builtins` directives that are not known to the compiler, like
+    /// experimental ones
+    pub fn allow_unknown_builtins(mut self) -> Self {
+        self.allow_unknown_builtins = true;
+        self
+    }
+
+    /// Appends a custom pass that runs after the standard passes
+    pub fn add_pass(mut self, pass: Box<dyn Pass + 'static>) -> Self {
+        self.custom_passes.push(pass);
+        self
+    }
+
+    /// Replaces the standard passes
+    pub fn with_passes(mut self, passes: Vec<Box<dyn Pass + 'static>>) -> Self {
+        self.passes = Some(passes);
         self
     }
 
     pub fn build(self) -> PassManager {
-        PassManager {
-            passes: vec![
-                Box::new(ModuleCollectorPass::new(self.module_reader.unwrap_or_default())),
-                Box::new(UniqueLabelPass::default()),
-                Box::new(IdentifierCollectorPass::default()),
-                Box::new(DirectivesCollectorPass::default()),
-                Box::new(StructCollectorPass::default()),
-            ],
+        let mut passes = self.passes.unwrap_or_else(|| {
+            Self::standard_passes(
+                self.code_reader,
+                self.supported_decorators,
+                self.allow_unknown_builtins,
+            )
+        });
+        passes.extend(self.custom_passes);
+        PassManager { passes, prime: self.prime }
+    }
+
+    fn standard_passes(
+        code_reader: Option<Box<dyn CodeReader>>,
+        supported_decorators: Option<Vec<String>>,
+        allow_unknown_builtins: bool,
+    ) -> Vec<Box<dyn Pass + 'static>> {
+        let mut preprocess = PreprocessPass::default();
+        if let Some(decorators) = supported_decorators {
+            preprocess = preprocess.with_supported_decorators(decorators);
         }
+        vec![
+            Box::new(ModuleCollectorPass::with_boxed_reader(
+                code_reader.unwrap_or_else(|| Box::new(ModuleReader::default())),
+                Vec::new(),
+            )),
+            Box::new(UniqueLabelPass::default()),
+            Box::new(IdentifierCollectorPass::default()),
+            Box::new(
+                DirectivesCollectorPass::default().allow_unknown_builtins(allow_unknown_builtins),
+            ),
+            Box::new(StructCollectorPass::default()),
+            Box::new(AllocLocalsPass),
+            Box::new(UnusedVariablesPass),
+            Box::new(DependencyGraphPass),
+            Box::new(preprocess),
+        ]
     }
 }
 
@@ -73,11 +181,100 @@ impl Default for PassManager {
 }
 
 impl From<U256> for PassManager {
-    fn from(_prime: U256) -> Self {
-        todo!()
+    fn from(prime: U256) -> Self {
+        PassManagerBuilder::default().prime(prime).build()
     }
 }
 
 pub trait Pass: fmt::Debug {
     fn run(&mut self, prg: &mut PreprocessedProgram) -> Result<()>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{
+        constants::DEFAULT_PRIME,
+        sema::{identifiers::IdentifierDefinitionType, ScopedName},
+        InMemoryReader,
+    };
+    use std::{cell::Cell, rc::Rc};
+
+    #[test]
+    fn can_create_pass_manager_from_prime() {
+        let pm = PassManager::from(DEFAULT_PRIME);
+        assert_eq!(pm.prime, Some(DEFAULT_PRIME));
+        assert_eq!(pm.passes.len(), PassManager::default().passes.len());
+        // the preprocessor runs with the program's default prime if none is set
+        for pm in [pm, PassManager::default()] {
+            assert!(format!("{:?}", pm.passes.last().unwrap()).starts_with("PreprocessPass"));
+        }
+    }
+
+    #[test]
+    fn can_set_prime_on_program() {
+        let mut prg = PreprocessedProgram::new(ScopedName::main_scope(), []);
+        assert_eq!(prg.prime, DEFAULT_PRIME);
+
+        let prime = U256::from(101);
+        let mut pm = PassManagerBuilder::default().prime(prime).with_passes(Vec::new()).build();
+        pm.run_on(&mut prg).unwrap();
+        assert_eq!(prg.prime, prime);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_zero_prime() {
+        let _ = PassManager::from(U256::zero());
+    }
+
+    #[derive(Debug, Default)]
+    struct CountingPass(Rc<Cell<usize>>);
+
+    impl Pass for CountingPass {
+        fn run(&mut self, _: &mut PreprocessedProgram) -> Result<()> {
+            self.0.set(self.0.get() + 1);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn can_add_custom_passes() {
+        let count = Rc::new(Cell::new(0));
+        let mut pm =
+            PassManagerBuilder::default().add_pass(Box::new(CountingPass(count.clone()))).build();
+        assert_eq!(pm.passes.len(), PassManager::default().passes.len() + 1);
+        assert!(format!("{:?}", pm.passes.last().unwrap()).starts_with("CountingPass"));
+
+        let mut prg = PreprocessedProgram::new(ScopedName::main_scope(), []);
+        pm.run_on(&mut prg).unwrap();
+        assert_eq!(count.get(), 1);
+        pm.run_on(&mut prg).unwrap();
+        assert_eq!(count.get(), 2);
+
+        let mut pm = PassManagerBuilder::default()
+            .with_passes(vec![Box::new(CountingPass(count.clone()))])
+            .add_pass(Box::new(CountingPass(count.clone())))
+            .build();
+        assert_eq!(pm.passes.len(), 2);
+        pm.run_on(&mut prg).unwrap();
+        assert_eq!(count.get(), 4);
+    }
+
+    #[test]
+    fn can_compile_modules_from_memory() {
+        let reader = InMemoryReader::new()
+            .with_module("a", "from b import B\nconst A = B * 2\n")
+            .with_module("b", "const B = 3\n");
+        let mut pm = PassManagerBuilder::default().code_reader(reader).prime(DEFAULT_PRIME).build();
+        let mut prg = PreprocessedProgram::new(
+            ScopedName::main_scope(),
+            [("from a import A\nconst C = A + 1\n".to_string(), "main.cairo".into())],
+        );
+        pm.run_on(&mut prg).unwrap();
+
+        assert_eq!(prg.modules.len(), 3);
+        let c = prg.identifiers.get_by_full_name(&"__main__.C".into()).unwrap();
+        assert_eq!(*c, IdentifierDefinitionType::ConstDef(Some(7.into())));
+    }
+}
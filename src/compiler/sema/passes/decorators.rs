@@ -0,0 +1,58 @@
+use crate::{
+    compiler::{
+        sema::{passes::Pass, PreprocessedProgram},
+        VResult, Visitable, Visitor,
+    },
+    error::{CairoError, Result},
+    parser::ast::{Decorator, FunctionDef, Loc, Namespace, StructDef},
+};
+use std::collections::HashSet;
+
+/// Rejects all decorators that are not in the set of supported decorators
+#[derive(Debug, Default)]
+pub struct DecoratorValidationPass {
+    supported_decorators: HashSet<String>,
+}
+
+impl DecoratorValidationPass {
+    pub fn new(supported_decorators: HashSet<String>) -> Self {
+        Self { supported_decorators }
+    }
+
+    fn validate(&self, decorators: &[Decorator], name: &str, loc: Loc) -> VResult {
+        for decorator in decorators {
+            if !self.supported_decorators.contains(decorator.name()) {
+                return Err(CairoError::Preprocess(format!(
+                    "Unsupported decorator {} for {} {}",
+                    decorator, name, loc
+                ))
+                .spanned(loc))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Pass for DecoratorValidationPass {
+    fn run(&mut self, prg: &mut PreprocessedProgram) -> Result<()> {
+        log::trace!("starting pass: DecoratorValidation");
+        for module in prg.modules.iter_mut() {
+            module.cairo_file.visit(self)?;
+        }
+        Ok(())
+    }
+}
+
+impl Visitor for DecoratorValidationPass {
+    fn visit_struct_def(&mut self, s: &mut StructDef) -> VResult {
+        self.validate(&s.decorators, &s.name, s.loc)
+    }
+
+    fn visit_function(&mut self, f: &mut FunctionDef) -> VResult {
+        self.validate(&f.decorators, &f.name, f.loc)
+    }
+
+    fn visit_namespace(&mut self, ns: &mut Namespace) -> VResult {
+        self.validate(&ns.decorators, &ns.name, ns.loc)
+    }
+}
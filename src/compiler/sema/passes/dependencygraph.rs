@@ -1,26 +1,16 @@
 use crate::{
     compiler::{
-        sema::{
-            ast::macros::delegate_scope_tracking, identifiers::Identifiers, passes::Pass,
-            PreprocessedProgram, ScopedName,
-        },
+        sema::{identifiers::Identifiers, passes::Pass, PreprocessedProgram, ScopedName},
         VResult, Visitable, Visitor,
     },
     error::Result,
     parser::ast::*,
 };
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
+/// Collects the identifiers every scope depends on into `PreprocessedProgram::dependency_graph`
 #[derive(Debug, Default)]
-pub struct DependencyGraphPass {
-    additional_scopes_to_compile: HashSet<ScopedName>,
-}
-
-impl DependencyGraphPass {
-    pub fn new(additional_scopes_to_compile: HashSet<ScopedName>) -> Self {
-        Self { additional_scopes_to_compile }
-    }
-}
+pub struct DependencyGraphPass;
 
 impl Pass for DependencyGraphPass {
     fn run(&mut self, prg: &mut PreprocessedProgram) -> Result<()> {
@@ -31,6 +21,14 @@ impl Pass for DependencyGraphPass {
 
             let mut visitor = GraphVisitor::new(&mut prg.identifiers);
             module.cairo_file.visit(&mut visitor)?;
+            for (scope, deps) in visitor.into_graph() {
+                let entry = prg.dependency_graph.entry(scope).or_default();
+                for dep in deps {
+                    if !entry.contains(&dep) {
+                        entry.push(dep);
+                    }
+                }
+            }
 
             prg.identifiers.scope_tracker_mut().exit_scope();
             prg.identifiers.scope_tracker_mut().exit_lang();
@@ -44,8 +42,10 @@ struct GraphVisitor<'a> {
     identifiers: &'a mut Identifiers,
     /// scope names to all the identifiers it uses.
     visited_identifiers: HashMap<ScopedName, Vec<ScopedName>>,
-    /// the current function we're tracking
-    current_function: Option<ScopedName>,
+    /// the functions we're currently in, the innermost last
+    functions: Vec<ScopedName>,
+    /// set while visiting the operand of a dotted name that was already resolved as a whole
+    in_resolved_dot: bool,
 }
 
 impl<'a> GraphVisitor<'a> {
@@ -53,26 +53,178 @@ impl<'a> GraphVisitor<'a> {
         Self {
             identifiers,
             visited_identifiers: Default::default(),
-            current_function: Default::default(),
+            functions: Default::default(),
+            in_resolved_dot: false,
+        }
+    }
+
+    pub fn into_graph(self) -> HashMap<ScopedName, Vec<ScopedName>> {
+        self.visited_identifiers
+    }
+
+    /// The scope that depends on the identifiers currently visited, this is either the current
+    /// function or the current scope for code outside of functions
+    fn current_dependent(&self) -> ScopedName {
+        self.functions
+            .last()
+            .cloned()
+            .unwrap_or_else(|| self.identifiers.current_scope().as_ref().clone())
+    }
+
+    /// Resolves the identifier in the current accessible scopes and adds it as dependency
+    ///
+    /// Identifiers that can't be resolved are ignored
+    fn add_identifier(&mut self, name: ScopedName) {
+        if let Ok(resolved) = self.identifiers.search_current_scopes(&name) {
+            self.add_dependency(resolved.canonical_name);
         }
     }
 
-    fn add_identifier(&mut self) {
-        todo!()
+    fn add_dependency(&mut self, dependency: ScopedName) {
+        let deps = self.visited_identifiers.entry(self.current_dependent()).or_default();
+        if !deps.contains(&dependency) {
+            deps.push(dependency);
+        }
     }
 }
 
 impl<'a> Visitor for GraphVisitor<'a> {
-    fn visit_import(&mut self, _import: &mut ImportDirective) -> VResult {
-        todo!()
+    fn visit_import(&mut self, import: &mut ImportDirective) -> VResult {
+        self.add_dependency(ScopedName::new(import.path.clone()));
+        Ok(())
+    }
+
+    fn visit_function_call(&mut self, call: &mut FunctionCall) -> VResult {
+        self.add_identifier(ScopedName::new(call.id.clone()));
+        Ok(())
     }
-    fn visit_function(&mut self, _: &mut FunctionDef) -> VResult {
-        todo!()
+
+    fn visit_expr_identifier(&mut self, id: &mut Identifier, _: Loc) -> VResult {
+        if std::mem::take(&mut self.in_resolved_dot) {
+            // the identifier is the start of a dotted name that was already recorded
+            return Ok(())
+        }
+        self.add_identifier(ScopedName::new(id.clone()));
+        Ok(())
     }
 
-    fn visit_expr_dot(&mut self, _: &mut Expr, _: &mut String, _: Loc) -> VResult {
-        todo!()
+    fn visit_expr_dot(&mut self, expr: &mut Expr, member: &mut String, _: Loc) -> VResult {
+        if self.in_resolved_dot {
+            return Ok(())
+        }
+        // `a.b.c` may refer to a scoped identifier or to a member of `a` or `a.b`, the name is
+        // resolved as a whole so that only the identifier it refers to is recorded
+        if let Some(name) = dotted_name(expr) {
+            self.add_identifier(name.appended(member.clone()));
+            self.in_resolved_dot = true;
+        }
+        Ok(())
     }
 
-    delegate_scope_tracking!();
+    fn enter_function(&mut self, f: &mut FunctionDef) -> VResult {
+        self.identifiers.enter_function(f)?;
+        self.functions.push(self.identifiers.current_scope().as_ref().clone());
+        Ok(())
+    }
+
+    fn exit_function(&mut self, f: &mut FunctionDef) -> VResult {
+        self.functions.pop();
+        self.identifiers.exit_function(f)
+    }
+
+    fn enter_namespace(&mut self, n: &mut Namespace) -> VResult {
+        self.identifiers.enter_namespace(n)
+    }
+
+    fn exit_namespace(&mut self, n: &mut Namespace) -> VResult {
+        self.identifiers.exit_namespace(n)
+    }
+}
+
+/// Returns the name of an expression like `a.b.c` that consists only of identifiers
+fn dotted_name(expr: &Expr) -> Option<ScopedName> {
+    match expr {
+        Expr::Id(id, _) => Some(ScopedName::new(id.clone())),
+        Expr::Dot(expr, member, _) => Some(dotted_name(expr)?.appended(member.clone())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::sema::{passes::identifier::IdentifierCollectorPass, CairoModule};
+
+    fn dependency_graph(code: &str) -> HashMap<ScopedName, Vec<ScopedName>> {
+        let module = CairoModule::new(ScopedName::main_scope(), CairoFile::parse(code).unwrap());
+        let mut prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        IdentifierCollectorPass::default().run(&mut prg).unwrap();
+        DependencyGraphPass.run(&mut prg).unwrap();
+        prg.dependency_graph
+    }
+
+    #[test]
+    fn can_record_function_calls() {
+        let graph = dependency_graph(
+            r#"
+func foo():
+    ret
+end
+
+func bar():
+    foo()
+    ret
+end
+"#,
+        );
+        assert_eq!(graph[&"__main__.bar".into()], vec![ScopedName::from("__main__.foo")]);
+        assert!(!graph.contains_key(&"__main__.foo".into()));
+    }
+
+    #[test]
+    fn can_record_dotted_names() {
+        let graph = dependency_graph(
+            r#"
+namespace Foo:
+    const BAR = 1
+
+    func baz():
+        ret
+    end
+end
+
+func main():
+    [ap] = Foo.BAR; ap++
+    Foo.baz()
+    ret
+end
+"#,
+        );
+        assert_eq!(
+            graph[&"__main__.main".into()],
+            vec![ScopedName::from("__main__.Foo.BAR"), ScopedName::from("__main__.Foo.baz")]
+        );
+    }
+
+    #[test]
+    fn can_restore_enclosing_function() {
+        let graph = dependency_graph(
+            r#"
+func foo():
+    ret
+end
+
+func bar():
+    func inner():
+        foo()
+        ret
+    end
+    inner()
+    ret
+end
+"#,
+        );
+        assert_eq!(graph[&"__main__.bar.inner".into()], vec![ScopedName::from("__main__.foo")]);
+        assert_eq!(graph[&"__main__.bar".into()], vec![ScopedName::from("__main__.bar.inner")]);
+    }
 }
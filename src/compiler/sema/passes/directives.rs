@@ -1,5 +1,6 @@
 use crate::{
     compiler::{
+        constants::SUPPORTED_BUILTINS,
         sema::{passes::Pass, PreprocessedProgram},
         VResult, Visitable, Visitor,
     },
@@ -12,6 +13,16 @@ use std::collections::HashSet;
 pub struct DirectivesCollectorPass {
     builtins: Vec<Builtin>,
     builtins_set: bool,
+    /// whether `Builtin::Other` builtins are accepted
+    allow_unknown_builtins: bool,
+}
+
+impl DirectivesCollectorPass {
+    /// Whether to accept unknown builtins, like experimental ones
+    pub fn allow_unknown_builtins(mut self, allow: bool) -> Self {
+        self.allow_unknown_builtins = allow;
+        self
+    }
 }
 
 impl Pass for DirectivesCollectorPass {
@@ -20,21 +31,83 @@ impl Pass for DirectivesCollectorPass {
         for module in prg.modules.iter_mut() {
             module.cairo_file.visit(self)?;
         }
+        if self.builtins_set {
+            prg.builtins = Some(self.builtins.clone());
+        }
         Ok(())
     }
 }
 
+/// Ensures that the builtin is not an unknown `Builtin::Other`
+fn check_builtin(builtin: &Builtin, loc: Loc) -> Result<()> {
+    if !builtin.is_other() {
+        return Ok(())
+    }
+    let name = builtin.to_string();
+    let suggestion = SUPPORTED_BUILTINS
+        .iter()
+        .map(|known| (edit_distance(&name, known), known))
+        .filter(|(distance, _)| *distance <= (name.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| format!(", did you mean `{}`?", known))
+        .unwrap_or_default();
+    Err(CairoError::Preprocess(format!("Unknown builtin `{}`{} {}", name, suggestion, loc))
+        .spanned(loc))
+}
+
+/// Ensures that the known builtins are declared in their canonical order
+fn check_builtins_order(builtins: &[Builtin], loc: Loc) -> Result<()> {
+    let mut known = builtins.iter().filter_map(|b| b.canonical_index().map(|idx| (idx, b)));
+    let mut prev = match known.next() {
+        Some(prev) => prev,
+        None => return Ok(()),
+    };
+    for (idx, builtin) in known {
+        if idx < prev.0 {
+            return Err(CairoError::Preprocess(format!(
+                "Builtin `{}` must appear before `{}`, expected order: {} {}",
+                builtin,
+                prev.1,
+                SUPPORTED_BUILTINS.join(", "),
+                loc
+            ))
+            .spanned(loc))
+        }
+        prev = (idx, builtin);
+    }
+    Ok(())
+}
+
+/// The Levenshtein distance between the two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            current.push(substitution.min(prev[j + 1] + 1).min(current[j] + 1));
+        }
+        prev = current;
+    }
+    prev[b.len()]
+}
+
 impl Visitor for DirectivesCollectorPass {
     fn visit_builtins(&mut self, builtins: &mut [Builtin], loc: Loc) -> VResult {
         if self.builtins_set {
             return Err(CairoError::Preprocess(format!(
                 "Redefinition of builtins directive: {}",
                 loc
-            )))
+            ))
+            .spanned(loc))
         }
 
         let mut unique_builtins = HashSet::new();
         for builtin in builtins.iter() {
+            if !self.allow_unknown_builtins {
+                check_builtin(builtin, loc)?;
+            }
             if !unique_builtins.insert(builtin) {
                 return Err(CairoError::Preprocess(format!(
                     "Builtin {} appears twice in builtins directive",
@@ -42,8 +115,85 @@ impl Visitor for DirectivesCollectorPass {
                 )))
             }
         }
+        check_builtins_order(builtins, loc)?;
         self.builtins = builtins.to_vec();
+        self.builtins_set = true;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compiler::sema::{CairoModule, ScopedName},
+        parser::ast::CairoFile,
+    };
+
+    fn collect_directives(code: &str) -> Result<()> {
+        let module = CairoModule::new(ScopedName::main_scope(), CairoFile::parse(code).unwrap());
+        let mut prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        DirectivesCollectorPass::default().run(&mut prg)
+    }
+
+    #[test]
+    fn can_collect_builtins() {
+        let code = "%builtins pedersen range_check\n";
+        let module = CairoModule::new(ScopedName::main_scope(), CairoFile::parse(code).unwrap());
+        let mut prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        DirectivesCollectorPass::default().run(&mut prg).unwrap();
+        assert_eq!(prg.builtins, Some(vec![Builtin::Pedersen, Builtin::RangeCheck]));
+        assert_eq!(prg.builtin_names(), ["pedersen", "range_check"]);
+        assert_eq!(prg.modules[0].lang().unwrap(), None);
+
+        let err = collect_directives("%builtins pedersen\n%builtins range_check\n").unwrap_err();
+        assert!(err.to_string().starts_with("Redefinition of builtins directive"), "{}", err);
+    }
+
+    #[test]
+    fn can_reject_unknown_builtins() {
+        collect_directives("%builtins output pedersen range_check bitwise poseidon\n").unwrap();
+
+        let err = collect_directives("%builtins output pedrsen\n").unwrap_err();
+        assert!(
+            err.to_string().starts_with("Unknown builtin `pedrsen`, did you mean `pedersen`?"),
+            "{}",
+            err
+        );
+        assert!(err.span().is_some());
+        let err = collect_directives("%builtins my_builtin\n").unwrap_err();
+        assert!(err.to_string().starts_with("Unknown builtin `my_builtin` "), "{}", err);
+
+        let code = "%builtins output my_builtin\n";
+        let module = CairoModule::new(ScopedName::main_scope(), CairoFile::parse(code).unwrap());
+        let mut prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        DirectivesCollectorPass::default().allow_unknown_builtins(true).run(&mut prg).unwrap();
+        assert_eq!(prg.builtin_names(), ["output", "my_builtin"]);
+        assert_eq!(prg.builtins.unwrap()[1], Builtin::Other("my_builtin".to_string()));
+    }
+
+    #[test]
+    fn can_check_builtins_order() {
+        collect_directives("%builtins pedersen range_check\n").unwrap();
+        collect_directives("%builtins output range_check bitwise poseidon\n").unwrap();
+
+        let err = collect_directives("%builtins range_check pedersen\n").unwrap_err();
+        assert!(
+            err.to_string().starts_with("Builtin `pedersen` must appear before `range_check`"),
+            "{}",
+            err
+        );
+        assert!(err.span().is_some());
+        let err = collect_directives("%builtins output poseidon bitwise\n").unwrap_err();
+        assert!(err.to_string().starts_with("Builtin `bitwise` must appear before `poseidon`"));
+    }
+
+    #[test]
+    fn can_compute_edit_distance() {
+        assert_eq!(edit_distance("pedersen", "pedersen"), 0);
+        assert_eq!(edit_distance("pedrsen", "pedersen"), 1);
+        assert_eq!(edit_distance("rang_chek", "range_check"), 2);
+        assert_eq!(edit_distance("", "ecdsa"), 5);
+    }
+}
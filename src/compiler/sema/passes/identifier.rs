@@ -10,7 +10,7 @@ use crate::{
     error::{CairoError, Result},
     parser::ast::*,
 };
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 /// Resolves identifiers for cairo code elements.
 #[derive(Debug, Default)]
@@ -51,16 +51,16 @@ impl<'a> IdVisitor<'a> {
         loc: Loc,
     ) -> VResult {
         if let Some(existing_def) = self.identifiers.get_by_full_name(&name) {
-            if !existing_def.is_unresolved() || !ty.is_unresolved() {
-                return Err(CairoError::Redefinition(name, loc))
-            }
-            if !(existing_def.is_reference() || existing_def.is_unresolved_reference()) ||
+            if !existing_def.is_unresolved() ||
+                !ty.is_unresolved() ||
+                !(existing_def.is_reference() || existing_def.is_unresolved_reference()) ||
                 !(ty.is_reference() || ty.is_unresolved_reference())
             {
-                return Err(CairoError::Redefinition(name, loc))
+                let first = self.identifiers.definition_loc_by_full_name(&name).unwrap_or_default();
+                return Err(CairoError::Redefinition { name, first, second: loc })
             }
         }
-        self.identifiers.add_identifier(name, ty);
+        self.identifiers.add_identifier_at(name, ty, loc);
         Ok(())
     }
 
@@ -83,7 +83,8 @@ impl<'a> IdVisitor<'a> {
                 return Err(CairoError::Preprocess(format!(
                     "The name {} is reserved and cannot be used as argument {}",
                     N_LOCALS_CONSTANT, arg_id.loc
-                )))
+                ))
+                .spanned(arg_id.loc))
             }
             self.add_unresolved_identifier(
                 function_scope.clone().appended(arg_id.id.clone()),
@@ -99,7 +100,7 @@ impl<'a> Visitor for IdVisitor<'a> {
     fn visit_const_def(&mut self, c: &mut ConstantDef) -> VResult {
         self.add_unresolved_identifier(
             self.current_identifier(c.name.clone()),
-            IdentifierDefinitionType::ConstDef,
+            IdentifierDefinitionType::ConstDef(None),
             c.loc,
         )
     }
@@ -162,7 +163,7 @@ impl<'a> Visitor for IdVisitor<'a> {
 
     fn visit_import(&mut self, el: &mut ImportDirective) -> VResult {
         for item in el.aliased_identifier() {
-            let alias_dest = ScopedName::new(el.path.clone()).appended(item.id.clone());
+            let alias_dest = ScopedName::new(el.path.clone()).appended(item.source());
 
             // ensure destination is a valid id
             if self.identifiers.get_by_full_name(&alias_dest).is_none() {
@@ -185,19 +186,33 @@ impl<'a> Visitor for IdVisitor<'a> {
             IdentifierDefinitionType::Function,
             fun.loc,
         )?;
+        self.identifiers.set_function_decorators(function_scope.clone(), fun.decorators.clone());
+
+        // arguments and return values share a namespace
+        let mut arg_and_return_locs = HashMap::new();
+        for arg_id in fun.input_args.iter().chain(fun.return_values.iter().flatten()) {
+            if let Some(first) = arg_and_return_locs.insert(&arg_id.id, arg_id.loc) {
+                return Err(CairoError::Redefinition {
+                    name: function_scope.clone().appended(arg_id.id.clone()),
+                    first,
+                    second: arg_id.loc,
+                })
+            }
+        }
 
         let arg_scope = function_scope.clone().appended(ARG_SCOPE);
         self.add_unresolved_identifier(arg_scope, IdentifierDefinitionType::Struct(None), fun.loc)?;
 
         self.handle_function_arguments(function_scope.clone(), &fun.input_args)?;
 
+        // like the argument struct, the implicit argument struct exists even if it is empty
+        let implicit_arg_scope = function_scope.clone().appended(IMPLICIT_ARG_SCOPE);
+        self.add_unresolved_identifier(
+            implicit_arg_scope,
+            IdentifierDefinitionType::Struct(None),
+            fun.loc,
+        )?;
         if let Some(ref implicit) = fun.implicit_args {
-            let implicit_arg_scope = function_scope.clone().appended(IMPLICIT_ARG_SCOPE);
-            self.add_unresolved_identifier(
-                implicit_arg_scope,
-                IdentifierDefinitionType::Struct(None),
-                fun.loc,
-            )?;
             self.handle_function_arguments(function_scope.clone(), implicit)?;
         }
 
@@ -218,14 +233,14 @@ impl<'a> Visitor for IdVisitor<'a> {
             }
             for arg_id in arg_and_return_identifiers {
                 if implicit_arg_names.contains(&arg_id.id) {
-                    return Err(CairoError::Preprocess(format!("Arguments and return values cannot have the same name of an implicit argument {} at {}",arg_id.id, arg_id.loc)))
+                    return Err(CairoError::Preprocess(format!("Arguments and return values cannot have the same name of an implicit argument {} at {}",arg_id.id, arg_id.loc)).spanned(arg_id.loc))
                 }
             }
         }
 
         self.add_unresolved_identifier(
             function_scope.appended(N_LOCALS_CONSTANT),
-            IdentifierDefinitionType::ConstDef,
+            IdentifierDefinitionType::ConstDef(None),
             fun.loc,
         )
     }
@@ -289,7 +304,7 @@ impl<'a> Visitor for IdVisitor<'a> {
 
         self.add_unresolved_identifier(
             function_scope.appended(N_LOCALS_CONSTANT),
-            IdentifierDefinitionType::ConstDef,
+            IdentifierDefinitionType::ConstDef(None),
             ns.loc,
         )
     }
@@ -377,7 +392,7 @@ foo:
 local foo = [ap]
         "#;
         let res = try_visit(s).unwrap_err();
-        assert!(matches!(res, CairoError::Redefinition(_, _)));
+        assert!(matches!(res, CairoError::Redefinition { .. }));
 
         let s = r#"
 func bar():
@@ -387,7 +402,86 @@ func bar():
 end
         "#;
         let res = try_visit(s).unwrap_err();
-        assert!(matches!(res, CairoError::Redefinition(_, _)));
+        match res {
+            CairoError::Redefinition { ref name, first, second } => {
+                assert_eq!(name, &ScopedName::from_str("bar"));
+                assert_eq!(first.0, s.find("func bar").unwrap());
+                assert_eq!(second.0, s.rfind("func bar").unwrap());
+            }
+            ref err => panic!("unexpected error {:?}", err),
+        }
+        let msg = res.to_string();
+        assert!(msg.contains(&format!("{}", s.find("func bar").unwrap())), "{}", msg);
+        assert!(msg.contains(&format!("{}", s.rfind("func bar").unwrap())), "{}", msg);
+        assert_eq!(res.span().unwrap().0, s.rfind("func bar").unwrap());
+    }
+
+    #[test]
+    fn can_identify_argument_collisions() {
+        let s = "func f(a, a):\nend\n";
+        match try_visit(s).unwrap_err() {
+            CairoError::Redefinition { name, first, second } => {
+                assert_eq!(name, ScopedName::from_str("f.a"));
+                assert_eq!(first.0, s.find('a').unwrap());
+                assert_eq!(second.0, s.rfind('a').unwrap());
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+
+        let s = "func f(a) -> (a):\nend\n";
+        match try_visit(s).unwrap_err() {
+            CairoError::Redefinition { name, second, .. } => {
+                assert_eq!(name, ScopedName::from_str("f.a"));
+                assert_eq!(second.0, s.rfind('a').unwrap());
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+
+        let s = "func f() -> (a, a):\nend\n";
+        assert!(matches!(try_visit(s).unwrap_err(), CairoError::Redefinition { .. }));
+
+        try_visit("func f(a, b) -> (c, d):\nend\n").unwrap();
+    }
+
+    #[test]
+    fn can_collect_identifiers_in_with_attr() {
+        let s = r#"
+func foo():
+    with_attr error_message("oops"):
+        tempvar x = 1
+    end
+    ret
+end
+"#;
+        let ids = visit(s);
+        let x = ids.get_by_full_name(&ScopedName::from_str("foo.x")).unwrap();
+        assert!(x.is_unresolved_reference(), "{:?}", x);
+    }
+
+    #[test]
+    fn can_locate_definitions() {
+        let s = r#"
+const X = 1
+
+namespace ns:
+    func foo():
+        ret
+    end
+end
+"#;
+        let mut identifiers = visit(s);
+        let loc = identifiers.definition_location(&ScopedName::from_str("ns.foo")).unwrap();
+        assert_eq!(loc.0, s.find("func foo").unwrap());
+        let loc = identifiers.definition_location(&ScopedName::from_str("X")).unwrap();
+        assert_eq!(loc.0, s.find("const X").unwrap());
+
+        identifiers.add_identifier(
+            ScopedName::from_str("bar"),
+            IdentifierDefinitionType::Alias(ScopedName::from_str("ns.foo")),
+        );
+        let loc = identifiers.definition_location(&ScopedName::from_str("bar")).unwrap();
+        assert_eq!(loc.0, s.find("func foo").unwrap());
+        assert!(identifiers.definition_location(&ScopedName::from_str("missing")).is_none());
     }
 
     #[test]
@@ -397,7 +491,8 @@ from a import b as b0
         "#;
         let mut cairo = CairoFile::parse(s).unwrap();
         let mut identifiers = Identifiers::default();
-        identifiers.add_identifier(ScopedName::from_str("a.b"), IdentifierDefinitionType::ConstDef);
+        identifiers
+            .add_identifier(ScopedName::from_str("a.b"), IdentifierDefinitionType::ConstDef(None));
         identifiers.scope_tracker.enter_scope(Rc::new(ScopedName::root()));
         let mut vistor = IdVisitor { identifiers: &mut identifiers };
         cairo.visit(&mut vistor).unwrap();
@@ -406,4 +501,40 @@ from a import b as b0
 
         assert_eq!(scope, IdentifierDefinitionType::Alias(ScopedName::from_str("a.b")));
     }
+
+    #[test]
+    fn can_distinguish_aliases_from_duplicate_imports() {
+        let collect = |s: &str| {
+            let mut identifiers = Identifiers::default();
+            for name in ["a.b", "a.c"] {
+                identifiers.add_identifier(
+                    ScopedName::from_str(name),
+                    IdentifierDefinitionType::ConstDef(None),
+                );
+            }
+            identifiers.scope_tracker.enter_scope(Rc::new(ScopedName::root()));
+            let mut vistor = IdVisitor { identifiers: &mut identifiers };
+            CairoFile::parse(s).unwrap().visit(&mut vistor).map(|_| identifiers)
+        };
+        let alias = |identifiers: &Identifiers, name: &str| {
+            identifiers.identifiers.get(&ScopedName::from_str(name)).unwrap().as_ref().clone()
+        };
+
+        // two aliases of the same symbol
+        let identifiers = collect("from a import b as x\nfrom a import b as y\n").unwrap();
+        let dest = IdentifierDefinitionType::Alias(ScopedName::from_str("a.b"));
+        assert_eq!(alias(&identifiers, "x"), dest);
+        assert_eq!(alias(&identifiers, "y"), dest);
+        collect("from a import b\nfrom a import b as x\n").unwrap();
+
+        // the same bare import twice
+        let err = collect("from a import b\nfrom a import b\n").unwrap_err();
+        assert!(matches!(err, CairoError::Redefinition { ref name, .. } if name.name() == "b"));
+
+        // two different symbols under the same name
+        let err = collect("from a import b as x\nfrom a import c as x\n").unwrap_err();
+        assert!(matches!(err, CairoError::Redefinition { ref name, .. } if name.name() == "x"));
+        let err = collect("from a import b as c, c\n").unwrap_err();
+        assert!(matches!(err, CairoError::Redefinition { ref name, .. } if name.name() == "c"));
+    }
 }
@@ -9,30 +9,46 @@ use crate::{
         ModuleReader, VResult, Visitable,
     },
     error::{CairoError, Result},
-    parser::ast::ImportDirective,
+    parser::ast::{Directive, ImportDirective, Instruction},
     CairoFile,
 };
 use std::{
     collections::{HashMap, HashSet},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct ModuleCollectorPass {
     additional_modules: Vec<String>,
-    reader: ModuleReader,
+    reader: Box<dyn CodeReader>,
 }
 
 impl ModuleCollectorPass {
-    pub fn new(reader: ModuleReader) -> Self {
+    pub fn new(reader: impl CodeReader + 'static) -> Self {
         Self::with_modules(reader, Default::default())
     }
 
-    pub fn with_modules(reader: ModuleReader, additional_modules: Vec<String>) -> Self {
+    pub fn with_modules(
+        reader: impl CodeReader + 'static,
+        additional_modules: Vec<String>,
+    ) -> Self {
+        Self::with_boxed_reader(Box::new(reader), additional_modules)
+    }
+
+    pub(crate) fn with_boxed_reader(
+        reader: Box<dyn CodeReader>,
+        additional_modules: Vec<String>,
+    ) -> Self {
         Self { reader, additional_modules }
     }
 }
 
+impl Default for ModuleCollectorPass {
+    fn default() -> Self {
+        Self::new(ModuleReader::default())
+    }
+}
+
 impl Pass for ModuleCollectorPass {
     fn run(&mut self, prg: &mut PreprocessedProgram) -> Result<()> {
         log::trace!("starting pass: ModuleCollector");
@@ -40,32 +56,32 @@ impl Pass for ModuleCollectorPass {
 
         // resolve additional modules
         for module in &self.additional_modules {
-            let mut collector = ImportCollector::new(&self.reader);
+            let mut collector = ImportCollector::new(self.reader.as_ref());
             collector.collect_imports(module)?;
-            for (module_name, cairo_file) in collector.collected_files {
+            for (module_name, cairo_file, content) in collector.collected_files {
                 if visited.insert(module_name.clone()) {
-                    let scope = ScopedName::from_str(module_name);
-                    prg.modules.push(CairoModule::new(scope, cairo_file));
+                    let scope = module_name.parse::<ScopedName>()?;
+                    prg.modules.push(CairoModule::new(scope, cairo_file).with_content(content));
                 }
             }
         }
 
-        // resolve source files
+        // resolve source files, modules next to the importing file take precedence
         for content in &prg.codes {
-            let mut collector =
-                ImportCollector::new(InputCodeReader { reader: &self.reader, content });
+            let reader = self.reader.as_ref();
+            let mut collector = ImportCollector::new(InputCodeReader { reader, content });
             let file_name = content.name();
             collector.collect_imports(file_name.clone())?;
-            for (module_name, cairo_file) in collector.collected_files {
+            for (module_name, cairo_file, content) in collector.collected_files {
                 let scope = if module_name == file_name {
                     prg.main_scope.clone()
                 } else {
                     if !visited.insert(module_name.clone()) {
                         continue
                     }
-                    ScopedName::from_str(module_name)
+                    module_name.parse::<ScopedName>()?
                 };
-                prg.modules.push(CairoModule::new(scope, cairo_file));
+                prg.modules.push(CairoModule::new(scope, cairo_file).with_content(content));
             }
         }
 
@@ -73,8 +89,9 @@ impl Pass for ModuleCollectorPass {
     }
 }
 
+#[derive(Debug)]
 struct InputCodeReader<'a> {
-    reader: &'a ModuleReader,
+    reader: &'a dyn CodeReader,
     content: &'a CairoContent,
 }
 
@@ -86,13 +103,26 @@ impl<'a> CodeReader for InputCodeReader<'a> {
             self.reader.read(module)
         }
     }
+
+    fn with_cwd(&self, cwd: &Path) -> Option<Box<dyn CodeReader>> {
+        self.reader.with_cwd(cwd)
+    }
+
+    fn parse(&self, module: &str, code: &str) -> Result<CairoFile> {
+        self.reader.parse(module, code)
+    }
 }
 
 /// A helper visitor type that can collect all imports of a given module
+///
+/// The imports of a module are looked up relative to the directory of that module first.
 struct ImportCollector<T> {
     reader: T,
     current_ancestors: Vec<String>,
-    collected_files: HashMap<String, CairoFile>,
+    /// the directories of the current ancestors
+    current_dirs: Vec<PathBuf>,
+    /// all collected modules and their code, every module comes after its dependencies
+    collected_files: Vec<(String, CairoFile, CairoContent)>,
     langs: HashMap<String, Option<String>>,
 }
 
@@ -101,6 +131,7 @@ impl<T: CodeReader> ImportCollector<T> {
         Self {
             reader,
             current_ancestors: Default::default(),
+            current_dirs: Default::default(),
             collected_files: Default::default(),
             langs: Default::default(),
         }
@@ -109,23 +140,28 @@ impl<T: CodeReader> ImportCollector<T> {
     /// Scans all imports of the given module recursively
     pub fn collect_imports(&mut self, current_module: impl Into<String>) -> Result<()> {
         let current_module = current_module.into();
-        if self.current_ancestors.contains(&current_module) {
-            return Err(CairoError::CircularDependencies(std::mem::take(
-                &mut self.current_ancestors,
-            )))
+        if let Some(pos) = self.current_ancestors.iter().position(|m| m == &current_module) {
+            // only report the cycle, not the modules that lead to it
+            let mut cycle = self.current_ancestors.split_off(pos);
+            cycle.push(current_module);
+            return Err(CairoError::CircularDependencies(cycle))
         }
-        if self.collected_files.contains_key(&current_module) {
+        if self.collected_files.iter().any(|(module, _, _)| module == &current_module) {
             // file already parsed
             return Ok(())
         }
 
-        let (code, _) = self.reader.read(&current_module)?;
-        let mut cairo_file = CairoFile::parse(&code)?;
+        let relative_reader = self.current_dirs.last().and_then(|dir| self.reader.with_cwd(dir));
+        let reader = relative_reader.as_deref().unwrap_or(&self.reader);
+        let (code, path) = reader.read(&current_module)?;
+        let mut cairo_file = reader.parse(&current_module, &code)?;
+        check_directives_position(&cairo_file)?;
 
         let lang = LangVisitor::lang(&mut cairo_file)?;
 
         // add current package to ancestors list before scanning its dependencies.
         self.current_ancestors.push(current_module.clone());
+        self.current_dirs.push(path.parent().map(Path::to_path_buf).unwrap_or_default());
 
         // collect direct dependencies
         for pkg in DirectDependenciesCollector::deps(&mut cairo_file)? {
@@ -137,7 +173,12 @@ impl<T: CodeReader> ImportCollector<T> {
         }
 
         self.current_ancestors.pop();
-        self.collected_files.insert(current_module.clone(), cairo_file);
+        self.current_dirs.pop();
+        self.collected_files.push((
+            current_module.clone(),
+            cairo_file,
+            CairoContent::new(code, path),
+        ));
         self.langs.insert(current_module, lang);
         Ok(())
     }
@@ -162,10 +203,30 @@ impl Visitor for DirectDependenciesCollector {
     }
 }
 
+/// Ensures that `%builtins` and `%lang` directives precede all code elements of the file
+fn check_directives_position(file: &CairoFile) -> Result<()> {
+    let mut has_code_element = false;
+    for instruction in file {
+        match instruction {
+            Instruction::Directive(Directive::Builtins(loc, _) | Directive::Lang(loc, _))
+                if has_code_element =>
+            {
+                return Err(CairoError::Preprocess(format!(
+                    "Directives must appear at the top of the file: {}",
+                    loc
+                ))
+                .spanned(*loc))
+            }
+            Instruction::Directive(_) => {}
+            _ => has_code_element = true,
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::Path;
 
     fn test_reader() -> ModuleReader {
         let root = Path::new(&env!("CARGO_MANIFEST_DIR"));
@@ -179,4 +240,82 @@ mod tests {
         imports.collect_imports("imports").unwrap();
         assert!(!imports.collected_files.is_empty());
     }
+
+    #[test]
+    fn can_report_import_cycles() {
+        let reader = crate::compiler::InMemoryReader::new()
+            .with_module("main", "from a import x\n")
+            .with_module("a", "from b import y\nconst x = 1\n")
+            .with_module("b", "from c import z\nconst y = 1\n")
+            .with_module("c", "from a import x\nconst z = 1\n");
+        let err = ImportCollector::new(reader).collect_imports("main").unwrap_err();
+        match &err {
+            CairoError::CircularDependencies(cycle) => assert_eq!(cycle, &["a", "b", "c", "a"]),
+            err => panic!("unexpected error {}", err),
+        }
+        assert_eq!(err.to_string(), "Circular imports: a -> b -> c -> a");
+    }
+
+    #[test]
+    fn can_reject_misplaced_lang_directives() {
+        let collect = |code: &str| {
+            let reader = crate::compiler::InMemoryReader::new().with_module("a", code);
+            ImportCollector::new(reader).collect_imports("a")
+        };
+        collect("%lang starknet\n%builtins pedersen\nfunc f():\n    ret\nend\n").unwrap();
+
+        let err = collect("func f():\n    ret\nend\n%lang starknet\n").unwrap_err();
+        assert!(matches!(err.unspanned(), CairoError::Preprocess(_)), "{}", err);
+        assert!(err.to_string().starts_with("Directives must appear at the top of the file"));
+        assert!(err.span().is_some());
+
+        let err = collect("%lang starknet\n%lang starknet\n").unwrap_err();
+        assert!(err.to_string().starts_with("Found two %lang directives"), "{}", err);
+    }
+
+    #[test]
+    fn can_reject_misplaced_builtins_directives() {
+        let reader = crate::compiler::InMemoryReader::new()
+            .with_module("a", "func f():\n    ret\nend\n%builtins output pedersen\n");
+        let err = ImportCollector::new(reader).collect_imports("a").unwrap_err();
+        assert!(err.to_string().starts_with("Directives must appear at the top of the file"));
+    }
+
+    #[test]
+    fn can_reject_misplaced_lang_directives_in_imported_modules() {
+        let reader = crate::compiler::InMemoryReader::new()
+            .with_module("main", "%lang starknet\nfrom a import x\n")
+            .with_module("a", "const x = 1\n%lang starknet\n");
+        let err = ImportCollector::new(reader).collect_imports("main").unwrap_err();
+        assert!(err.to_string().starts_with("Directives must appear at the top of the file"));
+    }
+
+    #[test]
+    fn can_collect_relative_imports() {
+        let root = Path::new(&env!("CARGO_MANIFEST_DIR"));
+        let main = root.join("test-data/relative-imports/main.cairo");
+        let code = std::fs::read_to_string(&main).unwrap();
+        let mut prg = PreprocessedProgram::new(ScopedName::main_scope(), [(code, main)]);
+
+        // `c` also exists in the configured root, but the sibling of the importing file wins
+        let reader = ModuleReader::new([root.join("test-data/module-reader")]);
+        ModuleCollectorPass::new(reader).run(&mut prg).unwrap();
+
+        let module = |name: &str| {
+            prg.modules.iter().find(|m| m.module_name.as_ref() == &ScopedName::from_str(name))
+        };
+        assert!(module("sibling").is_some());
+        assert!(module("__main__").is_some());
+        let c = &module("c").unwrap().cairo_file;
+        assert!(
+            matches!(&c.0[0], crate::parser::ast::Instruction::Const(c) if c.name == "RELATIVE")
+        );
+
+        // imports of imported modules are resolved relative to the importing module
+        let dir = root.join("test-data/relative-imports");
+        let path = |name: &str| &module(name).unwrap().content.as_ref().unwrap().path;
+        assert_eq!(path("lib.outer"), &dir.join("lib/outer.cairo"));
+        assert_eq!(path("sub.inner"), &dir.join("lib/sub/inner.cairo"));
+        assert_eq!(path("leaf"), &dir.join("lib/sub/leaf.cairo"));
+    }
 }
@@ -1,11 +1,16 @@
 use crate::{
     compiler::{
-        sema::{passes::Pass, PreprocessedProgram},
+        sema::{
+            ast::{RefVisitor, Walk},
+            passes::Pass,
+            PreprocessedProgram, ScopedName,
+        },
         VResult, Visitable, Visitor,
     },
-    error::Result,
-    parser::ast::IfStatement,
+    error::{CairoError, Result},
+    parser::ast::{Call, Expr, FunctionDef, IfStatement, Instruction, Jmp, Namespace, RValue},
 };
+use std::collections::HashMap;
 
 /// Adds unique labels to `IfStatements`.
 #[derive(Debug, Default)]
@@ -38,3 +43,173 @@ impl Visitor for UniqueLabelPass {
         Ok(())
     }
 }
+
+/// Assigns each label and function the program counter offset of the instruction that follows it.
+///
+/// Only low level instructions can be sized for now, compound statements like `tempvar` or
+/// function calls are rejected.
+#[derive(Debug, Default)]
+pub struct LabelOffsetPass {
+    pc: usize,
+    scopes: Vec<ScopedName>,
+    labels: HashMap<ScopedName, usize>,
+}
+
+impl Pass for LabelOffsetPass {
+    fn run(&mut self, prg: &mut PreprocessedProgram) -> Result<()> {
+        log::trace!("starting pass: LabelOffset");
+        for module in prg.modules.iter() {
+            self.scopes.push(module.module_name.as_ref().clone());
+            module.cairo_file.walk(self)?;
+            self.scopes.pop();
+        }
+        prg.labels.extend(std::mem::take(&mut self.labels));
+        Ok(())
+    }
+}
+
+impl LabelOffsetPass {
+    fn current_scope(&self) -> ScopedName {
+        self.scopes.last().cloned().unwrap_or_else(ScopedName::root)
+    }
+}
+
+impl RefVisitor for LabelOffsetPass {
+    fn visit_instruction(&mut self, ins: &Instruction) -> VResult {
+        if let Instruction::Label(label, _) = ins {
+            let name = self.current_scope().extended(ScopedName::new(label.clone()));
+            self.labels.insert(name, self.pc);
+            return Ok(())
+        }
+        self.pc += instruction_size(ins)?;
+        Ok(())
+    }
+
+    fn enter_function(&mut self, f: &FunctionDef) -> VResult {
+        let function = self.current_scope().appended(&f.name);
+        self.labels.insert(function.clone(), self.pc);
+        self.scopes.push(function);
+        Ok(())
+    }
+
+    fn exit_function(&mut self, _: &FunctionDef) -> VResult {
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn enter_namespace(&mut self, ns: &Namespace) -> VResult {
+        self.scopes.push(self.current_scope().appended(&ns.name));
+        Ok(())
+    }
+
+    fn exit_namespace(&mut self, _: &Namespace) -> VResult {
+        self.scopes.pop();
+        Ok(())
+    }
+}
+
+/// Returns the number of memory cells the instruction occupies
+///
+/// Statements that don't emit code, and statements whose body is sized separately, take no space.
+fn instruction_size(ins: &Instruction) -> Result<usize> {
+    let size = match ins {
+        Instruction::Assign(dst, res, _) => 1 + has_immediate(dst, res),
+        Instruction::Jmp(jmp, _) => match jmp {
+            Jmp::Rel(expr) | Jmp::Abs(expr) | Jmp::RelIf(expr, _, _) => 1 + has_immediate(expr, expr),
+            Jmp::Id(_) | Jmp::IdIf(_, _, _) => 2,
+        },
+        Instruction::CallInstruction(call, _) => match call {
+            Call::Rel(expr) | Call::Abs(expr) => 1 + has_immediate(expr, expr),
+            Call::Id(_) => 2,
+        },
+        Instruction::ApAddAssign(expr, _) => 1 + has_immediate(expr, expr),
+        Instruction::Ret(_) | Instruction::DataWord(_, _) => 1,
+        Instruction::Let(_, rvalue, _) if matches!(**rvalue, RValue::Expr(_)) => 0,
+        Instruction::Const(_) |
+        Instruction::Member(_, _) |
+        Instruction::StaticAssert(_, _, _) |
+        Instruction::Label(_, _) |
+        Instruction::Function(_) |
+        Instruction::Struct(_) |
+        Instruction::Namespace(_) |
+        Instruction::WithAttrStatement(_) |
+        Instruction::WithStatement(_) |
+        Instruction::Hint(_, _) |
+        Instruction::Directive(_) |
+        Instruction::Import(_) |
+        // the size of the inner instruction is added when it's visited
+        Instruction::ApAdd(_, _) => 0,
+        ins => {
+            return Err(CairoError::Preprocess(format!(
+                "Cannot compute the size of `{}` yet",
+                ins
+            )))
+        }
+    };
+    Ok(size)
+}
+
+/// Whether the instruction needs an additional cell for an immediate value
+///
+/// Offsets of dereferenced registers are encoded in the instruction itself.
+fn has_immediate(lhs: &Expr, rhs: &Expr) -> usize {
+    fn imm(expr: &Expr) -> bool {
+        match expr {
+            Expr::Int(_, _) | Expr::HexInt(_, _) | Expr::ShortString(_, _) | Expr::Id(_, _) => true,
+            Expr::Register(_, _) | Expr::Deref(_, _) | Expr::Subscript(_, _, _) => false,
+            Expr::Cast(expr, _, _) | Expr::Neg(expr, _) => imm(expr),
+            Expr::Parentheses(exprs, _) => exprs.iter().any(|e| imm(e.expr())),
+            Expr::Add(lhs, rhs, _) |
+            Expr::Sub(lhs, rhs, _) |
+            Expr::Mul(lhs, rhs, _) |
+            Expr::Div(lhs, rhs, _) |
+            Expr::Pow(lhs, rhs, _) => imm(lhs) || imm(rhs),
+            _ => false,
+        }
+    }
+    (imm(lhs) || imm(rhs)) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compiler::sema::CairoModule, CairoFile};
+
+    #[test]
+    fn can_assign_label_offsets() {
+        let s = r#"
+start:
+[ap] = [fp]
+[ap] = 5; ap++
+jmp start
+call rel 3
+middle:
+ret
+func foo():
+    inner:
+    ret
+end
+"#;
+        let module = CairoModule::new(ScopedName::main_scope(), CairoFile::parse(s).unwrap());
+        let mut prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        LabelOffsetPass::default().run(&mut prg).unwrap();
+
+        assert_eq!(prg.labels[&ScopedName::from_str("__main__.start")], 0);
+        // 1 + 2 + 2 + 2
+        assert_eq!(prg.labels[&ScopedName::from_str("__main__.middle")], 7);
+        assert_eq!(prg.labels[&ScopedName::from_str("__main__.foo")], 8);
+        assert_eq!(prg.labels[&ScopedName::from_str("__main__.foo.inner")], 8);
+        assert_eq!(prg.labels.len(), 4);
+    }
+
+    #[test]
+    fn can_reject_unsized_instructions() {
+        let module = CairoModule::new(
+            ScopedName::main_scope(),
+            CairoFile::parse("tempvar x = 1\nlabel:\nret\n").unwrap(),
+        );
+        let mut prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        let err = LabelOffsetPass::default().run(&mut prg).unwrap_err();
+        assert!(err.to_string().starts_with("Cannot compute the size of `tempvar"), "{}", err);
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{
+    compiler::{
+        sema::{passes::Pass, PreprocessedProgram},
+        VResult, Visitable, Visitor,
+    },
+    error::{CairoError, Result},
+    parser::ast::{FunctionDef, Instruction},
+};
+
+/// Ensures `local` variables are only declared after `alloc_locals`
+#[derive(Debug, Default)]
+pub struct AllocLocalsPass;
+
+impl AllocLocalsPass {
+    /// Checks the instructions of a single function body, nested functions are checked separately
+    fn check(instructions: &[Instruction], allocated: &mut bool, function: &str) -> VResult {
+        for ins in instructions {
+            match ins {
+                Instruction::AllocLocals(_) => *allocated = true,
+                Instruction::Local(id, _, loc) if !*allocated => {
+                    return Err(CairoError::Preprocess(format!(
+                        "Local variable {} in function {} is declared before alloc_locals {}",
+                        id.id, function, loc
+                    ))
+                    .spanned(*loc))
+                }
+                Instruction::If(stmt) => {
+                    Self::check(&stmt.instructions, allocated, function)?;
+                    if let Some(else_branch) = stmt.else_branch.as_ref() {
+                        Self::check(else_branch, allocated, function)?;
+                    }
+                }
+                Instruction::WithStatement(w) => Self::check(&w.instructions, allocated, function)?,
+                Instruction::WithAttrStatement(w) => {
+                    Self::check(&w.instructions, allocated, function)?
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Pass for AllocLocalsPass {
+    fn run(&mut self, prg: &mut PreprocessedProgram) -> Result<()> {
+        log::trace!("starting pass: AllocLocals");
+        for module in prg.modules.iter_mut() {
+            module.cairo_file.visit(self)?;
+        }
+        Ok(())
+    }
+}
+
+impl Visitor for AllocLocalsPass {
+    fn visit_function(&mut self, f: &mut FunctionDef) -> VResult {
+        Self::check(&f.instructions, &mut false, &f.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CairoFile;
+
+    fn check(code: &str) -> Result<()> {
+        let mut file = CairoFile::parse(code).unwrap();
+        file.visit(&mut AllocLocalsPass)
+    }
+
+    #[test]
+    fn can_detect_missing_alloc_locals() {
+        check(
+            r#"
+func foo():
+    alloc_locals
+    local x = 1
+    ret
+end
+"#,
+        )
+        .unwrap();
+
+        let err = check(
+            r#"
+func foo():
+    local x = 1
+    alloc_locals
+    ret
+end
+"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().starts_with("Local variable x in function foo"), "{}", err);
+
+        // nested functions don't inherit `alloc_locals`
+        let err = check(
+            r#"
+func foo():
+    alloc_locals
+    func bar():
+        local y = 1
+        ret
+    end
+    ret
+end
+"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().starts_with("Local variable y in function bar"), "{}", err);
+    }
+}
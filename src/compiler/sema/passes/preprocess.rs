@@ -1,54 +1,677 @@
 use crate::{
     compiler::{
-        sema::{passes::Pass, PreprocessedProgram},
-        Visitor,
+        constants::RETURN_SCOPE,
+        data::ProgramHint,
+        sema::{
+            const_eval::eval_const,
+            identifiers::IdentifierDefinitionType,
+            passes::{decorators::DecoratorValidationPass, Pass},
+            type_inference::try_infer_type,
+            CairoModule, PreprocessedProgram, ScopedName,
+        },
+        VResult, Visitable, Visitor,
+    },
+    error::{CairoError, Result},
+    parser::ast::{
+        CairoType, Call, ConstantDef, Expr, ExprAssignment, FunctionDef, Loc, Namespace, RValue,
+        TypedIdentifier,
     },
-    error::Result,
 };
-use ethers::types::U256;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 /// Collects extra information during preprocessing.
-#[derive(Debug)]
-pub struct AuxiliaryInfo {}
+#[derive(Debug, Clone, Default)]
+pub struct AuxiliaryInfo {
+    /// All hints, keyed by the scope they appear in
+    pub hints: HashMap<ScopedName, Vec<HintInfo>>,
+    /// Variables that are declared but never referenced, see `UnusedVariablesPass`
+    pub unused_variables: Vec<(ScopedName, Loc)>,
+}
+
+impl AuxiliaryInfo {
+    /// Returns all hints in the format of the `hints` of a compiled program
+    ///
+    /// Until instructions are assigned a pc, hints are keyed by their index in the order of their
+    /// module and their position in it.
+    pub fn program_hints(&self) -> BTreeMap<String, Vec<ProgramHint>> {
+        let mut hints = self.hints.values().flatten().collect::<Vec<_>>();
+        hints
+            .sort_by_key(|hint| (hint.accessible_scopes.first().map(ScopedName::name), hint.loc.0));
+        hints
+            .into_iter()
+            .enumerate()
+            .map(|(idx, hint)| (idx.to_string(), vec![hint.to_program_hint()]))
+            .collect()
+    }
+}
+
+/// A `%{ ... %}` hint and the scopes accessible from it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HintInfo {
+    pub code: String,
+    pub accessible_scopes: Vec<ScopedName>,
+    pub loc: Loc,
+}
+
+impl HintInfo {
+    /// Converts the hint into a hint of a compiled program, without any flow tracking yet
+    pub fn to_program_hint(&self) -> ProgramHint {
+        ProgramHint {
+            accessible_scopes: self.accessible_scopes.iter().map(ScopedName::name).collect(),
+            code: self.code.clone(),
+            flow_tracking_data: Default::default(),
+        }
+    }
+}
+
+/// Decorators that are supported by plain cairo
+pub const DEFAULT_SUPPORTED_DECORATORS: &[&str] = &["known_ap_change"];
 
 /// The pass that does the actual preprocessing
 #[derive(Debug)]
 pub struct PreprocessPass {
-    /// The prime to compile for
-    pub prime: U256,
     /// A set of decorators that may appear before a function declaration
     pub supported_decorators: HashSet<String>,
 }
 
-impl PreprocessPass {}
+impl Default for PreprocessPass {
+    fn default() -> Self {
+        Self {
+            supported_decorators: DEFAULT_SUPPORTED_DECORATORS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl PreprocessPass {
+    /// Replaces the set of supported decorators
+    pub fn with_supported_decorators<I, S>(mut self, decorators: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.supported_decorators = decorators.into_iter().map(Into::into).collect();
+        self
+    }
+}
 
 impl Pass for PreprocessPass {
-    fn run(&mut self, _prg: &mut PreprocessedProgram) -> Result<()> {
+    fn run(&mut self, prg: &mut PreprocessedProgram) -> Result<()> {
         log::trace!("starting pass: Preprocessor");
-        // for module in prg.modules.iter_mut() {
-        //     prg.identifiers.scope_tracker_mut().enter_scope(module.module_name.clone());
-        //     prg.identifiers.scope_tracker_mut().enter_lang(module.lang()?);
+        DecoratorValidationPass::new(self.supported_decorators.clone()).run(prg)?;
+        prg.check_main_function()?;
+        // the visitor needs the whole program, so the modules are detached while visiting them
+        let mut modules = std::mem::take(&mut prg.modules);
+        let result = modules.iter_mut().try_for_each(|module| preprocess_module(prg, module));
+        // put the modules back even if preprocessing failed
+        prg.modules = modules;
+        result
+    }
+}
 
-        //     let mut visitor = PreprocessVisitor::new(prg);
-        //     module.cairo_file.visit(&mut visitor)?;
+/// Preprocesses a single module, the scopes are restored even if preprocessing fails
+fn preprocess_module(prg: &mut PreprocessedProgram, module: &mut CairoModule) -> Result<()> {
+    let lang = module.lang()?;
+    let tracker = prg.identifiers.scope_tracker_mut();
+    let depth = tracker.accessible_scopes().len();
+    tracker.enter_scope(module.module_name.clone());
+    tracker.enter_lang(lang);
 
-        //     prg.identifiers.scope_tracker_mut().exit_scope();
-        //     prg.identifiers.scope_tracker_mut().exit_lang();
-        // }
-        Ok(())
-    }
+    let mut visitor = PreprocessVisitor::new(prg);
+    let result = module.cairo_file.visit(&mut visitor);
+
+    let tracker = prg.identifiers.scope_tracker_mut();
+    tracker.exit_scopes_to(depth);
+    tracker.exit_lang();
+    result
 }
 
 struct PreprocessVisitor<'a> {
-    pass: &'a PreprocessPass,
     prg: &'a mut PreprocessedProgram,
 }
 
 impl<'a> PreprocessVisitor<'a> {
-    fn new(_prg: &'a mut PreprocessedProgram) -> Self {
-        todo!()
+    fn new(prg: &'a mut PreprocessedProgram) -> Self {
+        Self { prg }
+    }
+
+    /// Checks that the expression bound to the identifier is well typed and, if the identifier is
+    /// typed, that the types match
+    fn check_binding(&self, id: &TypedIdentifier, expr: &Expr) -> VResult {
+        let identifiers = &self.prg.identifiers;
+        let (declared, ty) = match (&id.ty, try_infer_type(expr, identifiers)?) {
+            (Some(declared), Some(ty)) => (declared, ty),
+            _ => return Ok(()),
+        };
+        // tuples can be assigned to structs with the same members
+        if matches!(ty, CairoType::Tuple(_)) {
+            return Ok(())
+        }
+        let declared =
+            identifiers.resolve_type(declared.clone()).map_err(|err| err.spanned(id.loc))?;
+        // the types may only differ in their locations
+        if declared.to_string() != ty.to_string() {
+            let loc = expr.loc();
+            return Err(CairoError::Preprocess(format!(
+                "Expected expression of type {} for {}, found {} {}",
+                declared, id.id, ty, loc
+            ))
+            .spanned(loc))
+        }
+        Ok(())
     }
 }
 
-impl<'a> Visitor for PreprocessVisitor<'a> {}
+impl<'a> Visitor for PreprocessVisitor<'a> {
+    fn visit_const_def(&mut self, c: &mut ConstantDef) -> VResult {
+        match c.ty {
+            None | Some(CairoType::Felt) => {}
+            Some(ref ty) => {
+                return Err(CairoError::Preprocess(format!(
+                    "Constants must be of type felt, found {} {}",
+                    ty, c.loc
+                ))
+                .spanned(c.loc))
+            }
+        }
+        let identifiers = &mut self.prg.identifiers;
+        let value = eval_const(&c.init, &self.prg.prime, identifiers)?;
+        let name = identifiers.current_scope().as_ref().clone().appended(c.name.clone());
+        identifiers.add_name_definition(
+            name,
+            IdentifierDefinitionType::ConstDef(Some(value)),
+            c.loc,
+            true,
+        )
+    }
+
+    fn visit_ap_add_assign(&mut self, expr: &mut Expr, loc: Loc) -> VResult {
+        let value = eval_const(expr, &self.prg.prime, &self.prg.identifiers).map_err(|_| {
+            CairoError::Preprocess(format!(
+                "ap += expects a constant expression, found {} {}",
+                expr, loc
+            ))
+            .spanned(loc)
+        })?;
+        // like data words, struct sizes and constants can't be resolved by the assembler
+        *expr = Expr::HexInt(format!("{:#x}", value), loc);
+        Ok(())
+    }
+
+    fn visit_data_word(&mut self, expr: &mut Expr, loc: Loc) -> VResult {
+        let value = eval_const(expr, &self.prg.prime, &self.prg.identifiers).map_err(|_| {
+            CairoError::Preprocess(format!(
+                "dw expects a constant expression, found {} {}",
+                expr, loc
+            ))
+            .spanned(loc)
+        })?;
+        // replace the expression with its value, so it doesn't need to be resolved again
+        *expr = Expr::HexInt(format!("{:#x}", value), loc);
+        Ok(())
+    }
+
+    fn visit_static_assert(&mut self, lhs: &mut Expr, rhs: &mut Expr, loc: Loc) -> VResult {
+        let eval = |expr: &Expr| {
+            eval_const(expr, &self.prg.prime, &self.prg.identifiers).map_err(|_| {
+                CairoError::Preprocess(format!(
+                    "static_assert expects a constant expression, found {} {}",
+                    expr, loc
+                ))
+                .spanned(loc)
+            })
+        };
+        let (lhs_value, rhs_value) = (eval(lhs)?, eval(rhs)?);
+        if lhs_value != rhs_value {
+            return Err(CairoError::Preprocess(format!(
+                "Static assert failed: {} != {} {}",
+                lhs, rhs, loc
+            ))
+            .spanned(loc))
+        }
+        Ok(())
+    }
+
+    fn visit_return(&mut self, exprs: &mut [ExprAssignment], loc: Loc) -> VResult {
+        let identifiers = &self.prg.identifiers;
+        let return_scope = identifiers.current_scope().as_ref().clone().appended(RETURN_SCOPE);
+        let return_struct =
+            match identifiers.get_by_full_name(&return_scope).and_then(|ty| ty.as_struct()) {
+                Some(return_struct) => return_struct,
+                // not inside a function
+                None => return Ok(()),
+            };
+        let members = &return_struct.members;
+        if exprs.len() != members.len() {
+            return Err(CairoError::Preprocess(format!(
+                "Expected {} return values, found {} {}",
+                members.len(),
+                exprs.len(),
+                loc
+            ))
+            .spanned(loc))
+        }
+        // named values must match the declared return values in order
+        for (expr, member) in exprs.iter().zip(members) {
+            if let ExprAssignment::Id(name, _, expr_loc) = expr {
+                if name != &member.name {
+                    return Err(CairoError::Preprocess(format!(
+                        "Expected return value {}, found {} {}",
+                        member.name, name, expr_loc
+                    ))
+                    .spanned(*expr_loc))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_unpack_binding(
+        &mut self,
+        ids: &mut [TypedIdentifier],
+        rvalue: &mut RValue,
+    ) -> VResult {
+        let callee = match rvalue {
+            RValue::Expr(Expr::FunctionCall(call)) => ScopedName::new(call.id.clone()),
+            RValue::Call(Call::Id(id)) => ScopedName::new(id.clone()),
+            _ => return Ok(()),
+        };
+        let identifiers = &mut self.prg.identifiers;
+        let function = match identifiers
+            .search_current_scopes(&callee)
+            .and_then(|resolved| identifiers.get_function(&resolved.canonical_name))
+        {
+            Ok(function) => function,
+            // unknown callees are reported when the call is resolved
+            Err(_) => return Ok(()),
+        };
+        let loc = match (ids.first(), ids.last()) {
+            (Some(first), Some(last)) => Loc(first.loc.0, last.loc.1),
+            _ => return Ok(()),
+        };
+        let members = &function.return_values.members;
+        if ids.len() != members.len() {
+            return Err(CairoError::Preprocess(format!(
+                "Expected {} identifiers to unpack the return values of {}, found {} {}",
+                members.len(),
+                function.full_name,
+                ids.len(),
+                loc
+            ))
+            .spanned(loc))
+        }
+        for (id, member) in ids.iter().zip(members) {
+            if let Some(ty) = &id.ty {
+                let ty = identifiers.resolve_type(ty.clone())?;
+                if ty != member.cairo_type {
+                    return Err(CairoError::Preprocess(format!(
+                        "Expected {} to be of type {}, found {} {}",
+                        id.id, member.cairo_type, ty, loc
+                    ))
+                    .spanned(loc))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_local_var(&mut self, id: &mut TypedIdentifier, expr: &mut Option<Expr>) -> VResult {
+        expr.as_ref().map_or(Ok(()), |expr| self.check_binding(id, expr))
+    }
+
+    fn visit_temp_var(&mut self, id: &mut TypedIdentifier, expr: &mut Option<Expr>) -> VResult {
+        expr.as_ref().map_or(Ok(()), |expr| self.check_binding(id, expr))
+    }
+
+    fn visit_element_reference(&mut self, id: &mut TypedIdentifier, expr: &mut Expr) -> VResult {
+        self.check_binding(id, expr)
+    }
+
+    fn visit_expr_cat(&mut self, _: &mut Expr, ty: &mut CairoType, loc: Loc) -> VResult {
+        *ty = self.prg.identifiers.resolve_type(ty.clone()).map_err(|err| err.spanned(loc))?;
+        Ok(())
+    }
+
+    fn visit_hint(&mut self, hint: &mut String, loc: Loc) -> VResult {
+        let identifiers = &self.prg.identifiers;
+        let info = HintInfo {
+            code: hint.clone(),
+            accessible_scopes: identifiers
+                .accessible_scopes()
+                .iter()
+                .map(|scope| scope.as_ref().clone())
+                .collect(),
+            loc,
+        };
+        let scope = identifiers.current_scope().as_ref().clone();
+        self.prg.auxiliary_info.hints.entry(scope).or_default().push(info);
+        Ok(())
+    }
+
+    fn enter_function(&mut self, f: &mut FunctionDef) -> VResult {
+        self.prg.identifiers.enter_function(f)
+    }
+
+    fn exit_function(&mut self, f: &mut FunctionDef) -> VResult {
+        self.prg.identifiers.exit_function(f)
+    }
+
+    fn enter_namespace(&mut self, n: &mut Namespace) -> VResult {
+        self.prg.identifiers.enter_namespace(n)
+    }
+
+    fn exit_namespace(&mut self, n: &mut Namespace) -> VResult {
+        self.prg.identifiers.exit_namespace(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compiler::sema::passes::{
+            identifier::IdentifierCollectorPass, struct_collect::StructCollectorPass,
+        },
+        parser::ast::{CairoFile, Instruction},
+    };
+
+    fn preprocess(code: &str) -> Result<PreprocessedProgram> {
+        let module = CairoModule::new(ScopedName::main_scope(), CairoFile::parse(code).unwrap());
+        let mut prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        IdentifierCollectorPass::default().run(&mut prg)?;
+        StructCollectorPass::default().run(&mut prg)?;
+        PreprocessPass::default().run(&mut prg)?;
+        Ok(prg)
+    }
+
+    #[test]
+    fn can_evaluate_constants() {
+        let prg = preprocess(
+            r#"
+const A = 3
+const B = A * 2 + 1
+namespace foo:
+    const C = B - A
+end
+"#,
+        )
+        .unwrap();
+        let value = |name: &str| prg.identifiers.get(&name.into()).unwrap().ty;
+        assert_eq!(*value("__main__.B"), IdentifierDefinitionType::ConstDef(Some(7.into())));
+        assert_eq!(*value("__main__.foo.C"), IdentifierDefinitionType::ConstDef(Some(4.into())));
+
+        let err = preprocess("const A = B\nconst B = 1\n").unwrap_err();
+        assert!(err.to_string().contains("is not a constant"), "{}", err);
+    }
+
+    #[test]
+    fn can_resolve_cast_types() {
+        let prg = preprocess(
+            r#"
+struct MyStruct:
+    member x : felt
+end
+namespace foo:
+    func main():
+        tempvar p = cast([ap], MyStruct*)
+        ret
+    end
+end
+"#,
+        )
+        .unwrap();
+        let code = prg.modules[0].cairo_file.to_string();
+        assert!(code.contains("cast([ap], __main__.MyStruct*)"), "{}", code);
+
+        let err = preprocess("func main():\n    tempvar p = cast([ap], Missing*)\n    ret\nend\n")
+            .unwrap_err();
+        assert!(err.span().is_some(), "{}", err);
+    }
+
+    #[test]
+    fn can_fold_data_words() {
+        let prg = preprocess("const A = 5\ndw 0x1234\ndw A + 3\n").unwrap();
+        let words: Vec<_> = (&prg.modules[0].cairo_file)
+            .into_iter()
+            .filter_map(|ins| match ins {
+                Instruction::DataWord(expr, _) => expr.as_u256(),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(words, [0x1234.into(), 8.into()]);
+
+        let err = preprocess("dw [ap]\n").unwrap_err();
+        assert!(err.to_string().starts_with("dw expects a constant expression, found [ap]"));
+        assert!(err.span().is_some());
+    }
+
+    #[test]
+    fn can_check_static_asserts() {
+        preprocess("const A = 2\nfunc foo():\n    static_assert A + 2 == 4\n    ret\nend\n")
+            .unwrap();
+
+        let err = preprocess("func foo():\n    static_assert 1 == 2\n    ret\nend\n").unwrap_err();
+        assert!(err.to_string().starts_with("Static assert failed: 1 != 2"), "{}", err);
+        assert!(err.span().is_some());
+
+        let err = preprocess("func foo(x):\n    static_assert x == 2\n    ret\nend\n").unwrap_err();
+        assert!(
+            err.to_string().starts_with("static_assert expects a constant expression, found x"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn can_validate_constant_types() {
+        let prg = preprocess("const A = 1\nconst B : felt = 2\n").unwrap();
+        let value = |name: &str| prg.identifiers.get(&name.into()).unwrap().ty;
+        assert_eq!(*value("__main__.B"), IdentifierDefinitionType::ConstDef(Some(2.into())));
+
+        let err =
+            preprocess("struct MyStruct:\n    member x : felt\nend\nconst C : MyStruct = 1\n")
+                .unwrap_err();
+        assert!(
+            err.to_string().starts_with("Constants must be of type felt, found MyStruct"),
+            "{}",
+            err
+        );
+        assert!(err.span().is_some());
+    }
+
+    #[test]
+    fn can_validate_decorators() {
+        preprocess("@known_ap_change\nfunc foo():\n    ret\nend\n").unwrap();
+
+        let err = preprocess("@totally_made_up\nfunc foo():\n    ret\nend\n").unwrap_err();
+        assert!(err.to_string().starts_with("Unsupported decorator @totally_made_up"), "{}", err);
+    }
+
+    #[test]
+    fn can_validate_ap_add_assign() {
+        preprocess("ap += 3\n").unwrap();
+        preprocess("const A = 2\nap += A * 3\n").unwrap();
+
+        // struct sizes are constants, including the argument structs of functions
+        let prg = preprocess(
+            r#"
+struct Point:
+    member x : felt
+    member y : felt
+end
+func main{range_check_ptr}(p : Point):
+    ret
+end
+ap += Point.SIZE + main.Args.SIZE + main.ImplicitArgs.SIZE + main.Return.SIZE
+"#,
+        )
+        .unwrap();
+        let sizes: Vec<_> = (&prg.modules[0].cairo_file)
+            .into_iter()
+            .filter_map(|ins| match ins {
+                Instruction::ApAddAssign(expr, _) => expr.as_u256(),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(sizes, [5.into()]);
+
+        let err =
+            preprocess("struct Point:\n    member x : felt\nend\nap += Point.x\n").unwrap_err();
+        assert!(err.to_string().starts_with("ap += expects a constant expression"), "{}", err);
+
+        let err = preprocess("ap += [ap]\n").unwrap_err();
+        assert!(err.to_string().starts_with("ap += expects a constant expression"), "{}", err);
+    }
+
+    #[test]
+    fn can_restore_program_on_error() {
+        let module = CairoModule::new(
+            ScopedName::main_scope(),
+            CairoFile::parse("namespace foo:\n    func bar():\n        dw [ap]\n    end\nend\n")
+                .unwrap(),
+        );
+        let mut prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        IdentifierCollectorPass::default().run(&mut prg).unwrap();
+        let scopes = prg.identifiers.accessible_scopes().to_vec();
+
+        assert!(PreprocessPass::default().run(&mut prg).is_err());
+        assert_eq!(prg.modules.len(), 1);
+        assert_eq!(prg.identifiers.accessible_scopes(), scopes.as_slice());
+    }
+
+    #[test]
+    fn can_validate_return_values() {
+        let code =
+            |ret: &str| format!("func foo() -> (a : felt, b : felt):\n    return ({})\nend\n", ret);
+        preprocess(&code("a=1, b=2")).unwrap();
+        preprocess(&code("1, b=2")).unwrap();
+        preprocess(&code("1, 2")).unwrap();
+        preprocess("func foo():\n    return ()\nend\n").unwrap();
+
+        let err = preprocess(&code("a=1, wrong=2")).unwrap_err();
+        assert!(err.to_string().starts_with("Expected return value b, found wrong"), "{}", err);
+        let err = preprocess(&code("b=1, a=2")).unwrap_err();
+        assert!(err.to_string().starts_with("Expected return value a, found b"), "{}", err);
+        let err = preprocess(&code("1")).unwrap_err();
+        assert!(err.to_string().starts_with("Expected 2 return values, found 1"), "{}", err);
+    }
+
+    #[test]
+    fn can_validate_unpack_bindings() {
+        let code = |ret: &str, binding: &str| {
+            format!(
+                "struct S:\n    member x : felt\nend\nfunc f() -> ({}):\n    ret\nend\nfunc g():\n    let {} = f()\n    ret\nend\n",
+                ret, binding
+            )
+        };
+        preprocess(&code("a : felt, b : felt", "(a, b)")).unwrap();
+        preprocess(&code("a : felt, b : S*", "(a : felt, b : S*)")).unwrap();
+
+        let err = preprocess(&code("a : felt", "(a, b)")).unwrap_err();
+        assert!(
+            err.to_string().starts_with(
+                "Expected 1 identifiers to unpack the return values of __main__.f, found 2"
+            ),
+            "{}",
+            err
+        );
+        assert!(err.span().is_some());
+        let err = preprocess(&code("a : felt, b : felt", "(a, b : S*)")).unwrap_err();
+        assert!(
+            err.to_string().starts_with("Expected b to be of type felt, found __main__.S*"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn can_infer_binding_types() {
+        let code = |binding: &str| {
+            format!(
+                r#"
+namespace Foo:
+    struct Inner:
+        member x : felt
+    end
+
+    struct Outer:
+        member inner : Inner*
+    end
+
+    func f(a : felt):
+        {}
+        ret
+    end
+end
+"#,
+                binding
+            )
+        };
+        preprocess(&code("tempvar y = [ap] + 1")).unwrap();
+        preprocess(&code("tempvar y : felt* = ap + 1")).unwrap();
+        // members are resolved in the accessible scopes of the struct
+        preprocess(&code("tempvar y : felt = cast(fp, Outer*).inner.x")).unwrap();
+        preprocess(&code("let y : Inner* = cast(fp, Outer*).inner")).unwrap();
+        // the types of references are not known yet
+        preprocess(&code("tempvar y : felt* = [a]")).unwrap();
+
+        let err = preprocess(&code("tempvar y = [5]")).unwrap_err();
+        assert!(err.to_string().starts_with("Cannot dereference type felt"), "{}", err);
+        let err = preprocess(&code("let y = ap + fp")).unwrap_err();
+        assert!(err.to_string().starts_with("Operator '+' is not implemented"), "{}", err);
+        let err = preprocess(&code("local y : felt* = [ap]")).unwrap_err();
+        assert!(
+            err.to_string().starts_with("Expected expression of type felt* for y, found felt"),
+            "{}",
+            err
+        );
+        assert!(err.span().is_some());
+        let err = preprocess(&code("tempvar y = cast(fp, Outer*).missing")).unwrap_err();
+        assert!(
+            err.to_string().starts_with("Member missing does not exist in __main__.Foo.Outer"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn can_collect_hints() {
+        let code = r#"
+func foo():
+    %{ memory[ap] = 1 %}
+    ret
+end
+"#;
+        let prg = preprocess(code).unwrap();
+
+        let hints = &prg.auxiliary_info.hints[&"__main__.foo".into()];
+        assert_eq!(hints.len(), 1);
+        assert_eq!(hints[0].code.trim(), "memory[ap] = 1");
+        assert_eq!(
+            hints[0].accessible_scopes,
+            vec![ScopedName::main_scope(), ScopedName::from("__main__.foo")]
+        );
+    }
+
+    #[test]
+    fn can_convert_to_program_hints() {
+        let code = r#"
+%{ x = 0 %}
+func foo():
+    %{ x = 1 %}
+    ret
+end
+"#;
+        let prg = preprocess(code).unwrap();
+        let hints = prg.auxiliary_info.program_hints();
+        assert_eq!(hints.len(), 2);
+        assert_eq!(hints["0"][0].code.trim(), "x = 0");
+        assert_eq!(hints["0"][0].accessible_scopes, vec!["__main__"]);
+        assert_eq!(hints["1"].len(), 1);
+        assert_eq!(hints["1"][0].code.trim(), "x = 1");
+        assert_eq!(hints["1"][0].accessible_scopes, vec!["__main__", "__main__.foo"]);
+        assert!(hints["1"][0].flow_tracking_data.reference_ids.is_empty());
+    }
+}
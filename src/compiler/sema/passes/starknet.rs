@@ -0,0 +1,443 @@
+use crate::{
+    compiler::{
+        data::{Abi, AbiType, Item},
+        sema::{
+            ast::{RefVisitor, Walk},
+            passes::{preprocess::DEFAULT_SUPPORTED_DECORATORS, Pass},
+            PreprocessedProgram, ScopedName,
+        },
+        VResult, Visitable, Visitor,
+    },
+    error::{CairoError, Result},
+    parser::ast::{Decorator, FunctionDef, Identifier, Instruction, Loc, TypedIdentifier},
+};
+
+/// Decorators besides the ones in `Decorator` that are supported
+const SUPPORTED_DECORATORS: &[&str] =
+    &["known_ap_change", "l1_handler", "event", "contract_interface", "raw_input", "raw_output"];
+
+/// Decorators with a dedicated `Decorator` variant that StarkNet contracts may use
+const STARKNET_DECORATORS: &[&str] = &["view", "external", "constructor", "storage_var"];
+
+/// All decorators the preprocessor accepts in StarkNet contracts
+pub(crate) fn starknet_supported_decorators() -> impl Iterator<Item = &'static str> {
+    DEFAULT_SUPPORTED_DECORATORS
+        .iter()
+        .chain(STARKNET_DECORATORS)
+        .chain(SUPPORTED_DECORATORS)
+        .copied()
+}
+
+/// The only `%lang` StarkNet contracts can be written in
+const STARKNET_LANG: &str = "starknet";
+
+/// Validates the `%lang` directive and the decorators of StarkNet contract functions
+#[derive(Debug, Default)]
+pub struct StarknetPreprocessPass;
+
+impl Pass for StarknetPreprocessPass {
+    fn run(&mut self, prg: &mut PreprocessedProgram) -> Result<()> {
+        log::trace!("starting pass: Starknet Preprocess");
+        for module in prg.modules.iter_mut() {
+            module.cairo_file.visit(self)?;
+        }
+        Ok(())
+    }
+}
+
+impl Visitor for StarknetPreprocessPass {
+    fn visit_lang(&mut self, id: &mut Identifier, loc: Loc) -> VResult {
+        let lang = id.join(".");
+        if lang != STARKNET_LANG {
+            return Err(CairoError::Preprocess(format!(
+                "Unsupported %lang directive {}, expected {} {}",
+                lang, STARKNET_LANG, loc
+            ))
+            .spanned(loc))
+        }
+        Ok(())
+    }
+
+    fn visit_function(&mut self, f: &mut FunctionDef) -> VResult {
+        let mut entry_point = None;
+        for decorator in f.decorators.iter() {
+            match decorator {
+                Decorator::Other(name) if !SUPPORTED_DECORATORS.contains(&name.as_str()) => {
+                    return Err(CairoError::Preprocess(format!(
+                        "Unsupported decorator {} for function `{}` {}",
+                        decorator,
+                        f.signature_string(),
+                        f.loc
+                    ))
+                    .spanned(f.loc))
+                }
+                Decorator::StorageVar if f.decorators.len() > 1 => {
+                    return Err(CairoError::Preprocess(format!(
+                        "Storage variable {} can not have other decorators {}",
+                        f.name, f.loc
+                    ))
+                    .spanned(f.loc))
+                }
+                Decorator::View | Decorator::External | Decorator::Constructor => {
+                    if let Some(other) = entry_point.replace(decorator) {
+                        return Err(CairoError::Preprocess(format!(
+                            "Function {} has multiple entry point decorators {} and {} {}",
+                            f.name, other, decorator, f.loc
+                        ))
+                        .spanned(f.loc))
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rejects low level flow instructions, `jmp`, `call`, `ret` and `dw`, in the body of StarkNet
+/// entry point functions
+#[derive(Debug, Default)]
+pub struct EntryPointInstructionsPass {
+    /// the name and location of the entry point function that is currently visited
+    entry_point: Option<(String, Loc)>,
+}
+
+impl Pass for EntryPointInstructionsPass {
+    fn run(&mut self, prg: &mut PreprocessedProgram) -> Result<()> {
+        log::trace!("starting pass: EntryPointInstructions");
+        for module in prg.modules.iter() {
+            module.cairo_file.walk(self)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the name and location of the instruction if it's a low level flow instruction
+fn low_level_instruction(ins: &Instruction) -> Option<(&'static str, Option<Loc>)> {
+    match ins {
+        Instruction::Jmp(_, loc) => Some(("jmp", Some(*loc))),
+        Instruction::CallInstruction(_, loc) => Some(("call", Some(*loc))),
+        Instruction::Ret(loc) => Some(("ret", Some(*loc))),
+        Instruction::DataWord(_, loc) => Some(("dw", Some(*loc))),
+        Instruction::ApAdd(ins, loc) => {
+            low_level_instruction(ins).map(|(name, ins_loc)| (name, ins_loc.or(Some(*loc))))
+        }
+        _ => None,
+    }
+}
+
+impl RefVisitor for EntryPointInstructionsPass {
+    fn visit_instruction(&mut self, ins: &Instruction) -> VResult {
+        if let (Some((function, function_loc)), Some((name, loc))) =
+            (&self.entry_point, low_level_instruction(ins))
+        {
+            let loc = loc.unwrap_or(*function_loc);
+            return Err(CairoError::Preprocess(format!(
+                "Low level instruction {} is not allowed in entry point function {} {}",
+                name, function, loc
+            ))
+            .spanned(loc))
+        }
+        Ok(())
+    }
+
+    fn enter_function(&mut self, f: &FunctionDef) -> VResult {
+        if f.decorators.iter().any(Decorator::is_entry_point) {
+            self.entry_point = Some((f.name.clone(), f.loc));
+        }
+        Ok(())
+    }
+
+    fn exit_function(&mut self, _: &FunctionDef) -> VResult {
+        self.entry_point = None;
+        Ok(())
+    }
+}
+
+/// Returns the ABI entries of the `@external`, `@view` and `@constructor` functions of the main
+/// scope
+///
+/// Entry points of other modules are only included if they are imported into the main scope, under
+/// the name they are imported as. The types of the inputs and outputs are written as they appear in
+/// the source, untyped arguments are `felt`.
+pub fn build_abi(prg: &PreprocessedProgram) -> Vec<Abi> {
+    let mut collector = AbiCollector { prg, abi: Vec::new() };
+    for module in prg.modules.iter().filter(|module| *module.module_name == prg.main_scope) {
+        module.cairo_file.walk(&mut collector).expect("does not fail");
+    }
+    collector.abi
+}
+
+/// Collects the ABI entries of the entry point functions
+#[derive(Debug)]
+struct AbiCollector<'a> {
+    prg: &'a PreprocessedProgram,
+    abi: Vec<Abi>,
+}
+
+impl<'a> RefVisitor for AbiCollector<'a> {
+    fn visit_instruction(&mut self, ins: &Instruction) -> VResult {
+        let import = match ins {
+            Instruction::Import(import) => import,
+            _ => return Ok(()),
+        };
+        let module_name = ScopedName::new(import.path.clone());
+        let modules = self.prg.modules.iter().filter(|module| *module.module_name == module_name);
+        for module in modules {
+            for id in import.aliased_identifier() {
+                let functions = module.cairo_file.0.iter().filter_map(|ins| match ins {
+                    Instruction::Function(f) if f.name == id.id => Some(f),
+                    _ => None,
+                });
+                self.abi.extend(functions.filter_map(|f| abi_entry(f, id.identifier())));
+            }
+        }
+        Ok(())
+    }
+
+    fn enter_function(&mut self, f: &FunctionDef) -> VResult {
+        self.abi.extend(abi_entry(f, &f.name));
+        Ok(())
+    }
+}
+
+/// Returns the ABI entry of the function under the given name, if it is an entry point
+fn abi_entry(f: &FunctionDef, name: &str) -> Option<Abi> {
+    let (abi_type, state_mutability) =
+        match f.decorators.iter().find(|decorator| decorator.is_entry_point())? {
+            Decorator::View => (AbiType::Function, Some("view".to_string())),
+            Decorator::Constructor => (AbiType::Constructor, None),
+            _ => (AbiType::Function, None),
+        };
+    let items = |ids: &[TypedIdentifier]| {
+        ids.iter()
+            .map(|id| Item {
+                name: id.id.clone(),
+                put_type: id.ty.as_ref().map(ToString::to_string).unwrap_or_else(|| "felt".into()),
+            })
+            .collect()
+    };
+    Some(Abi {
+        inputs: items(&f.input_args),
+        name: name.to_string(),
+        outputs: f.return_values.as_deref().map(items).unwrap_or_default(),
+        abi_type,
+        state_mutability,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compiler::sema::{
+            passes::{PassManager, PassManagerBuilder},
+            CairoModule,
+        },
+        parser::ast::CairoFile,
+    };
+
+    fn run_starknet_passes(code: &str) -> Result<()> {
+        let module = CairoModule::new(ScopedName::main_scope(), CairoFile::parse(code).unwrap());
+        let mut prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        PassManager::starknet_pass_manager().run_on(&mut prg)
+    }
+
+    #[test]
+    fn can_validate_decorators() {
+        run_starknet_passes(
+            r#"
+%lang starknet
+
+@external
+func foo():
+    return ()
+end
+
+@storage_var
+func balance() -> (res : felt):
+end
+"#,
+        )
+        .unwrap();
+
+        let err = run_starknet_passes(
+            r#"
+%lang starknet
+
+@bogus
+func foo():
+    ret
+end
+"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Unsupported decorator @bogus"), "{}", err);
+
+        let err = run_starknet_passes(
+            r#"
+@external
+@view
+func foo():
+    ret
+end
+"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("multiple entry point decorators"), "{}", err);
+    }
+
+    #[test]
+    fn can_reject_low_level_instructions_in_entry_points() {
+        let code = "%lang starknet\n@external\nfunc foo():\n    ret\nend\n";
+        let err = run_starknet_passes(code).unwrap_err();
+        assert!(
+            err.to_string().starts_with(
+                "Low level instruction ret is not allowed in entry point function foo"
+            ),
+            "{}",
+            err
+        );
+        let span = err.span().unwrap();
+        assert_eq!(&code[span.0..span.1], "ret");
+
+        for body in ["jmp rel 2", "call rel 2", "call abs 2; ap++", "dw 1"] {
+            let code = format!(
+                "@view\nfunc foo():\n    if 1 == 1:\n        {}\n    end\n    return ()\nend\n",
+                body
+            );
+            let err = run_starknet_passes(&code).unwrap_err();
+            assert!(err.to_string().starts_with("Low level instruction"), "{}: {}", body, err);
+        }
+
+        // plain functions may use them
+        run_starknet_passes("%lang starknet\nfunc foo():\n    ret\nend\n@external\nfunc bar():\n    return ()\nend\n").unwrap();
+        let module = CairoModule::new(ScopedName::main_scope(), CairoFile::parse(code).unwrap());
+        let mut prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        // only the StarkNet passes reject them
+        PassManagerBuilder::default()
+            .supported_decorators(starknet_supported_decorators())
+            .build()
+            .run_on(&mut prg)
+            .unwrap();
+    }
+
+    #[test]
+    fn can_build_abi() {
+        let code = r#"
+%lang starknet
+
+@external
+func transfer(to, amount : felt) -> (ok : felt):
+    return (ok=1)
+end
+
+@view
+func get_balance(account) -> (balance):
+    return (balance=0)
+end
+
+@constructor
+func constructor(owner):
+    return ()
+end
+
+func helper():
+    return ()
+end
+"#;
+        let module = CairoModule::new(ScopedName::main_scope(), CairoFile::parse(code).unwrap());
+        let prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        let abi = build_abi(&prg);
+        assert_eq!(
+            serde_json::to_value(&abi).unwrap(),
+            serde_json::json!([
+                {
+                    "inputs": [
+                        { "name": "to", "type": "felt" },
+                        { "name": "amount", "type": "felt" }
+                    ],
+                    "name": "transfer",
+                    "outputs": [{ "name": "ok", "type": "felt" }],
+                    "type": "function"
+                },
+                {
+                    "inputs": [{ "name": "account", "type": "felt" }],
+                    "name": "get_balance",
+                    "outputs": [{ "name": "balance", "type": "felt" }],
+                    "type": "function",
+                    "stateMutability": "view"
+                },
+                {
+                    "inputs": [{ "name": "owner", "type": "felt" }],
+                    "name": "constructor",
+                    "outputs": [],
+                    "type": "constructor"
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn can_build_abi_of_imported_entry_points() {
+        let module = |name: &str, code: &str| {
+            CairoModule::new(ScopedName::from_str(name), CairoFile::parse(code).unwrap())
+        };
+        let prg = PreprocessedProgram::with_modules(
+            ScopedName::main_scope(),
+            [
+                module(
+                    "__main__",
+                    "%lang starknet\nfrom lib.token import transfer as send\n@view\nfunc name() -> (res):\n    return (res=0)\nend\n",
+                ),
+                module(
+                    "lib.token",
+                    "%lang starknet\n@external\nfunc transfer(to):\n    return ()\nend\n@view\nfunc balance() -> (res):\n    return (res=0)\nend\n",
+                ),
+                module("lib.other", "%lang starknet\n@external\nfunc mint():\n    return ()\nend\n"),
+            ],
+        );
+        let names: Vec<_> = build_abi(&prg).into_iter().map(|abi| abi.name).collect();
+        assert_eq!(names, ["send", "name"]);
+    }
+
+    #[test]
+    fn can_validate_lang() {
+        run_starknet_passes(
+            "%lang starknet
+",
+        )
+        .unwrap();
+        run_starknet_passes(
+            "func foo():
+    ret
+end
+",
+        )
+        .unwrap();
+
+        let code = "\n%lang other\n";
+        let err = run_starknet_passes(code).unwrap_err();
+        assert!(err.to_string().contains("Unsupported %lang directive other"), "{}", err);
+        assert!(err.to_string().ends_with(&format!("{}:{}", 1, code.len() - 1)), "{}", err);
+
+        let err = run_starknet_passes(
+            "%lang foo.bar.baz
+",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("foo.bar.baz"), "{}", err);
+
+        // generic cairo compilation doesn't care about the lang
+        let module = CairoModule::new(
+            ScopedName::main_scope(),
+            CairoFile::parse(
+                "%lang other
+",
+            )
+            .unwrap(),
+        );
+        let mut prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        PassManager::default().run_on(&mut prg).unwrap();
+    }
+}
@@ -55,8 +55,12 @@ impl<'a> StructVisitor<'a> {
         for member_info in members_list {
             let cairo_type = self.identifiers.resolve_type(member_info.ty)?;
 
-            if members.iter().any(|m| m.name == member_info.name) {
-                return Err(CairoError::Redefinition(struct_name.appended(member_info.name), loc))
+            if let Some(first) = members.iter().find(|m| m.name == member_info.name) {
+                return Err(CairoError::Redefinition {
+                    name: struct_name.appended(member_info.name),
+                    first: first.loc,
+                    second: member_info.loc,
+                })
             }
             let size = self.identifiers.get_size(&cairo_type)?;
             members.push(MemberDefinition {
@@ -65,7 +69,9 @@ impl<'a> StructVisitor<'a> {
                 cairo_type,
                 loc: member_info.loc,
             });
-            offset += size;
+            offset = offset
+                .checked_add(size)
+                .ok_or_else(|| CairoError::StructTooLarge(struct_name.clone()))?;
         }
 
         self.identifiers.add_name_definition(
@@ -101,7 +107,8 @@ impl<'a> Visitor for StructVisitor<'a> {
             return Err(CairoError::Preprocess(format!(
                 "Decorators for structs are not supported {} {}",
                 elem.name, elem.loc
-            )))
+            ))
+            .spanned(elem.loc))
         }
         let struct_name = self.current_identifier(elem.name.clone());
 
@@ -114,10 +121,9 @@ impl<'a> Visitor for StructVisitor<'a> {
         let arg_scope = function_scope.clone().appended(ARG_SCOPE);
         self.create_struct_from_identifier_list(&fun.input_args, arg_scope, fun.loc)?;
 
-        if let Some(ref implicit) = fun.implicit_args {
-            let implicit_arg_scope = function_scope.clone().appended(IMPLICIT_ARG_SCOPE);
-            self.create_struct_from_identifier_list(implicit, implicit_arg_scope, fun.loc)?;
-        }
+        let implicit_arg_scope = function_scope.clone().appended(IMPLICIT_ARG_SCOPE);
+        let implicit = fun.implicit_args.as_deref().unwrap_or_default();
+        self.create_struct_from_identifier_list(implicit, implicit_arg_scope, fun.loc)?;
 
         let return_scope = function_scope.appended(RETURN_SCOPE);
         if let Some(ref return_args) = fun.return_values {
@@ -172,6 +178,40 @@ mod tests {
         try_collect_struct_def(codes).unwrap()
     }
 
+    #[test]
+    fn can_diff_identifiers_of_struct_collect() {
+        let module = CairoModule::new(
+            ScopedName::main_scope(),
+            CairoFile::parse("const C = 1\nstruct S:\n    member x : felt\nend\n").unwrap(),
+        );
+        let mut prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        IdentifierCollectorPass::default().run(&mut prg).unwrap();
+
+        let before = prg.clone();
+        StructCollectorPass::default().run(&mut prg).unwrap();
+        assert_eq!(before.identifiers.diff(&prg.identifiers), [ScopedName::from("__main__.S")]);
+        assert!(prg.identifiers.diff(&prg.identifiers).is_empty());
+        assert!(before.identifiers.get_by_full_name(&"__main__.S".into()).unwrap().is_unresolved());
+    }
+
+    #[test]
+    fn can_get_function_signature() {
+        let ids = collect_struct_def([(
+            "__main__",
+            "func foo{x}(a) -> (b : felt*):\n    ret\nend\nconst C = 1\n",
+        )]);
+        let signature = ids.get_function(&ScopedName::from("__main__.foo")).unwrap();
+        assert_eq!(signature.full_name, ScopedName::from("__main__.foo"));
+        assert_eq!(signature.args.members.len(), 1);
+        assert_eq!(signature.args.members[0].name, "a");
+        assert_eq!(signature.implicit_args.unwrap().members[0].name, "x");
+        assert_eq!(signature.return_values.members.len(), 1);
+        assert_eq!(signature.return_values.members[0].name, "b");
+        assert_eq!(signature.sizeof_locals, None);
+
+        assert!(ids.get_function(&ScopedName::from("__main__.C")).is_err());
+    }
+
     #[test]
     fn test_struct_collect() {
         let ids = collect_struct_def([
@@ -344,10 +384,104 @@ end
 "#,
         )]);
         match ids {
-            Err(CairoError::Redefinition(name, _)) => {
+            Err(CairoError::Redefinition { name, .. }) => {
                 assert_eq!(name, "module.S.z".into())
             }
             _ => panic!(),
         }
     }
+
+    #[test]
+    fn can_detect_struct_size_overflow() {
+        // `S{i}` has a size of 2^i
+        let structs = |n: usize| {
+            let mut code = "struct S0:\n    member x : felt\nend\n".to_string();
+            for i in 1..=n {
+                code.push_str(&format!(
+                    "struct S{}:\n    member a : S{}\n    member b : S{}\nend\n",
+                    i,
+                    i - 1,
+                    i - 1
+                ));
+            }
+            code
+        };
+
+        match try_collect_struct_def([("module", structs(64).as_str())]) {
+            Err(CairoError::StructTooLarge(name)) => assert_eq!(name, "module.S64".into()),
+            res => panic!("unexpected result {:?}", res.map(|_| ())),
+        }
+
+        let code = format!("{}struct T:\n    member t : (S63, S63)\nend\n", structs(63));
+        match try_collect_struct_def([("module", code.as_str())]) {
+            Err(CairoError::TypeTooLarge(ty)) => {
+                assert_eq!(ty.to_string(), "(module.S63, module.S63)")
+            }
+            res => panic!("unexpected result {:?}", res.map(|_| ())),
+        }
+
+        let ids = collect_struct_def([("module", structs(63).as_str())]);
+        let resolved = ids.get(&"module.S63".into()).unwrap();
+        assert_eq!(resolved.ty.as_struct().unwrap().size, 1 << 63);
+    }
+
+    #[test]
+    fn can_convert_struct_to_program_identifier() {
+        let ids = collect_struct_def([(
+            "__main__",
+            r#"
+struct Point:
+    member x : felt
+    member y : felt*
+end
+"#,
+        )]);
+        let identifiers = ids.to_program_identifiers(&HashMap::new());
+        assert_eq!(
+            identifiers["__main__.Point"],
+            serde_json::json!({
+                "type": "struct",
+                "full_name": "__main__.Point",
+                "members": {
+                    "x": { "cairo_type": "felt", "offset": 0 },
+                    "y": { "cairo_type": "felt*", "offset": 1 }
+                },
+                "size": 2
+            })
+        );
+    }
+
+    #[test]
+    fn can_convert_functions_to_program_identifiers() {
+        let ids = collect_struct_def([(
+            "__main__",
+            r#"
+const C = 7
+
+@view
+func main(a : felt):
+    start:
+    ret
+end
+"#,
+        )]);
+        let labels =
+            HashMap::from([("__main__.main".into(), 3), ("__main__.main.start".into(), 5)]);
+        let identifiers = ids.to_program_identifiers(&labels);
+        assert_eq!(
+            identifiers["__main__.main"],
+            serde_json::json!({ "decorators": ["view"], "pc": 3, "type": "function" })
+        );
+        assert_eq!(
+            identifiers["__main__.main.start"],
+            serde_json::json!({ "pc": 5, "type": "label" })
+        );
+        // the type and the flow of references are not tracked
+        assert_eq!(
+            identifiers["__main__.main.a"],
+            serde_json::json!({ "full_name": "__main__.main.a", "type": "reference" })
+        );
+        // constants are only included once they are evaluated
+        assert!(!identifiers.contains_key("__main__.C"));
+    }
 }
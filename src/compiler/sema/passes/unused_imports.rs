@@ -0,0 +1,132 @@
+use crate::{
+    compiler::{
+        sema::{PreprocessedProgram, ScopedName},
+        RefVisitor, VResult, Walk,
+    },
+    parser::ast::*,
+};
+use std::collections::HashSet;
+
+/// Returns all identifiers that are imported but never referenced in the importing module
+///
+/// This is a syntactic check: an import counts as used if any identifier in the module starts
+/// with the imported name.
+pub fn unused_imports(prg: &PreprocessedProgram) -> Vec<(ScopedName, Loc)> {
+    let mut unused = Vec::new();
+    for module in prg.modules.iter() {
+        let mut visitor = ImportUsageVisitor::default();
+        module.cairo_file.walk(&mut visitor).expect("does not fail");
+        for (name, loc) in visitor.imports {
+            if !visitor.used.contains(&name) {
+                unused.push((module.module_name.as_ref().clone().appended(name), loc));
+            }
+        }
+    }
+    unused
+}
+
+/// Collects imported names and the first segment of every referenced identifier
+#[derive(Default)]
+struct ImportUsageVisitor {
+    imports: Vec<(String, Loc)>,
+    used: HashSet<String>,
+}
+
+impl ImportUsageVisitor {
+    fn use_identifier(&mut self, id: &[String]) {
+        if let Some(name) = id.first() {
+            self.used.insert(name.clone());
+        }
+    }
+}
+
+impl RefVisitor for ImportUsageVisitor {
+    fn visit_instruction(&mut self, ins: &Instruction) -> VResult {
+        match ins {
+            Instruction::Let(_, rvalue, _) => {
+                if let RValue::Call(Call::Id(id)) = rvalue.as_ref() {
+                    self.use_identifier(id);
+                }
+            }
+            Instruction::CallInstruction(Call::Id(id), _) => self.use_identifier(id),
+            Instruction::Jmp(Jmp::Id(id) | Jmp::IdIf(id, _, _), _) => self.use_identifier(id),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn visit_import(&mut self, import: &ImportDirective) -> VResult {
+        for item in import.aliased_identifier() {
+            self.imports.push((item.identifier().to_string(), item.loc));
+        }
+        Ok(())
+    }
+
+    fn visit_function_call(&mut self, call: &FunctionCall) -> VResult {
+        self.use_identifier(&call.id);
+        Ok(())
+    }
+
+    fn visit_type(&mut self, ty: &CairoType) -> VResult {
+        match ty {
+            CairoType::Felt => {}
+            CairoType::Id(ty) => self.use_identifier(&ty.name),
+            CairoType::Tuple(tys) => tys.walk(self)?,
+            CairoType::Pointer(ptr) => match ptr.as_ref() {
+                PointerType::Single(ty) | PointerType::Double(ty) => self.visit_type(ty)?,
+            },
+        }
+        Ok(())
+    }
+
+    fn visit_expr_identifier(&mut self, id: &Identifier, _: Loc) -> VResult {
+        self.use_identifier(id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::sema::CairoModule;
+
+    #[test]
+    fn can_find_unused_imports() {
+        let code = r#"
+from starkware.cairo.common.math import assert_nn, assert_le
+from starkware.cairo.common.uint256 import Uint256
+
+func foo(x : Uint256*):
+    assert_nn(x)
+    ret
+end
+"#;
+        let module = CairoModule::new(ScopedName::main_scope(), CairoFile::parse(code).unwrap());
+        let prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        let unused = unused_imports(&prg);
+        assert_eq!(unused.len(), 1);
+        assert_eq!(unused[0].0, ScopedName::from("__main__.assert_le"));
+        assert_eq!(&code[unused[0].1 .0..unused[0].1 .1], "assert_le");
+    }
+
+    #[test]
+    fn can_find_imports_used_in_nested_nodes() {
+        let code = r#"
+from a import A, B, C, D, E, F
+
+struct S:
+    member s : (felt, B*)
+end
+
+func foo(x) -> (y):
+    let c = cast(x, C)
+    tempvar d = D + 1
+    let (res) = call E
+    return (y=A(F(x)))
+end
+"#;
+        let module = CairoModule::new(ScopedName::main_scope(), CairoFile::parse(code).unwrap());
+        let prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        assert!(unused_imports(&prg).is_empty(), "{:?}", unused_imports(&prg));
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{
+    compiler::{
+        sema::{passes::Pass, PreprocessedProgram, ScopedName},
+        VResult, Visitable, Visitor,
+    },
+    error::Result,
+    parser::ast::*,
+};
+use std::collections::HashSet;
+
+/// Reports `local`, `tempvar` and `let` bindings that are never referenced in their function
+///
+/// The unused variables are stored in `AuxiliaryInfo::unused_variables`, this pass never fails.
+/// Like `unused_imports`, this is a syntactic check: a variable counts as used if any identifier in
+/// the function, or an `ids.` access in one of its hints, starts with its name. Names starting with
+/// `_` are never reported.
+#[derive(Debug, Default)]
+pub struct UnusedVariablesPass;
+
+impl Pass for UnusedVariablesPass {
+    fn run(&mut self, prg: &mut PreprocessedProgram) -> Result<()> {
+        log::trace!("starting pass: UnusedVariables");
+        let mut unused = Vec::new();
+        for module in prg.modules.iter_mut() {
+            let mut visitor = VariableUsageVisitor {
+                scopes: vec![module.module_name.as_ref().clone()],
+                frames: Vec::new(),
+                unused: &mut unused,
+            };
+            module.cairo_file.visit(&mut visitor)?;
+        }
+        prg.auxiliary_info.unused_variables = unused;
+        Ok(())
+    }
+}
+
+/// The declared variables and the referenced names of a function
+#[derive(Default)]
+struct Frame {
+    declared: Vec<(String, Loc)>,
+    used: HashSet<String>,
+}
+
+/// Tracks the variable usage of the functions that are currently visited
+struct VariableUsageVisitor<'a> {
+    scopes: Vec<ScopedName>,
+    /// one frame per function, the innermost function is last
+    frames: Vec<Frame>,
+    unused: &'a mut Vec<(ScopedName, Loc)>,
+}
+
+impl<'a> VariableUsageVisitor<'a> {
+    fn declare(&mut self, id: &TypedIdentifier) {
+        if id.id.starts_with('_') {
+            return
+        }
+        if let Some(frame) = self.frames.last_mut() {
+            frame.declared.push((id.id.clone(), id.loc));
+        }
+    }
+
+    fn use_name(&mut self, name: String) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.used.insert(name);
+        }
+    }
+
+    fn use_identifier(&mut self, id: &[String]) {
+        if let Some(name) = id.first() {
+            self.use_name(name.clone());
+        }
+    }
+
+    fn current_scope(&self) -> ScopedName {
+        self.scopes.last().cloned().unwrap_or_else(ScopedName::root)
+    }
+}
+
+impl<'a> Visitor for VariableUsageVisitor<'a> {
+    fn enter_namespace(&mut self, ns: &mut Namespace) -> VResult {
+        self.scopes.push(self.current_scope().appended(ns.name.clone()));
+        Ok(())
+    }
+
+    fn exit_namespace(&mut self, _: &mut Namespace) -> VResult {
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn enter_function(&mut self, f: &mut FunctionDef) -> VResult {
+        self.scopes.push(self.current_scope().appended(f.name.clone()));
+        self.frames.push(Frame::default());
+        Ok(())
+    }
+
+    fn exit_function(&mut self, _: &mut FunctionDef) -> VResult {
+        let scope = self.scopes.pop().unwrap_or_else(ScopedName::root);
+        let frame = self.frames.pop().unwrap_or_default();
+        for (name, loc) in frame.declared {
+            if !frame.used.contains(&name) {
+                self.unused.push((scope.clone().appended(name), loc));
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_local_var(&mut self, id: &mut TypedIdentifier, _: &mut Option<Expr>) -> VResult {
+        self.declare(id);
+        Ok(())
+    }
+
+    fn visit_temp_var(&mut self, id: &mut TypedIdentifier, _: &mut Option<Expr>) -> VResult {
+        self.declare(id);
+        Ok(())
+    }
+
+    fn visit_reference(&mut self, binding: &mut RefBinding, rvalue: &mut RValue) -> VResult {
+        match binding {
+            RefBinding::Id(id) => self.declare(id),
+            RefBinding::List(ids) => ids.iter().for_each(|id| self.declare(id)),
+        }
+        if let RValue::Call(Call::Id(id)) = rvalue {
+            self.use_identifier(id);
+        }
+        Ok(())
+    }
+
+    fn visit_with(&mut self, with: &mut WithStatement) -> VResult {
+        for id in with.ids.iter() {
+            self.use_name(id.identifier().to_string());
+        }
+        Ok(())
+    }
+
+    fn visit_hint(&mut self, hint: &mut String, _: Loc) -> VResult {
+        for (pos, _) in hint.match_indices("ids.") {
+            let name: String = hint[pos + 4..]
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+                .collect();
+            self.use_name(name);
+        }
+        Ok(())
+    }
+
+    fn visit_expr_identifier(&mut self, id: &mut Identifier, _: Loc) -> VResult {
+        self.use_identifier(id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::sema::CairoModule;
+
+    #[test]
+    fn can_find_unused_variables() {
+        let code = r#"
+func foo(x):
+    alloc_locals
+    tempvar used = x + 1
+    tempvar unused = 2
+    tempvar _ignored = 3
+    local in_hint = 4
+    %{ print(ids.in_hint) %}
+    [ap] = used; ap++
+    ret
+end
+"#;
+        let module = CairoModule::new(ScopedName::main_scope(), CairoFile::parse(code).unwrap());
+        let mut prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        UnusedVariablesPass.run(&mut prg).unwrap();
+        let unused = &prg.auxiliary_info.unused_variables;
+        assert_eq!(unused.len(), 1);
+        assert_eq!(unused[0].0, ScopedName::from("__main__.foo.unused"));
+        assert_eq!(&code[unused[0].1 .0..unused[0].1 .1], "unused");
+    }
+
+    #[test]
+    fn can_find_unused_variables_of_outer_functions() {
+        let code = r#"
+func outer():
+    alloc_locals
+    tempvar outer_unused = 1
+    tempvar shared = 2
+    func inner():
+        tempvar inner_unused = 3
+        tempvar x = 4
+        [ap] = x; ap++
+        ret
+    end
+    [ap] = shared; ap++
+    ret
+end
+"#;
+        let module = CairoModule::new(ScopedName::main_scope(), CairoFile::parse(code).unwrap());
+        let mut prg = PreprocessedProgram::with_modules(ScopedName::main_scope(), [module]);
+        UnusedVariablesPass.run(&mut prg).unwrap();
+        let mut unused: Vec<_> =
+            prg.auxiliary_info.unused_variables.iter().map(|(name, _)| name.to_string()).collect();
+        unused.sort();
+        assert_eq!(unused, ["__main__.outer.inner.inner_unused", "__main__.outer.outer_unused"]);
+    }
+}
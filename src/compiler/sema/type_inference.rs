@@ -0,0 +1,251 @@
+//! A lightweight type inference for expressions
+use crate::{
+    compiler::sema::{
+        identifiers::{IdentifierDefinitionType, Identifiers},
+        ScopedName,
+    },
+    error::{CairoError, Result},
+    parser::ast::{CairoType, Expr, ExprAssignment, Loc, PointerType},
+};
+
+/// Infers the type of the expression
+///
+/// Identifiers are resolved in the current accessible scopes, references are not supported yet.
+pub fn infer_type(expr: &Expr, identifiers: &Identifiers) -> Result<CairoType> {
+    try_infer_type(expr, identifiers)?.ok_or_else(|| {
+        let loc = expr.loc();
+        CairoError::Preprocess(format!("Cannot infer the type of {} {}", expr, loc)).spanned(loc)
+    })
+}
+
+/// Same as `infer_type`, but returns `None` if the type depends on something that is not tracked,
+/// like references or the return values of function calls
+///
+/// An error is only returned if the expression is not well typed.
+pub fn try_infer_type(expr: &Expr, identifiers: &Identifiers) -> Result<Option<CairoType>> {
+    macro_rules! infer {
+        ($expr:expr) => {
+            match try_infer_type($expr, identifiers)? {
+                Some(ty) => ty,
+                None => return Ok(None),
+            }
+        };
+    }
+
+    let ty = match expr {
+        Expr::Int(_, _) | Expr::HexInt(_, _) | Expr::ShortString(_, _) | Expr::Hint(_, _) => {
+            CairoType::Felt
+        }
+        // `ap` and `fp` point to felts in memory
+        Expr::Register(_, _) => pointer_to(CairoType::Felt),
+        Expr::Id(id, _) => {
+            let resolved = match identifiers.search_current_scopes(&ScopedName::new(id.clone())) {
+                Ok(resolved) => resolved,
+                // unknown identifiers are reported when they are resolved
+                Err(_) => return Ok(None),
+            };
+            let ty = resolved.ty.as_unresolved().unwrap_or(&resolved.ty);
+            match (ty, resolved.rem) {
+                (
+                    IdentifierDefinitionType::ConstDef(_) |
+                    IdentifierDefinitionType::Label |
+                    IdentifierDefinitionType::Function,
+                    None,
+                ) => CairoType::Felt,
+                (IdentifierDefinitionType::Struct(_), Some(rem)) if rem == ScopedName::size() => {
+                    CairoType::Felt
+                }
+                _ => return Ok(None),
+            }
+        }
+        Expr::Deref(expr, loc) => deref(infer!(expr), *loc)?,
+        Expr::Subscript(expr, offset, loc) => {
+            let ty = infer!(expr);
+            expect_felt(infer!(offset), *loc)?;
+            deref(ty, *loc)?
+        }
+        Expr::Address(expr, _) => pointer_to(infer!(expr)),
+        // casts only change the type, not the representation of the value
+        Expr::Cast(_, ty, loc) => {
+            identifiers.resolve_type(ty.clone()).map_err(|err| err.spanned(*loc))?
+        }
+        Expr::Dot(expr, member, loc) => {
+            let ty = match infer!(expr) {
+                CairoType::Pointer(ptr) if ptr.is_single() => ptr.into_pointee(),
+                ty => ty,
+            };
+            // the struct is resolved in the accessible scopes unless it's resolved already
+            let def = match identifiers.resolve_type(ty).map_err(|err| err.spanned(*loc))? {
+                CairoType::Id(ty) => {
+                    let name = ScopedName::new(ty.name);
+                    identifiers.get_by_full_name(&name).and_then(|ty| ty.as_struct()).ok_or_else(
+                        || {
+                            CairoError::Preprocess(format!(
+                                "Expected {} to be a struct definition {}",
+                                name, loc
+                            ))
+                            .spanned(*loc)
+                        },
+                    )?
+                }
+                ty => {
+                    return Err(CairoError::Preprocess(format!(
+                        "Cannot access member {} of non struct type {} {}",
+                        member, ty, loc
+                    ))
+                    .spanned(*loc))
+                }
+            };
+            def.members
+                .iter()
+                .find(|m| &m.name == member)
+                .map(|m| m.cairo_type.clone())
+                .ok_or_else(|| {
+                    CairoError::Preprocess(format!(
+                        "Member {} does not exist in {} {}",
+                        member, def.full_name, loc
+                    ))
+                    .spanned(*loc)
+                })?
+        }
+        Expr::Parentheses(exprs, _) => match exprs.as_slice() {
+            [ExprAssignment::Expr(expr, _)] => infer!(expr),
+            exprs => {
+                let mut types = Vec::with_capacity(exprs.len());
+                for expr in exprs {
+                    match expr {
+                        ExprAssignment::Expr(expr, _) | ExprAssignment::Id(_, expr, _) => {
+                            types.push(infer!(expr))
+                        }
+                    }
+                }
+                CairoType::Tuple(types)
+            }
+        },
+        Expr::Neg(expr, loc) => expect_felt(infer!(expr), *loc)?,
+        Expr::Add(lhs, rhs, loc) => match (infer!(lhs), infer!(rhs)) {
+            (CairoType::Felt, CairoType::Felt) => CairoType::Felt,
+            (ptr @ CairoType::Pointer(_), CairoType::Felt) |
+            (CairoType::Felt, ptr @ CairoType::Pointer(_)) => ptr,
+            (lhs, rhs) => {
+                return Err(CairoError::Preprocess(format!(
+                    "Operator '+' is not implemented for types {} and {} {}",
+                    lhs, rhs, loc
+                ))
+                .spanned(*loc))
+            }
+        },
+        Expr::Sub(lhs, rhs, loc) => match (infer!(lhs), infer!(rhs)) {
+            (CairoType::Felt, CairoType::Felt) => CairoType::Felt,
+            (ptr @ CairoType::Pointer(_), CairoType::Felt) => ptr,
+            // the distance between two pointers of the same type
+            (lhs @ CairoType::Pointer(_), rhs @ CairoType::Pointer(_)) if lhs == rhs => {
+                CairoType::Felt
+            }
+            (lhs, rhs) => {
+                return Err(CairoError::Preprocess(format!(
+                    "Operator '-' is not implemented for types {} and {} {}",
+                    lhs, rhs, loc
+                ))
+                .spanned(*loc))
+            }
+        },
+        Expr::Mul(lhs, rhs, loc) | Expr::Div(lhs, rhs, loc) | Expr::Pow(lhs, rhs, loc) => {
+            let lhs = infer!(lhs);
+            expect_felt(infer!(rhs), *loc)?;
+            expect_felt(lhs, *loc)?
+        }
+        Expr::FunctionCall(_) => return Ok(None),
+    };
+    Ok(Some(ty))
+}
+
+fn pointer_to(ty: CairoType) -> CairoType {
+    CairoType::Pointer(Box::new(PointerType::Single(ty)))
+}
+
+/// Returns the type a value of type `ty` points to
+fn deref(ty: CairoType, loc: Loc) -> Result<CairoType> {
+    match ty {
+        CairoType::Pointer(ptr) => Ok(ptr.into_pointee()),
+        ty => Err(CairoError::Preprocess(format!(
+            "Cannot dereference type {}, expected a pointer {}",
+            ty, loc
+        ))
+        .spanned(loc)),
+    }
+}
+
+fn expect_felt(ty: CairoType, loc: Loc) -> Result<CairoType> {
+    if !ty.is_felt() {
+        return Err(CairoError::Preprocess(format!("Expected a felt but found {} {}", ty, loc))
+            .spanned(loc))
+    }
+    Ok(ty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn infer(expr: &str) -> Result<CairoType> {
+        let mut identifiers = Identifiers::default();
+        identifiers.scope_tracker.enter_scope(ScopedName::root().into());
+        infer_type(&Expr::parse(expr).unwrap(), &identifiers)
+    }
+
+    #[test]
+    fn can_deref_pointers() {
+        assert_eq!(infer("[ap]").unwrap(), CairoType::Felt);
+        assert_eq!(infer("[fp + 3]").unwrap(), CairoType::Felt);
+        assert_eq!(infer("[cast(5, felt**)]").unwrap(), pointer_to(CairoType::Felt));
+        assert_eq!(infer("&[ap]").unwrap(), pointer_to(CairoType::Felt));
+        assert_eq!(infer("[[cast(ap, felt**)]]").unwrap(), CairoType::Felt);
+
+        let err = infer("[5]").unwrap_err();
+        assert!(err.to_string().starts_with("Cannot dereference type felt"), "{}", err);
+        let err = infer("[[ap]]").unwrap_err();
+        assert!(err.to_string().starts_with("Cannot dereference type felt"), "{}", err);
+    }
+
+    #[test]
+    fn can_check_pointer_arithmetic() {
+        assert_eq!(infer("1 + 2 * 3").unwrap(), CairoType::Felt);
+        assert_eq!(infer("1 + ap").unwrap(), pointer_to(CairoType::Felt));
+        assert_eq!(infer("ap - fp").unwrap(), CairoType::Felt);
+        assert_eq!(
+            infer("(ap, 1)").unwrap(),
+            CairoType::Tuple(vec![pointer_to(CairoType::Felt), CairoType::Felt])
+        );
+
+        assert!(infer("ap + fp").is_err());
+        assert!(infer("1 - ap").is_err());
+        assert!(infer("ap * 2").is_err());
+        assert!(infer("ap - cast(fp, felt**)").is_err());
+    }
+
+    #[test]
+    fn can_infer_casts() {
+        let mut identifiers = Identifiers::default();
+        identifiers.scope_tracker.enter_scope(ScopedName::from_str("a").into());
+        identifiers.add_identifier(
+            ScopedName::from_str("a.MyStruct"),
+            IdentifierDefinitionType::Struct(None),
+        );
+        let infer = |expr: &str| infer_type(&Expr::parse(expr).unwrap(), &identifiers);
+
+        let ty = infer("cast([ap], MyStruct*)").unwrap();
+        assert_eq!(ty.to_string(), "a.MyStruct*");
+        match ty {
+            CairoType::Pointer(ptr) => match ptr.into_pointee() {
+                CairoType::Id(ty) => assert!(ty.is_fully_resolved),
+                ty => panic!("unexpected type {}", ty),
+            },
+            ty => panic!("unexpected type {}", ty),
+        }
+        assert_eq!(infer("[cast(ap, felt**)]").unwrap(), pointer_to(CairoType::Felt));
+
+        let err = infer("cast([ap], Missing*)").unwrap_err();
+        assert!(err.span().is_some());
+    }
+}
@@ -3,7 +3,7 @@ use std::io;
 
 use crate::{
     compiler::sema::{identifiers::IdentifierDefinitionType, ScopedName},
-    parser::ast::Loc,
+    parser::ast::{CairoType, Loc},
 };
 use thiserror::Error;
 
@@ -16,7 +16,10 @@ pub enum CairoError {
     Lexer(#[from] CairoLexerError),
     #[error(transparent)]
     Io(#[from] io::Error),
-    #[error("Circular imports: {0:?}")]
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    /// The modules of an import cycle, starting and ending with the same module
+    #[error("Circular imports: {}", .0.join(" -> "))]
     CircularDependencies(Vec<String>),
     #[error("Could not find module: {0}")]
     ModuleNotFound(String),
@@ -32,18 +35,86 @@ pub enum CairoError {
     Identifier(String),
     #[error("{0}")]
     Preprocess(String),
-    #[error("Redefinition of `{0}` at `{1}`")]
-    Redefinition(ScopedName, Loc),
-    #[error("Expected `{0}` to be a {1:?} Found `{2:?}`")]
+    #[error("{0}")]
+    Assemble(String),
+    #[error("Redefinition of `{name}` at `{second}`, first defined at `{first}`")]
+    Redefinition { name: ScopedName, first: Loc, second: Loc },
+    #[error("Expected `{0}` to be a {1}, found {2}")]
     Definition(ScopedName, IdentifierDefinitionType, IdentifierDefinitionType),
-    #[error("`{0}` is not a scope")]
+    #[error("`{0}` is a {2}, not a scope{}", fmt_remainder(.1))]
     NotScope(ScopedName, Option<ScopedName>, IdentifierDefinitionType),
+    #[error("The size of `{0}` exceeds the maximum size")]
+    StructTooLarge(ScopedName),
+    #[error("The size of `{0}` exceeds the maximum size")]
+    TypeTooLarge(CairoType),
+    #[error("Exceeded the maximum alias resolution depth while resolving `{0}`")]
+    AliasDepthExceeded(ScopedName),
     #[error("Expected labeled element:`{0:?}`")]
     MissingLabel(Loc),
+    /// An error that can be attributed to a location in the source
+    ///
+    /// This only attaches the location, so it's displayed as the wrapped error and has no source.
+    /// Use `unspanned` to get the wrapped error.
+    #[error("{error}")]
+    Spanned { error: Box<CairoError>, loc: Loc },
 }
 
 impl CairoError {
     pub fn msg(msg: impl Into<String>) -> Self {
         CairoError::Message(msg.into())
     }
+
+    /// Attaches the location the error occurred at, unless it already has one
+    pub fn spanned(self, loc: Loc) -> Self {
+        if self.span().is_some() {
+            return self
+        }
+        CairoError::Spanned { error: Box::new(self), loc }
+    }
+
+    /// Returns the location in the source this error refers to, if known
+    pub fn span(&self) -> Option<Loc> {
+        match self {
+            CairoError::MissingLabel(loc) => Some(*loc),
+            CairoError::Redefinition { second, .. } => Some(*second),
+            CairoError::Spanned { loc, .. } => Some(*loc),
+            _ => None,
+        }
+    }
+
+    /// Returns the error without its location
+    pub fn unspanned(&self) -> &CairoError {
+        match self {
+            CairoError::Spanned { error, .. } => error.unspanned(),
+            err => err,
+        }
+    }
+}
+
+/// Formats the part of a name that was left to resolve when the resolution failed
+fn fmt_remainder(rem: &Option<ScopedName>) -> String {
+    rem.as_ref().map(|rem| format!(" (while resolving `.{}`)", rem)).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn can_report_spans() {
+        assert!(CairoError::msg("no location").span().is_none());
+        assert!(CairoError::Preprocess("no location".to_string()).span().is_none());
+
+        let err = CairoError::Preprocess("Division by zero 3:4".to_string()).spanned(Loc(3, 4));
+        assert_eq!(err.to_string(), "Division by zero 3:4");
+        assert_eq!(err.span().map(|loc| (loc.0, loc.1)), Some((3, 4)));
+        assert!(matches!(err.unspanned(), CairoError::Preprocess(_)));
+        // chain reporters don't print the message twice
+        assert!(err.source().is_none());
+
+        // an existing location is not replaced
+        let err = err.spanned(Loc(5, 6));
+        assert_eq!(err.span().unwrap().0, 3);
+    }
 }
@@ -1,4 +1,10 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
 pub mod compiler;
 pub mod parser;
-pub use parser::ast::CairoFile;
+pub use parser::{ast, ast::CairoFile};
+#[cfg(feature = "std")]
 pub mod error;
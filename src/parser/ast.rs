@@ -1,19 +1,26 @@
 //! AST for Cairo-lang based on https://cairo-lang.org/docs/reference/syntax.html
-use crate::{
-    compiler::{sema::ScopedName, VResult, Visitable, Visitor},
-    error::CairoError,
-    parser::{
-        self,
-        lexer::{CairoLexer, CairoLexerError},
-    },
+use crate::parser::{
+    self,
+    format::{self, FormatOptions, DISPLAY_INDENT},
+    lexer::{CairoLexer, CairoLexerError},
 };
-use std::{
-    fmt::{self, Write},
-    path::Path,
+#[cfg(feature = "std")]
+use crate::{compiler::sema::ScopedName, error::CairoError};
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
 };
+use core::fmt::{self, Write};
+use primitive_types::U256;
+#[cfg(feature = "std")]
+use std::path::Path;
 
 ///  start offset, end offset (in bytes)
 #[derive(Debug, Eq, Hash, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(not(test), derive(PartialEq))]
 #[cfg_attr(test, allow(clippy::derive_hash_xor_eq))]
 pub struct Loc(pub usize, pub usize);
@@ -32,6 +39,39 @@ impl fmt::Display for Loc {
     }
 }
 
+/// Maps byte offsets of a source string to 1-based line and column numbers
+///
+/// `Loc`s are byte based, this can be used to turn them into something a user can make sense of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    /// byte offsets at which each line starts
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(input: &str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(input.match_indices('\n').map(|(i, _)| i + 1));
+        Self { line_starts }
+    }
+
+    /// Returns the 1-based `(line, column)` of the byte offset
+    ///
+    /// The column is counted in bytes from the start of the line.
+    pub fn locate(&self, offset: usize) -> (usize, usize) {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        (line + 1, offset - self.line_starts[line] + 1)
+    }
+
+    /// Returns the 1-based `(line, column)` of the start of the `Loc`
+    pub fn locate_loc(&self, loc: Loc) -> (usize, usize) {
+        self.locate(loc.0)
+    }
+}
+
 // [a-zA-Z_][\w_]+?
 pub type IDStr = String;
 
@@ -40,22 +80,68 @@ pub type Identifier = Vec<String>;
 
 /// Represents a set of cairo instructions, like all instructions in a file
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CairoFile(pub Vec<Instruction>);
 
 impl CairoFile {
     /// Parses the contents of a Cairo file.
     pub fn parse(input: &str) -> Result<Self, CairoLexerError> {
-        let input = input.trim_start();
-        let instructions =
-            parser::cairo_grammar::CodeBlockParser::new().parse(input, CairoLexer::new(input))?;
-        Ok(CairoFile(instructions))
+        let instructions = parser::cairo_grammar::CodeBlockParser::new()
+            .parse(input, CairoLexer::new(input))
+            .map_err(|err| CairoLexerError::from(err).located(&LineIndex::new(input)))?;
+        let mut file = CairoFile(instructions);
+        DocCommentCollector { input }.collect(&mut file.0);
+        Ok(file)
     }
 
     /// Read the contents of a cairo file and parse all instructions
+    #[cfg(feature = "std")]
     pub fn read(path: impl AsRef<Path>) -> Result<Self, CairoError> {
         let content = std::fs::read_to_string(path.as_ref())?;
         Ok(Self::parse(&content)?)
     }
+
+    /// Returns all top level functions
+    pub fn functions(&self) -> impl Iterator<Item = &FunctionDef> {
+        self.into_iter().filter_map(|ins| match ins {
+            Instruction::Function(f) => Some(f),
+            _ => None,
+        })
+    }
+
+    /// Returns all top level import directives
+    pub fn imports(&self) -> impl Iterator<Item = &ImportDirective> {
+        self.into_iter().filter_map(|ins| match ins {
+            Instruction::Import(import) => Some(import),
+            _ => None,
+        })
+    }
+
+    /// Returns all top level struct definitions
+    pub fn structs(&self) -> impl Iterator<Item = &StructDef> {
+        self.into_iter().filter_map(|ins| match ins {
+            Instruction::Struct(s) => Some(s),
+            _ => None,
+        })
+    }
+}
+
+impl<'a> IntoIterator for &'a CairoFile {
+    type Item = &'a Instruction;
+    type IntoIter = core::slice::Iter<'a, Instruction>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl IntoIterator for CairoFile {
+    type Item = Instruction;
+    type IntoIter = alloc::vec::IntoIter<Instruction>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
 }
 
 impl AsRef<Vec<Instruction>> for CairoFile {
@@ -64,20 +150,26 @@ impl AsRef<Vec<Instruction>> for CairoFile {
     }
 }
 
-impl Visitable for CairoFile {
-    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
-        self.0.visit(v)
+impl fmt::Display for CairoFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(&FormatOptions::default(), 0, f)
     }
 }
 
-impl fmt::Display for CairoFile {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt_trailing_newline(&self.0, f)
+impl FormatWith for CairoFile {
+    fn fmt_with(
+        &self,
+        opts: &FormatOptions,
+        level: usize,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        fmt_trailing_newline(self.0.iter().map(|ins| Formatted(ins, opts, level)), f)
     }
 }
 
 /// An identifier with a potential alias `x as y`
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AliasedId {
     pub id: String,
     pub alias: Option<String>,
@@ -85,6 +177,7 @@ pub struct AliasedId {
 }
 
 impl AliasedId {
+    /// The name under which the item is imported, the alias if present
     pub fn identifier(&self) -> &str {
         if let Some(ref alias) = self.alias {
             alias
@@ -92,6 +185,11 @@ impl AliasedId {
             &self.id
         }
     }
+
+    /// The name of the item in the module it is imported from, ignoring any alias
+    pub fn source(&self) -> &str {
+        &self.id
+    }
 }
 
 impl fmt::Display for AliasedId {
@@ -105,6 +203,7 @@ impl fmt::Display for AliasedId {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ImportDirective {
     pub loc: Loc,
     /// the path segments of the module name like `starkware.cairo.common.math`
@@ -123,12 +222,6 @@ impl ImportDirective {
     }
 }
 
-impl Visitable for ImportDirective {
-    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
-        v.visit_import(self)
-    }
-}
-
 impl fmt::Display for ImportDirective {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str("from ")?;
@@ -137,6 +230,7 @@ impl fmt::Display for ImportDirective {
     }
 }
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FunctionImport {
     Direct(Loc, Vec<AliasedId>),
     Parantheses(Loc, Vec<AliasedId>),
@@ -167,14 +261,24 @@ impl fmt::Display for FunctionImport {
 
 /// Cairo lang builtins
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Builtin {
+    Output,
     Pedersen,
     RangeCheck,
     Ecdsa,
+    Bitwise,
+    EcOp,
+    Keccak,
+    Poseidon,
+    SegmentArena,
     Other(String),
 }
 
 impl Builtin {
+    pub fn is_output(&self) -> bool {
+        matches!(self, Builtin::Output)
+    }
     pub fn is_pedersen(&self) -> bool {
         matches!(self, Builtin::Pedersen)
     }
@@ -184,18 +288,57 @@ impl Builtin {
     pub fn is_ecdsa(&self) -> bool {
         matches!(self, Builtin::Ecdsa)
     }
+    pub fn is_bitwise(&self) -> bool {
+        matches!(self, Builtin::Bitwise)
+    }
+    pub fn is_ec_op(&self) -> bool {
+        matches!(self, Builtin::EcOp)
+    }
+    pub fn is_keccak(&self) -> bool {
+        matches!(self, Builtin::Keccak)
+    }
+    pub fn is_poseidon(&self) -> bool {
+        matches!(self, Builtin::Poseidon)
+    }
+    pub fn is_segment_arena(&self) -> bool {
+        matches!(self, Builtin::SegmentArena)
+    }
     pub fn is_other(&self) -> bool {
         matches!(self, Builtin::Other(_))
     }
+
+    /// The position of the builtin in the order in which builtins must be declared, `None` for
+    /// unknown builtins
+    pub fn canonical_index(&self) -> Option<usize> {
+        let idx = match self {
+            Builtin::Output => 0,
+            Builtin::Pedersen => 1,
+            Builtin::RangeCheck => 2,
+            Builtin::Ecdsa => 3,
+            Builtin::Bitwise => 4,
+            Builtin::EcOp => 5,
+            Builtin::Keccak => 6,
+            Builtin::Poseidon => 7,
+            Builtin::SegmentArena => 8,
+            Builtin::Other(_) => return None,
+        };
+        Some(idx)
+    }
 }
 
 impl<T: Into<String>> From<T> for Builtin {
     fn from(s: T) -> Self {
         let s = s.into();
         match s.as_str() {
+            "output" => Builtin::Output,
             "pedersen" => Builtin::Pedersen,
             "range_check" => Builtin::RangeCheck,
             "ecdsa" => Builtin::Ecdsa,
+            "bitwise" => Builtin::Bitwise,
+            "ec_op" => Builtin::EcOp,
+            "keccak" => Builtin::Keccak,
+            "poseidon" => Builtin::Poseidon,
+            "segment_arena" => Builtin::SegmentArena,
             _ => Builtin::Other(s),
         }
     }
@@ -204,9 +347,15 @@ impl<T: Into<String>> From<T> for Builtin {
 impl fmt::Display for Builtin {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            Builtin::Output => f.write_str("output"),
             Builtin::Pedersen => f.write_str("pedersen"),
             Builtin::RangeCheck => f.write_str("range_check"),
             Builtin::Ecdsa => f.write_str("ecdsa"),
+            Builtin::Bitwise => f.write_str("bitwise"),
+            Builtin::EcOp => f.write_str("ec_op"),
+            Builtin::Keccak => f.write_str("keccak"),
+            Builtin::Poseidon => f.write_str("poseidon"),
+            Builtin::SegmentArena => f.write_str("segment_arena"),
             Builtin::Other(s) => s.fmt(f),
         }
     }
@@ -214,6 +363,7 @@ impl fmt::Display for Builtin {
 
 /// Cairo lang decorators
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Decorator {
     View,
     External,
@@ -235,9 +385,24 @@ impl Decorator {
     pub fn is_storage_var(&self) -> bool {
         matches!(self, Decorator::StorageVar)
     }
+    /// Whether the decorator marks a StarkNet entry point: `@view`, `@external` or `@constructor`
+    pub fn is_entry_point(&self) -> bool {
+        matches!(self, Decorator::View | Decorator::External | Decorator::Constructor)
+    }
     pub fn is_other(&self) -> bool {
         matches!(self, Decorator::Other(_))
     }
+
+    /// The name of the decorator without the `@`
+    pub fn name(&self) -> &str {
+        match self {
+            Decorator::View => "view",
+            Decorator::External => "external",
+            Decorator::Constructor => "constructor",
+            Decorator::StorageVar => "storage_var",
+            Decorator::Other(s) => s,
+        }
+    }
 }
 
 impl<T: Into<String>> From<T> for Decorator {
@@ -255,67 +420,71 @@ impl<T: Into<String>> From<T> for Decorator {
 
 impl fmt::Display for Decorator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_char('@')?;
-        match self {
-            Decorator::View => f.write_str("view"),
-            Decorator::External => f.write_str("external"),
-            Decorator::Constructor => f.write_str("constructor"),
-            Decorator::StorageVar => f.write_str("storage_var"),
-            Decorator::Other(s) => s.fmt(f),
-        }
+        write!(f, "@{}", self.name())
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StructDef {
+    /// Comment lines directly preceding the declaration
+    pub doc: Vec<Note>,
     pub decorators: Vec<Decorator>,
     pub name: String,
     pub members: Vec<MemberInfo>,
     pub loc: Loc,
 }
 
-impl Visitable for StructDef {
-    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
-        v.visit_struct_def(self)
+impl fmt::Display for StructDef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(&FormatOptions::default(), 0, f)
     }
 }
 
-impl fmt::Display for StructDef {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl FormatWith for StructDef {
+    fn fmt_with(&self, opts: &FormatOptions, _: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_trailing_newline(&self.doc, f)?;
         fmt_trailing_newline(&self.decorators, f)?;
         writeln!(f, "struct {}:", self.name)?;
-        for mem in &self.members {
-            writeln!(f, "    member {}", mem)?;
-        }
+        fmt_block(self.members.iter().map(|mem| format!("member {}", mem)), opts.indent, f)?;
         f.write_str("end")
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Namespace {
+    /// Comment lines directly preceding the declaration
+    pub doc: Vec<Note>,
     pub decorators: Vec<Decorator>,
     pub name: String,
     pub instructions: Vec<Instruction>,
     pub loc: Loc,
 }
 
-impl Visitable for Namespace {
-    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
-        v.visit_namespace(self)?;
-        self.instructions.visit(v)
+impl fmt::Display for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(&FormatOptions::default(), 0, f)
     }
 }
 
-impl fmt::Display for Namespace {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl FormatWith for Namespace {
+    fn fmt_with(
+        &self,
+        opts: &FormatOptions,
+        level: usize,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        fmt_trailing_newline(&self.doc, f)?;
         fmt_trailing_newline(&self.decorators, f)?;
         writeln!(f, "namespace {}:", self.name)?;
-        fmt_trailing_newline(&self.instructions, f)?;
+        fmt_instructions(&self.instructions, opts, level, f)?;
         f.write_str("end")
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Member {
     pub name: String,
     pub ty: CairoType,
@@ -328,6 +497,7 @@ impl fmt::Display for Member {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TypeStruct {
     pub name: Identifier,
     /// Indicates whether scope refers to the fully resolved name
@@ -337,6 +507,7 @@ pub struct TypeStruct {
 
 impl TypeStruct {
     /// Returns the scope of this type if it was resolved previously
+    #[cfg(feature = "std")]
     pub fn resolved_scope(&self) -> Option<ScopedName> {
         if self.is_fully_resolved {
             Some(ScopedName::new(self.name.clone()))
@@ -353,6 +524,7 @@ impl fmt::Display for TypeStruct {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CairoType {
     Felt,
     Id(TypeStruct),
@@ -365,6 +537,16 @@ impl CairoType {
     pub const FELT_SIZE: u64 = 1;
     pub const POINTER_SIZE: u64 = 1;
 
+    /// Parses a standalone type like `felt*` or `(felt, a.b.C)`
+    ///
+    /// Named types are not resolved, so their `is_fully_resolved` is `false`.
+    pub fn parse(input: &str) -> Result<Self, CairoLexerError> {
+        let ty = parser::cairo_grammar::CairoTypeParser::new()
+            .parse(input, CairoLexer::new(input))
+            .map_err(|err| CairoLexerError::from(err).located(&LineIndex::new(input)))?;
+        Ok(ty)
+    }
+
     pub fn is_felt(&self) -> bool {
         matches!(self, CairoType::Felt)
     }
@@ -377,11 +559,42 @@ impl CairoType {
     pub fn is_pointer(&self) -> bool {
         matches!(self, CairoType::Pointer(_))
     }
-}
 
-impl Visitable for CairoType {
-    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
-        v.visit_type(self)
+    /// Returns the size of the type if it can be determined without the identifier table, that is
+    /// if it doesn't contain any named struct types
+    pub fn builtin_size(&self) -> Option<u64> {
+        match self {
+            CairoType::Felt => Some(CairoType::FELT_SIZE),
+            CairoType::Pointer(_) => Some(CairoType::POINTER_SIZE),
+            CairoType::Tuple(tys) => {
+                tys.iter().try_fold(0u64, |size, ty| size.checked_add(ty.builtin_size()?))
+            }
+            CairoType::Id(_) => None,
+        }
+    }
+
+    /// Returns the number of pointer indirections, `felt` is 0, `felt*` is 1 and `felt**` is 2
+    pub fn pointer_depth(&self) -> usize {
+        self.split_pointers().0
+    }
+
+    /// Returns the pointer depth and the innermost non pointer type
+    fn split_pointers(&self) -> (usize, &CairoType) {
+        let mut depth = 0;
+        let mut ty = self;
+        while let CairoType::Pointer(ptr) = ty {
+            match ptr.as_ref() {
+                PointerType::Single(inner) => {
+                    depth += 1;
+                    ty = inner;
+                }
+                PointerType::Double(inner) => {
+                    depth += 2;
+                    ty = inner;
+                }
+            }
+        }
+        (depth, ty)
     }
 }
 
@@ -400,7 +613,12 @@ impl fmt::Display for CairoType {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// A pointer, `**` is parsed as a single token and is therefore represented as `Double`
+///
+/// Pointers are compared by their depth and base type, so `Double(felt)` equals
+/// `Single(Pointer(Single(felt)))`.
+#[derive(Debug, Clone, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PointerType {
     Single(CairoType),
     Double(CairoType),
@@ -415,14 +633,21 @@ impl PointerType {
         matches!(self, PointerType::Double(_))
     }
 
-    pub fn pointee(&self) -> &CairoType {
+    /// Returns the type the pointer points to, this is `T*` for `T**`
+    pub fn pointee(&self) -> CairoType {
+        self.clone().into_pointee()
+    }
+
+    /// Converts the pointer into the type it points to, this is `T*` for `T**`
+    pub fn into_pointee(self) -> CairoType {
         match self {
             PointerType::Single(ty) => ty,
-            PointerType::Double(ty) => ty,
+            PointerType::Double(ty) => CairoType::Pointer(Box::new(PointerType::Single(ty))),
         }
     }
 
-    pub fn into_pointee(self) -> CairoType {
+    /// Returns the innermost type, `T` for both `T*` and `T**`
+    pub fn base(&self) -> &CairoType {
         match self {
             PointerType::Single(ty) => ty,
             PointerType::Double(ty) => ty,
@@ -430,21 +655,31 @@ impl PointerType {
     }
 }
 
+impl PartialEq for PointerType {
+    fn eq(&self, other: &Self) -> bool {
+        let depth = |ptr: &PointerType| if ptr.is_single() { 1 } else { 2 };
+        let (lhs_depth, lhs) = self.base().split_pointers();
+        let (rhs_depth, rhs) = other.base().split_pointers();
+        lhs_depth + depth(self) == rhs_depth + depth(other) && lhs == rhs
+    }
+}
+
 impl fmt::Display for PointerType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             PointerType::Single(ty) => {
-                write!(f, "{}+", ty)
+                write!(f, "{}*", ty)
             }
-            PointerType::Double(ty) => write!(f, "{}++", ty),
+            PointerType::Double(ty) => write!(f, "{}**", ty),
         }
     }
 }
 
 /// A cairo expression
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expr {
-    Int(i128, Loc),
+    Int(U256, Loc),
     HexInt(String, Loc),
     ShortString(String, Loc),
     Hint(String, Loc),
@@ -465,37 +700,178 @@ pub enum Expr {
     Sub(Box<Expr>, Box<Expr>, Loc),
 }
 
-impl Visitable for Expr {
-    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
-        v.visit_expr(self)?;
+impl Expr {
+    /// Parses a standalone Cairo expression
+    pub fn parse(input: &str) -> Result<Self, CairoLexerError> {
+        let expr = parser::cairo_grammar::ExprParser::new()
+            .parse(input, CairoLexer::new(input))
+            .map_err(|err| CairoLexerError::from(err).located(&LineIndex::new(input)))?;
+        Ok(expr)
+    }
+
+    /// Returns the location of the expression
+    pub fn loc(&self) -> Loc {
         match self {
-            Expr::Int(_, _) => {}
-            Expr::HexInt(_, _) => {}
-            Expr::ShortString(_, _) => {}
-            Expr::Hint(_, _) => {}
-            Expr::Register(_, _) => {}
-            Expr::FunctionCall(_) => {}
-            Expr::Id(id, loc) => {
-                v.visit_expr_identifier(id, *loc)?;
-            }
-            Expr::Deref(_, _) => {}
-            Expr::Subscript(_, _, _) => {}
-            Expr::Dot(expr, id, loc) => {
-                v.visit_expr_dot(&mut **expr, id, *loc)?;
+            Expr::FunctionCall(call) => call.loc,
+            Expr::Int(_, loc) |
+            Expr::HexInt(_, loc) |
+            Expr::ShortString(_, loc) |
+            Expr::Hint(_, loc) |
+            Expr::Register(_, loc) |
+            Expr::Id(_, loc) |
+            Expr::Deref(_, loc) |
+            Expr::Subscript(_, _, loc) |
+            Expr::Dot(_, _, loc) |
+            Expr::Cast(_, _, loc) |
+            Expr::Parentheses(_, loc) |
+            Expr::Address(_, loc) |
+            Expr::Neg(_, loc) |
+            Expr::Pow(_, _, loc) |
+            Expr::Mul(_, _, loc) |
+            Expr::Div(_, _, loc) |
+            Expr::Add(_, _, loc) |
+            Expr::Sub(_, _, loc) => *loc,
+        }
+    }
+
+    /// Returns the value of an integer literal, decimal or hex, if it fits into 256 bits
+    pub fn as_u256(&self) -> Option<U256> {
+        match self {
+            Expr::Int(val, _) => Some(*val),
+            Expr::HexInt(val, _) => {
+                let digits = val.trim_start_matches("0x").trim_start_matches("0X").replace('_', "");
+                U256::from_str_radix(&digits, 16).ok()
             }
-            Expr::Cast(expr, ty, loc) => {
-                v.visit_expr_cat(&mut **expr, ty, *loc)?;
+            _ => None,
+        }
+    }
+
+    /// Returns the felt value of a short string literal
+    ///
+    /// Escape sequences (`\n`, `\t`, `\\`, `\'`, `\x41`, ...) are interpreted and the resulting
+    /// bytes are packed big-endian, so `'ab'` is `0x6162`. At most 31 bytes fit into a felt.
+    #[cfg(feature = "std")]
+    pub fn short_string_value(&self) -> Result<U256, CairoError> {
+        let (val, loc) = match self {
+            Expr::ShortString(val, loc) => (val, loc),
+            expr => {
+                return Err(CairoError::Preprocess(format!(
+                    "Expected a short string but found `{}`",
+                    expr
+                )))
             }
-            Expr::Parentheses(_, _) => {}
-            Expr::Address(_, _) => {}
-            Expr::Neg(_, _) => {}
-            Expr::Pow(_, _, _) => {}
-            Expr::Mul(_, _, _) => {}
-            Expr::Div(_, _, _) => {}
-            Expr::Add(_, _, _) => {}
-            Expr::Sub(_, _, _) => {}
         };
-        todo!()
+        let invalid_escape = || {
+            CairoError::Preprocess(format!("Invalid escape sequence in '{}' {}", val, loc))
+                .spanned(*loc)
+        };
+        let mut bytes = Vec::with_capacity(val.len());
+        let mut chars = val.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '\\' {
+                let mut buf = [0u8; 4];
+                bytes.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+                continue
+            }
+            let byte = match chars.next().ok_or_else(invalid_escape)? {
+                'n' => b'\n',
+                't' => b'\t',
+                'r' => b'\r',
+                '0' => 0,
+                '\\' => b'\\',
+                '\'' => b'\'',
+                '"' => b'"',
+                'x' => {
+                    let digits: String = chars.by_ref().take(2).collect();
+                    if digits.len() != 2 {
+                        return Err(invalid_escape())
+                    }
+                    u8::from_str_radix(&digits, 16).map_err(|_| invalid_escape())?
+                }
+                _ => return Err(invalid_escape()),
+            };
+            bytes.push(byte);
+        }
+        if bytes.len() > 31 {
+            return Err(CairoError::Preprocess(format!(
+                "Short string '{}' exceeds 31 characters {}",
+                val, loc
+            ))
+            .spanned(*loc))
+        }
+        Ok(U256::from_big_endian(&bytes))
+    }
+
+    /// Whether the expression is made up of literals only
+    ///
+    /// Integer and short string literals are constant, as are arithmetic operations and
+    /// parentheses over constants. Anything that needs to be resolved or evaluated at runtime, like
+    /// registers, dereferences, identifiers or function calls, is not.
+    pub fn is_const(&self) -> bool {
+        match self {
+            Expr::Int(_, _) | Expr::HexInt(_, _) | Expr::ShortString(_, _) => true,
+            Expr::Neg(expr, _) => expr.is_const(),
+            Expr::Pow(lhs, rhs, _) |
+            Expr::Mul(lhs, rhs, _) |
+            Expr::Div(lhs, rhs, _) |
+            Expr::Add(lhs, rhs, _) |
+            Expr::Sub(lhs, rhs, _) => lhs.is_const() && rhs.is_const(),
+            Expr::Parentheses(exprs, _) => match exprs.as_slice() {
+                [ExprAssignment::Expr(expr, _)] => expr.is_const(),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Whether the expression references `ap`, `fp` or `pc` anywhere in its tree
+    pub fn contains_register(&self) -> bool {
+        match self {
+            Expr::Register(_, _) => true,
+            Expr::Int(_, _) |
+            Expr::HexInt(_, _) |
+            Expr::ShortString(_, _) |
+            Expr::Hint(_, _) |
+            Expr::Id(_, _) => false,
+            Expr::FunctionCall(call) => call
+                .implicit_args
+                .iter()
+                .flatten()
+                .chain(call.args.iter())
+                .any(|arg| arg.expr().contains_register()),
+            Expr::Deref(expr, _) |
+            Expr::Dot(expr, _, _) |
+            Expr::Cast(expr, _, _) |
+            Expr::Address(expr, _) |
+            Expr::Neg(expr, _) => expr.contains_register(),
+            Expr::Parentheses(exprs, _) => exprs.iter().any(|e| e.expr().contains_register()),
+            Expr::Subscript(lhs, rhs, _) |
+            Expr::Pow(lhs, rhs, _) |
+            Expr::Mul(lhs, rhs, _) |
+            Expr::Div(lhs, rhs, _) |
+            Expr::Add(lhs, rhs, _) |
+            Expr::Sub(lhs, rhs, _) => lhs.contains_register() || rhs.contains_register(),
+        }
+    }
+
+    /// The binding strength of the expression's outermost operator, atoms bind the strongest
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Add(_, _, _) | Expr::Sub(_, _, _) => 1,
+            Expr::Mul(_, _, _) | Expr::Div(_, _, _) => 2,
+            Expr::Neg(_, _) | Expr::Address(_, _) => 3,
+            Expr::Pow(_, _, _) => 4,
+            _ => 5,
+        }
+    }
+
+    /// Writes the expression, wrapped in parentheses if it binds weaker than `precedence`
+    fn fmt_operand(&self, precedence: u8, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.precedence() < precedence {
+            write!(f, "({})", self)
+        } else {
+            fmt::Display::fmt(self, f)
+        }
     }
 }
 
@@ -517,10 +893,12 @@ impl fmt::Display for Expr {
                 write!(f, "[{}]", expr)
             }
             Expr::Subscript(lhs, rhs, _) => {
-                write!(f, "{} [{}]", lhs, rhs)
+                lhs.fmt_operand(5, f)?;
+                write!(f, " [{}]", rhs)
             }
             Expr::Dot(lhs, rhs, _) => {
-                write!(f, "{}.{}", lhs, rhs)
+                lhs.fmt_operand(5, f)?;
+                write!(f, ".{}", rhs)
             }
             Expr::Cast(lhs, rhs, _) => {
                 write!(f, "cast({}, {})", lhs, rhs)
@@ -531,25 +909,40 @@ impl fmt::Display for Expr {
                 f.write_char(')')
             }
             Expr::Address(expr, _) => {
-                write!(f, "&{}", expr)
+                f.write_char('&')?;
+                expr.fmt_operand(3, f)
             }
             Expr::Neg(expr, _) => {
-                write!(f, "-{}", expr)
+                f.write_char('-')?;
+                expr.fmt_operand(3, f)
             }
+            // `**` is right associative and only accepts atoms on the left
             Expr::Pow(lhs, rhs, _) => {
-                write!(f, "{}**{}", lhs, rhs)
+                lhs.fmt_operand(5, f)?;
+                f.write_str("**")?;
+                // the exponent may be a unary expression, `a**-b`
+                rhs.fmt_operand(3, f)
             }
+            // the remaining operators are left associative
             Expr::Mul(lhs, rhs, _) => {
-                write!(f, "{} * {}", lhs, rhs)
+                lhs.fmt_operand(2, f)?;
+                f.write_str(" * ")?;
+                rhs.fmt_operand(3, f)
             }
             Expr::Div(lhs, rhs, _) => {
-                write!(f, "{} / {}", lhs, rhs)
+                lhs.fmt_operand(2, f)?;
+                f.write_str(" / ")?;
+                rhs.fmt_operand(3, f)
             }
             Expr::Add(lhs, rhs, _) => {
-                write!(f, "{} + {}", lhs, rhs)
+                lhs.fmt_operand(1, f)?;
+                f.write_str(" + ")?;
+                rhs.fmt_operand(2, f)
             }
             Expr::Sub(lhs, rhs, _) => {
-                write!(f, "{} - {}", lhs, rhs)
+                lhs.fmt_operand(1, f)?;
+                f.write_str(" - ")?;
+                rhs.fmt_operand(2, f)
             }
         }
     }
@@ -557,6 +950,7 @@ impl fmt::Display for Expr {
 
 /// Expression of  `expr | id  = expr`
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ExprAssignment {
     Expr(Expr, Loc),
     Id(String, Expr, Loc),
@@ -578,12 +972,6 @@ impl ExprAssignment {
     }
 }
 
-impl Visitable for ExprAssignment {
-    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
-        v.visit_expr_assignment(self)
-    }
-}
-
 impl fmt::Display for ExprAssignment {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -597,6 +985,7 @@ impl fmt::Display for ExprAssignment {
 
 /// Expression as condition for an if statement
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BoolExpr {
     Equal(Expr, Expr),
     NotEqual(Expr, Expr),
@@ -617,6 +1006,7 @@ impl fmt::Display for BoolExpr {
 
 /// cairo registers
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Register {
     Ap,
     Fp,
@@ -633,27 +1023,31 @@ impl fmt::Display for Register {
 
 /// Definition of a constant
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstantDef {
+    /// Comment lines directly preceding the declaration
+    pub doc: Vec<Note>,
     pub name: String,
+    /// The optional type annotation `const X : felt = 1`
+    pub ty: Option<CairoType>,
     pub init: Expr,
     pub loc: Loc,
 }
 
 impl fmt::Display for ConstantDef {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "const {} = {}", self.name, self.init)
-    }
-}
-
-impl Visitable for ConstantDef {
-    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
-        v.visit_const_def(self)?;
-        v.visit_expr(&mut self.init)
+        fmt_trailing_newline(&self.doc, f)?;
+        write!(f, "const {}", self.name)?;
+        if let Some(ref ty) = self.ty {
+            write!(f, " : {}", ty)?;
+        }
+        write!(f, " = {}", self.init)
     }
 }
 
 /// An identifier with an optional type hint `local <id> : ty`
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TypedIdentifier {
     pub is_local: bool,
     pub id: String,
@@ -667,16 +1061,6 @@ impl TypedIdentifier {
     }
 }
 
-impl Visitable for TypedIdentifier {
-    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
-        v.visit_typed_identifier(self)?;
-        if let Some(ty) = self.ty.as_mut() {
-            ty.visit(v)?;
-        }
-        Ok(())
-    }
-}
-
 impl fmt::Display for TypedIdentifier {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.is_local {
@@ -684,7 +1068,7 @@ impl fmt::Display for TypedIdentifier {
         }
         self.id.fmt(f)?;
         if let Some(ref ty) = self.ty {
-            write!(f, " {}", ty)?
+            write!(f, " : {}", ty)?
         }
 
         Ok(())
@@ -693,6 +1077,7 @@ impl fmt::Display for TypedIdentifier {
 
 /// Various cairo instructions a file is made up of
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Instruction {
     Const(ConstantDef),
     Member(TypedIdentifier, Loc),
@@ -719,7 +1104,7 @@ pub enum Instruction {
     // instruction
     Assign(Expr, Expr, Loc),
     Jmp(Jmp, Loc),
-    CallInstruction(Call),
+    CallInstruction(Call, Loc),
     Ret(Loc),
     ApAddAssign(Expr, Loc),
     ApAdd(Box<Instruction>, Loc),
@@ -730,82 +1115,14 @@ impl Instruction {
     /// Parses a Cairo instruction
     pub fn parse(input: &str) -> Result<Self, CairoLexerError> {
         let input = input.trim_start();
-        let instruction =
-            parser::cairo_grammar::CodeElementParser::new().parse(input, CairoLexer::new(input))?;
+        let mut instruction = parser::cairo_grammar::CodeElementParser::new()
+            .parse(input, CairoLexer::new(input))
+            .map_err(|err| CairoLexerError::from(err).located(&LineIndex::new(input)))?;
+        DocCommentCollector { input }.collect(core::slice::from_mut(&mut instruction));
         Ok(instruction)
     }
 }
 
-impl Visitable for Instruction {
-    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
-        match self {
-            Instruction::Const(i) => {
-                i.visit(v)?;
-            }
-            Instruction::Member(_, _) => {}
-            Instruction::Let(id, rvalue, _) => {
-                v.visit_reference(id, &mut **rvalue)?;
-            }
-            Instruction::Local(id, expr, _) => {
-                v.visit_local_var(id, expr)?;
-                id.visit(v)?;
-                if let Some(expr) = expr {
-                    v.visit_expr(expr)?;
-                }
-            }
-            Instruction::Tempvar(id, expr, _) => {
-                v.visit_temp_var(id, expr)?;
-                id.visit(v)?;
-                if let Some(expr) = expr {
-                    v.visit_expr(expr)?;
-                }
-            }
-            Instruction::Assert(_, _, _) => {}
-            Instruction::StaticAssert(_, _, _) => {}
-            Instruction::Return(_, _) => {}
-            Instruction::ReturnFunctionCall(_, _) => {}
-            Instruction::If(i) => {
-                i.visit(v)?;
-            }
-            Instruction::Label(i, loc) => {
-                v.visit_label(i, *loc)?;
-            }
-            Instruction::Function(i) => {
-                v.enter_function(i)?;
-                i.visit(v)?;
-                v.exit_function(i)?;
-            }
-            Instruction::FunctionCall(_) => {}
-            Instruction::Struct(i) => {
-                i.visit(v)?;
-            }
-            Instruction::Namespace(i) => {
-                v.enter_namespace(i)?;
-                i.visit(v)?;
-                v.exit_namespace(i)?;
-            }
-            Instruction::WithAttrStatement(_) => {}
-            Instruction::WithStatement(i) => {
-                i.visit(v)?;
-            }
-            Instruction::Hint(_, _) => {}
-            Instruction::Directive(_) => {}
-            Instruction::Import(i) => {
-                i.visit(v)?;
-            }
-            Instruction::AllocLocals(_) => {}
-            Instruction::Assign(_, _, _) => {}
-            Instruction::Jmp(_, _) => {}
-            Instruction::CallInstruction(_) => {}
-            Instruction::Ret(_) => {}
-            Instruction::ApAddAssign(_, _) => {}
-            Instruction::ApAdd(_, _) => {}
-            Instruction::DataWord(_, _) => {}
-        };
-        Ok(())
-    }
-}
-
 impl fmt::Display for Instruction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -855,9 +1172,7 @@ impl fmt::Display for Instruction {
             Instruction::Namespace(ins) => ins.fmt(f),
             Instruction::WithAttrStatement(ins) => ins.fmt(f),
             Instruction::WithStatement(ins) => ins.fmt(f),
-            Instruction::Hint(ins, _) => {
-                write!(f, "%{{{}%}}", ins)
-            }
+            Instruction::Hint(ins, _) => fmt_hint(ins, DISPLAY_INDENT, f),
             Instruction::Directive(ins) => ins.fmt(f),
             Instruction::Import(ins) => ins.fmt(f),
             Instruction::AllocLocals(_) => f.write_str("alloc_locals"),
@@ -865,7 +1180,7 @@ impl fmt::Display for Instruction {
                 write!(f, "{} = {}", lhs, rhs)
             }
             Instruction::Jmp(ins, _) => ins.fmt(f),
-            Instruction::CallInstruction(ins) => ins.fmt(f),
+            Instruction::CallInstruction(ins, _) => ins.fmt(f),
             Instruction::Ret(_) => f.write_str("ret"),
             Instruction::ApAddAssign(ins, _) => {
                 write!(f, "ap+={}", ins)
@@ -881,20 +1196,12 @@ impl fmt::Display for Instruction {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Directive {
     Lang(Loc, Identifier),
     Builtins(Loc, Vec<Builtin>),
 }
 
-impl Visitable for Directive {
-    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
-        match self {
-            Directive::Lang(_, id) => v.visit_lang(id),
-            Directive::Builtins(loc, builtins) => v.visit_builtins(builtins, *loc),
-        }
-    }
-}
-
 impl fmt::Display for Directive {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -911,6 +1218,7 @@ impl fmt::Display for Directive {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RValue {
     Call(Call),
     Expr(Expr),
@@ -926,6 +1234,7 @@ impl fmt::Display for RValue {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Call {
     Rel(Expr),
     Abs(Expr),
@@ -948,6 +1257,7 @@ impl fmt::Display for Call {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Jmp {
     Rel(Expr),
     Abs(Expr),
@@ -979,30 +1289,36 @@ impl fmt::Display for Jmp {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WithStatement {
     pub ids: Vec<AliasedId>,
     pub instructions: Vec<Instruction>,
     pub loc: Loc,
 }
 
-impl Visitable for WithStatement {
-    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
-        v.visit_with(self)?;
-        self.instructions.visit(v)
+impl fmt::Display for WithStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(&FormatOptions::default(), 0, f)
     }
 }
 
-impl fmt::Display for WithStatement {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl FormatWith for WithStatement {
+    fn fmt_with(
+        &self,
+        opts: &FormatOptions,
+        level: usize,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
         f.write_str("with ")?;
         comma_separated(&self.ids, f)?;
         f.write_str(" :\n")?;
-        fmt_trailing_newline(&self.instructions, f)?;
+        fmt_instructions(&self.instructions, opts, level, f)?;
         f.write_str("end")
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WithAttrStatement {
     pub id: String,
     pub attr_val: Option<Vec<String>>,
@@ -1012,6 +1328,17 @@ pub struct WithAttrStatement {
 
 impl fmt::Display for WithAttrStatement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(&FormatOptions::default(), 0, f)
+    }
+}
+
+impl FormatWith for WithAttrStatement {
+    fn fmt_with(
+        &self,
+        opts: &FormatOptions,
+        level: usize,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
         write!(f, "with_attr {} ", self.id)?;
         if let Some(ref attr) = self.attr_val {
             f.write_char('(')?;
@@ -1025,12 +1352,13 @@ impl fmt::Display for WithAttrStatement {
             f.write_char(')')?;
         }
         f.write_str(" :\n")?;
-        fmt_trailing_newline(&self.instructions, f)?;
+        fmt_instructions(&self.instructions, opts, level, f)?;
         f.write_str("end")
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RefBinding {
     Id(TypedIdentifier),
     List(Vec<TypedIdentifier>),
@@ -1050,7 +1378,10 @@ impl fmt::Display for RefBinding {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunctionDef {
+    /// Comment lines directly preceding the declaration
+    pub doc: Vec<Note>,
     pub decorators: Vec<Decorator>,
     pub name: String,
     pub implicit_args: Option<Vec<TypedIdentifier>>,
@@ -1060,36 +1391,71 @@ pub struct FunctionDef {
     pub loc: Loc,
 }
 
-impl Visitable for FunctionDef {
-    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
-        v.visit_function(self)?;
-        self.instructions.visit(v)
+impl FunctionDef {
+    /// Returns the signature of the function on a single line, without its body
+    ///
+    /// The decorators are followed by the name and the arguments, like
+    /// `@view foo{z : felt}(a : felt, b : MyStruct) -> (c : felt)`.
+    pub fn signature_string(&self) -> String {
+        let mut s = String::new();
+        for decorator in self.decorators.iter() {
+            s.push_str(&decorator.to_string());
+            s.push(' ');
+        }
+        s.push_str(&self.name);
+        s.push_str(&FunctionArgs(self).to_string());
+        s
     }
 }
 
-impl fmt::Display for FunctionDef {
+/// Displays the implicit arguments, the input arguments and the return values of a function
+struct FunctionArgs<'a>(&'a FunctionDef);
+
+impl<'a> fmt::Display for FunctionArgs<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt_trailing_newline(&self.decorators, f)?;
-        write!(f, "func {}", self.name)?;
-        if let Some(ref args) = self.implicit_args {
+        let FunctionArgs(def) = self;
+        if let Some(ref args) = def.implicit_args {
             f.write_char('{')?;
             comma_separated(args, f)?;
             f.write_char('}')?;
         }
         f.write_char('(')?;
-        comma_separated(&self.instructions, f)?;
+        comma_separated(&def.input_args, f)?;
         f.write_char(')')?;
-        if let Some(ref args) = self.return_values {
-            f.write_str("-> (")?;
+        if let Some(ref args) = def.return_values {
+            f.write_str(" -> (")?;
             comma_separated(args, f)?;
             f.write_char(')')?;
         }
-        fmt_trailing_newline(&self.instructions, f)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for FunctionDef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(&FormatOptions::default(), 0, f)
+    }
+}
+
+impl FormatWith for FunctionDef {
+    fn fmt_with(
+        &self,
+        opts: &FormatOptions,
+        level: usize,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        fmt_trailing_newline(&self.doc, f)?;
+        fmt_trailing_newline(&self.decorators, f)?;
+        let signature = format!("func {}{}:", self.name, FunctionArgs(self));
+        format::fmt_wrapped(&signature, opts, level, f)?;
+        f.write_char('\n')?;
+        fmt_instructions(&self.instructions, opts, level, f)?;
         f.write_str("end")
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunctionCall {
     pub id: Identifier,
     pub implicit_args: Option<Vec<ExprAssignment>>,
@@ -1113,6 +1479,7 @@ impl fmt::Display for FunctionCall {
 
 /// Represents a defined member within a struct
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MemberInfo {
     pub name: String,
     pub ty: CairoType,
@@ -1126,6 +1493,7 @@ impl fmt::Display for MemberInfo {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IfStatement {
     pub cond: BoolExpr,
     pub instructions: Vec<Instruction>,
@@ -1135,30 +1503,31 @@ pub struct IfStatement {
     pub loc: Loc,
 }
 
-impl Visitable for IfStatement {
-    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
-        v.visit_if(self)?;
-        self.instructions.visit(v)?;
-        if let Some(e) = self.else_branch.as_mut() {
-            e.visit(v)?;
-        }
-        Ok(())
+impl fmt::Display for IfStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(&FormatOptions::default(), 0, f)
     }
 }
 
-impl fmt::Display for IfStatement {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl FormatWith for IfStatement {
+    fn fmt_with(
+        &self,
+        opts: &FormatOptions,
+        level: usize,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
         writeln!(f, "if {} :", self.cond)?;
-        fmt_trailing_newline(&self.instructions, f)?;
+        fmt_instructions(&self.instructions, opts, level, f)?;
         if let Some(ref el) = self.else_branch {
             writeln!(f, "else:")?;
-            fmt_trailing_newline(el, f)?;
+            fmt_instructions(el, opts, level, f)?;
         }
         f.write_str("end")
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Notes {
     pub notes: Vec<Note>,
     pub loc: Loc,
@@ -1174,6 +1543,7 @@ impl fmt::Display for Notes {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Note {
     NewLine(Loc),
     Comment(String, Loc),
@@ -1191,6 +1561,7 @@ impl fmt::Display for Note {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Separator {
     Comma(Loc),
     NewLine(Loc),
@@ -1209,6 +1580,63 @@ impl fmt::Display for Separator {
     }
 }
 
+/// Attaches the comment lines directly above a declaration to it, since the lexer skips comments
+struct DocCommentCollector<'a> {
+    input: &'a str,
+}
+
+impl<'a> DocCommentCollector<'a> {
+    /// Returns the consecutive `#` comment lines preceding the declaration that starts at `offset`
+    fn doc_comments(&self, offset: usize) -> Vec<Note> {
+        // the location of a declaration may start with the whitespace before its first token
+        let start = offset + self.input[offset..].len() - self.input[offset..].trim_start().len();
+        let mut line_start = self.input[..start].rfind('\n').map(|i| i + 1).unwrap_or_default();
+        let mut doc = Vec::new();
+        while line_start > 0 {
+            let prev_start = self.input[..line_start - 1].rfind('\n').map(|i| i + 1).unwrap_or(0);
+            let line = &self.input[prev_start..line_start - 1];
+            let comment = match line.trim().strip_prefix('#') {
+                Some(comment) => comment.trim(),
+                None => break,
+            };
+            let loc = Loc(prev_start + line.find('#').expect("is comment"), line_start - 1);
+            doc.push(Note::Comment(comment.to_string(), loc));
+            line_start = prev_start;
+        }
+        doc.reverse();
+        doc
+    }
+}
+
+impl<'a> DocCommentCollector<'a> {
+    /// Attaches the doc comments of all declarations in the block, including nested blocks
+    fn collect(&self, instructions: &mut [Instruction]) {
+        for ins in instructions {
+            match ins {
+                Instruction::Const(c) => c.doc = self.doc_comments(c.loc.0),
+                Instruction::Struct(s) => s.doc = self.doc_comments(s.loc.0),
+                Instruction::Function(f) => {
+                    f.doc = self.doc_comments(f.loc.0);
+                    self.collect(&mut f.instructions);
+                }
+                Instruction::Namespace(n) => {
+                    n.doc = self.doc_comments(n.loc.0);
+                    self.collect(&mut n.instructions);
+                }
+                Instruction::If(i) => {
+                    self.collect(&mut i.instructions);
+                    if let Some(e) = i.else_branch.as_mut() {
+                        self.collect(e);
+                    }
+                }
+                Instruction::WithStatement(w) => self.collect(&mut w.instructions),
+                Instruction::WithAttrStatement(w) => self.collect(&mut w.instructions),
+                _ => {}
+            }
+        }
+    }
+}
+
 fn fmt_trailing_newline<I, D>(items: I, f: &mut fmt::Formatter<'_>) -> fmt::Result
 where
     I: IntoIterator<Item = D>,
@@ -1222,6 +1650,127 @@ where
     f.write_char('\n')
 }
 
+/// Pretty-printing of nodes that contain code blocks
+///
+/// The `Display` output of these nodes uses the default [`FormatOptions`].
+pub(crate) trait FormatWith {
+    /// Writes the node at the given nesting level, the caller indents its first line
+    fn fmt_with(
+        &self,
+        opts: &FormatOptions,
+        level: usize,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result;
+}
+
+/// Displays a node with the given options at the given nesting level
+pub(crate) struct Formatted<'a, T>(pub &'a T, pub &'a FormatOptions, pub usize);
+
+impl<'a, T: FormatWith> fmt::Display for Formatted<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Formatted(node, opts, level) = self;
+        node.fmt_with(opts, *level, f)
+    }
+}
+
+impl FormatWith for Instruction {
+    fn fmt_with(
+        &self,
+        opts: &FormatOptions,
+        level: usize,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Instruction::If(ins) => ins.fmt_with(opts, level, f),
+            Instruction::Function(ins) => ins.fmt_with(opts, level, f),
+            Instruction::Struct(ins) => ins.fmt_with(opts, level, f),
+            Instruction::Namespace(ins) => ins.fmt_with(opts, level, f),
+            Instruction::WithAttrStatement(ins) => ins.fmt_with(opts, level, f),
+            Instruction::WithStatement(ins) => ins.fmt_with(opts, level, f),
+            Instruction::Hint(ins, _) => fmt_hint(ins, opts.indent, f),
+            ins => format::fmt_wrapped(&ins.to_string(), opts, level, f),
+        }
+    }
+}
+
+/// Writes the instructions of a code block one level deeper than the block's header
+fn fmt_instructions(
+    instructions: &[Instruction],
+    opts: &FormatOptions,
+    level: usize,
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    fmt_block(instructions.iter().map(|ins| Formatted(ins, opts, level + 1)), opts.indent, f)
+}
+
+/// Writes the elements of a code block on separate lines, indented by one level
+///
+/// Nested blocks are indented further, because their output passes through all enclosing blocks.
+fn fmt_block<I, D>(items: I, indent: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result
+where
+    I: IntoIterator<Item = D>,
+    D: fmt::Display,
+{
+    let mut indented = Indented { f, indent, line_start: true };
+    for item in items {
+        writeln!(indented, "{}", item)?;
+    }
+    Ok(())
+}
+
+/// A writer that indents every non empty line by the given number of spaces
+struct Indented<'a, 'b> {
+    f: &'a mut fmt::Formatter<'b>,
+    indent: usize,
+    line_start: bool,
+}
+
+impl<'a, 'b> Write for Indented<'a, 'b> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if c == '\n' {
+                self.line_start = true;
+            } else if self.line_start {
+                write!(self.f, "{:1$}", "", self.indent)?;
+                self.line_start = false;
+            }
+            self.f.write_char(c)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a `%{ ... %}` hint
+///
+/// The lines of a multi line hint are dedented and then indented by one level, the closing `%}`
+/// is placed on its own line. The enclosing blocks indent all lines of the hint like any other
+/// code.
+fn fmt_hint(hint: &str, indent: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut lines = hint.split('\n');
+    let first = lines.next().unwrap_or_default();
+    let mut body = lines.collect::<Vec<_>>();
+    if body.is_empty() {
+        return write!(f, "%{{{}%}}", hint)
+    }
+    if body.last().is_some_and(|line| line.trim().is_empty()) {
+        body.pop();
+    }
+    let dedent = body
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or_default();
+    write!(f, "%{{{}", first.trim_end())?;
+    for line in body {
+        f.write_char('\n')?;
+        if !line.trim().is_empty() {
+            write!(f, "{:1$}{2}", "", indent, line[dedent..].trim_end())?;
+        }
+    }
+    f.write_str("\n%}")
+}
+
 fn comma_separated<I, D>(items: I, f: &mut fmt::Formatter<'_>) -> fmt::Result
 where
     I: IntoIterator<Item = D>,
@@ -0,0 +1,232 @@
+//! Configurable pretty-printing of cairo files
+
+use crate::parser::ast::{CairoFile, Formatted};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{self, Write};
+
+/// The indentation width of the `Display` output of the AST
+pub(crate) const DISPLAY_INDENT: usize = 4;
+
+/// Options for [`CairoFile::to_string_with`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// number of spaces per indentation level
+    pub indent: usize,
+    /// the width after which argument lists are wrapped, one argument per line
+    pub max_width: Option<usize>,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self { indent: DISPLAY_INDENT, max_width: None }
+    }
+}
+
+impl CairoFile {
+    /// Pretty-prints the file according to the given options
+    ///
+    /// With the default options this is the same as the `Display` output. Hints are re-indented
+    /// like any other block, but their content is never wrapped.
+    pub fn to_string_with(&self, opts: &FormatOptions) -> String {
+        Formatted(self, opts, 0).to_string()
+    }
+}
+
+/// Writes the single line statement at the given nesting level, wrapping its first argument list
+/// if the line exceeds the max width
+///
+/// The enclosing blocks indent the first line, only the wrapped lines are indented here.
+pub(crate) fn fmt_wrapped(
+    line: &str,
+    opts: &FormatOptions,
+    level: usize,
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    fmt_line(line, 0, opts, level, f)
+}
+
+/// Writes the line `depth` levels below the statement
+fn fmt_line(
+    content: &str,
+    depth: usize,
+    opts: &FormatOptions,
+    level: usize,
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    write!(f, "{:1$}", "", depth * opts.indent)?;
+    let width = (level + depth) * opts.indent + content.len();
+    let too_long = opts.max_width.is_some_and(|w| width > w);
+    // comments are printed as is
+    match find_argument_list(content).filter(|_| too_long && !content.starts_with('#')) {
+        Some((open, close)) => {
+            f.write_str(&content[..=open])?;
+            for arg in split_arguments(&content[open + 1..close]) {
+                f.write_char('\n')?;
+                fmt_line(&format!("{},", arg), depth + 1, opts, level, f)?;
+            }
+            f.write_char('\n')?;
+            fmt_line(&content[close..], depth, opts, level, f)
+        }
+        None => f.write_str(content),
+    }
+}
+
+/// Returns the positions of the parentheses of the first non empty argument list of a call or
+/// function signature in the line
+fn find_argument_list(line: &str) -> Option<(usize, usize)> {
+    let bytes = line.as_bytes();
+    let mut quote = None;
+    let mut open = None;
+    let mut depth = 0usize;
+    for (i, &c) in bytes.iter().enumerate() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, b'\'' | b'"') => quote = Some(c),
+            (None, b'(') => {
+                if open.is_some() {
+                    depth += 1;
+                } else if i > 0 &&
+                    (bytes[i - 1].is_ascii_alphanumeric() || b"_}".contains(&bytes[i - 1]))
+                {
+                    open = Some(i);
+                }
+            }
+            (None, b')') => match open {
+                Some(start) if depth == 0 => {
+                    if line[start + 1..i].trim().is_empty() {
+                        open = None;
+                    } else {
+                        return Some((start, i))
+                    }
+                }
+                Some(_) => depth -= 1,
+                None => {}
+            },
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits an argument list at its top level commas
+fn split_arguments(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '(' | '{' | '[') => depth += 1,
+            (None, ')' | '}' | ']') => depth = depth.saturating_sub(1),
+            (None, ',') if depth == 0 => {
+                parts.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(args[start..].trim());
+    parts.retain(|arg| !arg.is_empty());
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_format_with_indent() {
+        let code = r#"func foo(a) -> (b):
+    if a == 0:
+        %{
+            x = 1
+        %}
+        return (b=1)
+    end
+    return (b=a)
+end
+"#;
+        let file = CairoFile::parse(code).unwrap();
+        assert_eq!(file.to_string_with(&FormatOptions::default()), file.to_string());
+
+        let opts = FormatOptions { indent: 2, ..Default::default() };
+        let formatted = file.to_string_with(&opts);
+        assert_eq!(
+            formatted,
+            r#"func foo(a) -> (b):
+  if a == 0 :
+    %{
+      x = 1
+    %}
+    return(b = 1)
+  end
+  return(b = a)
+end
+"#
+        );
+        // only the indentation of the hint changed
+        assert_eq!(CairoFile::parse(&formatted).unwrap().to_string(), file.to_string());
+    }
+
+    #[test]
+    fn can_reindent_hints() {
+        let code = r#"func foo():
+    %{
+x = compute(1, 2, 3)
+if x:
+    y = 2
+      %}
+    ret
+end
+"#;
+        let file = CairoFile::parse(code).unwrap();
+        let expected = r#"func foo():
+    %{
+        x = compute(1, 2, 3)
+        if x:
+            y = 2
+    %}
+    ret
+end
+"#;
+        assert_eq!(file.to_string(), expected);
+        // hints are never wrapped
+        let opts = FormatOptions { max_width: Some(20), ..Default::default() };
+        assert_eq!(file.to_string_with(&opts), expected);
+    }
+
+    #[test]
+    fn can_wrap_long_argument_lists() {
+        let code = r#"func main():
+    let (res) = compute(first=1, second=bar(2, 3), third='a,b')
+    foo(1)
+    ret
+end
+"#;
+        let file = CairoFile::parse(code).unwrap();
+        let opts = FormatOptions { max_width: Some(30), ..Default::default() };
+        let formatted = file.to_string_with(&opts);
+        assert_eq!(
+            formatted,
+            r#"func main():
+    let (res) = compute(
+        first = 1,
+        second = bar(2, 3),
+        third = 'a,b',
+    )
+    foo(1)
+    ret
+end
+"#
+        );
+        assert!(CairoFile::parse(&formatted).unwrap().structural_eq(&file));
+    }
+}
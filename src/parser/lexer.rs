@@ -1,51 +1,84 @@
-use crate::parser::ast::Loc;
+use crate::parser::ast::{LineIndex, Loc};
 
+use alloc::{borrow::ToOwned, boxed::Box, format, string::String, vec::Vec};
+use core::{fmt, iter::Peekable, str::CharIndices};
 use lalrpop_util::ParseError;
-use std::{iter::Peekable, str::CharIndices};
 use unicode_xid::UnicodeXID;
 
 pub type Spanned<Token, Loc, Error> = Result<(Loc, Token, Loc), Error>;
 
 /// Various errors that can happen during lexing
-#[derive(Debug, Clone, thiserror::Error, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum CairoLexerError {
-    #[error("EndOfFileInString {0}:{1}")]
     EndOfFileInString(usize, usize),
-    #[error("EndOfFileInHint {0}:{1}")]
     EndOfFileInHint(usize, usize),
-    #[error("EndofFileInHex {0}:{1}")]
     EndofFileInHex(usize, usize),
-    #[error("UnrecognisedToken {0}:{1} `{2}`")]
     UnrecognisedToken(usize, usize, String),
-    #[error("MissingNumber {0}:{1}")]
     MissingNumber(usize, usize),
-    #[error("Unsupported {0}")]
     Unsupported(String),
-    #[error("ParserError {0}:{1} `{2}`")]
     ParserError(usize, usize, String),
+    InvalidToken { loc: Loc },
+    UnexpectedToken { loc: Loc, found: String, expected: Vec<String> },
+    UnexpectedEof { loc: Loc, expected: Vec<String> },
+    ExtraToken { loc: Loc, found: String },
+    Located { line: usize, col: usize, error: Box<CairoLexerError> },
 }
 
+// implemented by hand, because `thiserror` requires `std`
+impl fmt::Display for CairoLexerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CairoLexerError::EndOfFileInString(l, r) => write!(f, "EndOfFileInString {}:{}", l, r),
+            CairoLexerError::EndOfFileInHint(l, r) => write!(f, "EndOfFileInHint {}:{}", l, r),
+            CairoLexerError::EndofFileInHex(l, r) => write!(f, "EndofFileInHex {}:{}", l, r),
+            CairoLexerError::UnrecognisedToken(l, r, token) => {
+                write!(f, "UnrecognisedToken {}:{} `{}`", l, r, token)
+            }
+            CairoLexerError::MissingNumber(l, r) => write!(f, "MissingNumber {}:{}", l, r),
+            CairoLexerError::Unsupported(msg) => write!(f, "Unsupported {}", msg),
+            CairoLexerError::ParserError(l, r, msg) => {
+                write!(f, "ParserError {}:{} `{}`", l, r, msg)
+            }
+            CairoLexerError::InvalidToken { loc } => write!(f, "InvalidToken {}", loc),
+            CairoLexerError::UnexpectedToken { loc, found, expected } => {
+                write!(f, "UnexpectedToken {} `{}`, expected {}", loc, found, expected.join(", "))
+            }
+            CairoLexerError::UnexpectedEof { loc, expected } => {
+                write!(f, "UnexpectedEof {}, expected {}", loc, expected.join(", "))
+            }
+            CairoLexerError::ExtraToken { loc, found } => {
+                write!(f, "ExtraToken {} `{}`", loc, found)
+            }
+            CairoLexerError::Located { line, col, error } => {
+                write!(f, "line {}, col {}: {}", line, col, error)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CairoLexerError {}
+
 impl<'input> From<ParseError<usize, CairoToken<'input>, CairoLexerError>> for CairoLexerError {
     fn from(err: ParseError<usize, CairoToken<'input>, CairoLexerError>) -> Self {
         match err {
             ParseError::InvalidToken { location } => {
-                CairoLexerError::parser_error(Loc(location, location), "invalid token".to_string())
+                CairoLexerError::InvalidToken { loc: Loc(location, location) }
             }
             ParseError::UnrecognizedToken { token: (l, token, r), expected } => {
-                CairoLexerError::parser_error(
-                    Loc(l, r),
-                    format!("unrecognised token `{:?}', expected {}", token, expected.join(", ")),
-                )
+                CairoLexerError::UnexpectedToken {
+                    loc: Loc(l, r),
+                    found: format!("{:?}", token),
+                    expected,
+                }
             }
             ParseError::User { error } => error,
-            ParseError::ExtraToken { token } => CairoLexerError::parser_error(
-                Loc(token.0, token.2),
-                format!("extra token `{}' encountered", token.0),
-            ),
-            ParseError::UnrecognizedEOF { location, expected } => CairoLexerError::parser_error(
-                Loc(location, location),
-                format!("unexpected end of file, expecting {}", expected.join(", ")),
-            ),
+            ParseError::ExtraToken { token: (l, token, r) } => {
+                CairoLexerError::ExtraToken { loc: Loc(l, r), found: format!("{:?}", token) }
+            }
+            ParseError::UnrecognizedEOF { location, expected } => {
+                CairoLexerError::UnexpectedEof { loc: Loc(location, location), expected }
+            }
         }
     }
 }
@@ -54,9 +87,46 @@ impl CairoLexerError {
     pub fn parser_error(pos: Loc, message: String) -> Self {
         Self::ParserError(pos.0, pos.1, message)
     }
+
+    /// Returns the byte offset at which the error starts, if known
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            CairoLexerError::EndOfFileInString(start, _) |
+            CairoLexerError::EndOfFileInHint(start, _) |
+            CairoLexerError::EndofFileInHex(start, _) |
+            CairoLexerError::UnrecognisedToken(start, _, _) |
+            CairoLexerError::MissingNumber(start, _) |
+            CairoLexerError::ParserError(start, _, _) => Some(*start),
+            CairoLexerError::InvalidToken { loc } |
+            CairoLexerError::UnexpectedToken { loc, .. } |
+            CairoLexerError::UnexpectedEof { loc, .. } |
+            CairoLexerError::ExtraToken { loc, .. } => Some(loc.0),
+            CairoLexerError::Unsupported(_) => None,
+            CairoLexerError::Located { error, .. } => error.offset(),
+        }
+    }
+
+    /// Attaches the line and column of the error's offset in the source the `index` was built from
+    pub fn located(self, index: &LineIndex) -> Self {
+        match self.offset() {
+            Some(offset) if !matches!(self, CairoLexerError::Located { .. }) => {
+                let (line, col) = index.locate(offset);
+                CairoLexerError::Located { line, col, error: Box::new(self) }
+            }
+            _ => self,
+        }
+    }
+
+    /// Returns the error without any attached line information
+    pub fn inner(&self) -> &CairoLexerError {
+        match self {
+            CairoLexerError::Located { error, .. } => error.inner(),
+            err => err,
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub enum CairoToken<'input> {
     Identifier(&'input str),
     StringLiteral(&'input str),
@@ -130,14 +200,52 @@ pub enum CairoToken<'input> {
     Dw,
 }
 
-pub(crate) struct CairoLexer<'input> {
+/// Splits Cairo source code into tokens
+///
+/// Comments are skipped unless enabled via [CairoLexer::with_comments], all tokens are [Spanned]
+/// with the byte offsets of their first character and the position right after their last
+/// character in the input, so `&input[start..end]` is the token's source text.
+pub struct CairoLexer<'input> {
     input: &'input str,
     chars: Peekable<CharIndices<'input>>,
+    /// whether to emit `Comment` tokens
+    comments: bool,
+}
+
+/// Returns an iterator over all tokens of the input and their spans
+///
+/// # Example
+///
+/// ```
+/// use cairo_lang_rs::parser::lexer::{tokenize, CairoToken};
+/// let tokens = tokenize("let x = 1").collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(
+///     tokens,
+///     vec![
+///         (0, CairoToken::Let, 3),
+///         (4, CairoToken::Identifier("x"), 5),
+///         (6, CairoToken::Assign, 7),
+///         (8, CairoToken::Number("1"), 9),
+///     ]
+/// );
+/// ```
+pub fn tokenize(
+    input: &str,
+) -> impl Iterator<Item = Spanned<CairoToken<'_>, usize, CairoLexerError>> + '_ {
+    CairoLexer::new(input)
 }
 
 impl<'input> CairoLexer<'input> {
     pub fn new(input: &'input str) -> CairoLexer<'input> {
-        CairoLexer { chars: input.char_indices().peekable(), input }
+        CairoLexer { chars: input.char_indices().peekable(), input, comments: false }
+    }
+
+    /// Emits a `Comment` token with the text after the `#` for every comment
+    ///
+    /// The grammar does not accept comment tokens, so this is only useful for tooling.
+    pub fn with_comments(mut self) -> Self {
+        self.comments = true;
+        self
     }
 
     fn string(
@@ -201,7 +309,8 @@ impl<'input> CairoLexer<'input> {
             }
         }
 
-        Ok((token_start, CairoToken::Hint(&self.input[string_start..end]), end + 1))
+        // `end` is the offset of the closing `%}`, which is part of the token
+        Ok((token_start, CairoToken::Hint(&self.input[string_start..end]), end + 2))
     }
 
     fn keyword(id: &str) -> Option<CairoToken> {
@@ -268,26 +377,17 @@ impl<'input> CairoLexer<'input> {
                     }
                 }
                 Some((i, '#')) => {
-                    // ignore Comments for now
-                    let start = i + 1;
-                    let mut end = start;
-                    loop {
-                        match self.chars.peek() {
-                            Some((_, '\r')) | Some((_, '\n')) => break,
-                            None => return None,
-                            Some((idx, _)) => {
-                                end = *idx;
-                                self.chars.next();
-                            }
+                    let mut end = self.input.len();
+                    while let Some((idx, ch)) = self.chars.peek() {
+                        if *ch == '\r' || *ch == '\n' {
+                            end = *idx;
+                            break
                         }
+                        self.chars.next();
+                    }
+                    if self.comments {
+                        return Some(Ok((i, CairoToken::Comment(&self.input[i + 1..end]), end)))
                     }
-                    let _end = end;
-                    // TODO
-                    // return Some(Ok((
-                    //     i,
-                    //     CairoToken::Comment(&self.input[start..=end]),
-                    //     end +1,
-                    // )));
                 }
                 Some((i, '=')) => {
                     return match self.chars.peek() {
@@ -306,7 +406,7 @@ impl<'input> CairoLexer<'input> {
                         }
                         Some((_, 'b')) => {
                             if self.input[i..].starts_with("%builtins") {
-                                self.chars.nth(9);
+                                self.chars.nth(7);
                                 Some(Ok((i, CairoToken::Builtins, i + 9)))
                             } else {
                                 Some(Ok((i, CairoToken::Percent, i + 1)))
@@ -314,7 +414,7 @@ impl<'input> CairoLexer<'input> {
                         }
                         Some((_, 'l')) => {
                             if self.input[i..].starts_with("%lang") {
-                                self.chars.nth(5);
+                                self.chars.nth(3);
                                 Some(Ok((i, CairoToken::Lang, i + 5)))
                             } else {
                                 Some(Ok((i, CairoToken::Percent, i + 1)))
@@ -362,21 +462,33 @@ impl<'input> CairoLexer<'input> {
                         }
                     }
 
-                    loop {
-                        if let Some((i, ch)) = self.chars.peek().cloned() {
-                            if !ch.is_ascii_digit() {
-                                break
-                            }
-                            self.chars.next();
-                            end = i;
-                        } else {
-                            end = self.input.len();
+                    // `_` is allowed as digit separator, like in hex numbers
+                    while let Some((i, ch)) = self.chars.peek() {
+                        if !ch.is_ascii_digit() && *ch != '_' {
                             break
                         }
+                        end = *i + 1;
+                        self.chars.next();
+                    }
+                    let number = &self.input[start..end];
+                    if number.ends_with('_') {
+                        return Some(Err(CairoLexerError::UnrecognisedToken(
+                            start,
+                            end,
+                            number.to_owned(),
+                        )))
+                    }
+                    return Some(Ok((start, CairoToken::Number(number), end)))
+                }
+                Some((i, '\r')) => {
+                    // `\r\n` is a single line break
+                    if let Some((_, '\n')) = self.chars.peek() {
+                        self.chars.next();
+                        return Some(Ok((i, CairoToken::Newline, i + 2)))
                     }
-                    return Some(Ok((start, CairoToken::Number(&self.input[start..end]), end + 1)))
+                    return Some(Ok((i, CairoToken::Newline, i + 1)))
                 }
-                Some((i, '\r' | '\n')) => return Some(Ok((i, CairoToken::Newline, i + 1))),
+                Some((i, '\n')) => return Some(Ok((i, CairoToken::Newline, i + 1))),
                 Some((i, '(')) => return Some(Ok((i, CairoToken::OpenParenthesis, i + 1))),
                 Some((i, ')')) => return Some(Ok((i, CairoToken::CloseParenthesis, i + 1))),
                 Some((i, '{')) => return Some(Ok((i, CairoToken::OpenCurlyBrace, i + 1))),
@@ -1,17 +1,59 @@
 pub mod ast;
+pub mod format;
 pub mod lexer;
+pub mod strip_locs;
+#[cfg(feature = "std")]
+mod visit;
 
 pub use grammar::*;
 
+use crate::parser::{
+    ast::{CairoFile, Loc, Note},
+    lexer::{CairoLexer, CairoLexerError, CairoToken},
+};
+use alloc::{string::ToString, vec::Vec};
+
+/// Parses the input and returns all comments and runs of blank lines alongside the parsed file
+///
+/// The comments and blank lines are returned in order of their position, so they can be
+/// reassociated with the nodes of the file, for example to reprint the file losslessly. A run of
+/// blank lines spans all of its line breaks except the one that ends the preceding line.
+pub fn parse_with_trivia(input: &str) -> Result<(CairoFile, Vec<(Loc, Note)>), CairoLexerError> {
+    let file = CairoFile::parse(input)?;
+    let mut trivia = Vec::new();
+    let mut previous_newline = false;
+    for token in CairoLexer::new(input).with_comments() {
+        let (start, token, end) = token?;
+        match token {
+            CairoToken::Comment(comment) => {
+                let loc = Loc(start, end);
+                trivia.push((loc, Note::Comment(comment.trim().to_string(), loc)));
+            }
+            CairoToken::Newline if previous_newline => match trivia.last_mut() {
+                // extend the current run of blank lines
+                Some((loc, Note::NewLine(run))) if loc.1 == start => {
+                    loc.1 = end;
+                    run.1 = end;
+                }
+                _ => trivia.push((Loc(start, end), Note::NewLine(Loc(start, end)))),
+            },
+            _ => {}
+        }
+        previous_newline = token == CairoToken::Newline;
+    }
+    Ok((file, trivia))
+}
+
 mod grammar {
     #![allow(dead_code)]
     #![allow(clippy::all)]
     lalrpop_util::lalrpop_mod!(pub cairo_grammar, "/parser/cairo_grammar.rs");
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
 
+    use super::*;
     use crate::parser::{ast::*, lexer::*};
     use std::path::Path;
 
@@ -79,4 +121,772 @@ end
 "#;
         CairoFile::parse(s).unwrap();
     }
+
+    #[test]
+    fn can_locate_offsets() {
+        let s = "func foo():\n    ret\nend\n";
+        let index = LineIndex::new(s);
+        assert_eq!(index.locate(0), (1, 1));
+        assert_eq!(index.locate(5), (1, 6));
+        assert_eq!(index.locate(s.find("ret").unwrap()), (2, 5));
+        assert_eq!(index.locate(s.find("end").unwrap()), (3, 1));
+    }
+
+    #[test]
+    fn parse_error_reports_line_and_column() {
+        let s = r#"
+func foo():
+    ret $
+end
+"#;
+        let err = CairoFile::parse(s).unwrap_err();
+        assert!(matches!(err, CairoLexerError::Located { line: 3, col: 9, .. }), "{:?}", err);
+        assert!(err.to_string().starts_with("line 3, col 9: "), "{}", err);
+        assert!(matches!(err.inner(), CairoLexerError::UnrecognisedToken(..)));
+    }
+
+    #[test]
+    fn can_lex_directives() {
+        let tokens = tokenize("%builtins pedersen\n%lang starknet")
+            .into_iter()
+            .map(|t| t.unwrap().1)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                CairoToken::Builtins,
+                CairoToken::Identifier("pedersen"),
+                CairoToken::Newline,
+                CairoToken::Lang,
+                CairoToken::Identifier("starknet"),
+            ]
+        );
+    }
+
+    #[test]
+    fn can_lex_large_unterminated_literals() {
+        let body = "a".repeat(1 << 20);
+        let code = format!("tempvar x = '{}", body);
+        assert_eq!(
+            CairoLexer::new(&code).last(),
+            Some(Err(CairoLexerError::EndOfFileInString(12, code.len())))
+        );
+        // offsets are byte offsets, also for multibyte characters
+        let code = format!("\"{}", "é".repeat(1 << 19));
+        assert_eq!(
+            CairoLexer::new(&code).last(),
+            Some(Err(CairoLexerError::EndOfFileInString(0, code.len())))
+        );
+
+        let code = format!("ret\n%{{ {}", body);
+        assert_eq!(
+            CairoLexer::new(&code).last(),
+            Some(Err(CairoLexerError::EndOfFileInHint(4, code.len())))
+        );
+        // a trailing `%` doesn't close the hint
+        let code = format!("%{{ {}%", body);
+        assert_eq!(
+            CairoLexer::new(&code).last(),
+            Some(Err(CairoLexerError::EndOfFileInHint(0, code.len())))
+        );
+
+        // the span of a hint includes the closing `%}`
+        let code = format!("%{{{}%}}\nret", body);
+        let tokens = tokenize(&code);
+        assert_eq!(tokens[0], Ok((0, CairoToken::Hint(&body), body.len() + 4)));
+        assert_eq!(tokens[1], Ok((body.len() + 4, CairoToken::Newline, body.len() + 5)));
+    }
+
+    #[test]
+    fn can_lex_crlf_line_endings() {
+        let code = "with x:\n    # comment\n    ret\nend\n";
+        let crlf = code.replace('\n', "\r\n");
+        let lf_tokens = tokenize(code).into_iter().map(|t| t.unwrap().1).collect::<Vec<_>>();
+        let crlf_tokens = tokenize(&crlf).into_iter().map(|t| t.unwrap().1).collect::<Vec<_>>();
+        assert_eq!(lf_tokens, crlf_tokens);
+
+        assert_eq!(
+            tokenize("ret\r\nret\rret"),
+            vec![
+                Ok((0, CairoToken::Ret, 3)),
+                Ok((3, CairoToken::Newline, 5)),
+                Ok((5, CairoToken::Ret, 8)),
+                Ok((8, CairoToken::Newline, 9)),
+                Ok((9, CairoToken::Ret, 12)),
+            ]
+        );
+        assert_eq!(CairoFile::parse(&crlf).unwrap(), CairoFile::parse(code).unwrap());
+    }
+
+    #[test]
+    fn can_lex_decimal_numbers() {
+        assert_eq!(tokenize("12 "), vec![Ok((0, CairoToken::Number("12"), 2))]);
+        assert_eq!(tokenize("1_000"), vec![Ok((0, CairoToken::Number("1_000"), 5))]);
+        assert_eq!(
+            tokenize("1_000+2"),
+            vec![
+                Ok((0, CairoToken::Number("1_000"), 5)),
+                Ok((5, CairoToken::Add, 6)),
+                Ok((6, CairoToken::Number("2"), 7))
+            ]
+        );
+        // a leading underscore makes it an identifier
+        assert_eq!(tokenize("_1000"), vec![Ok((0, CairoToken::Identifier("_1000"), 5))]);
+        assert_eq!(
+            tokenize("1000_"),
+            vec![Err(CairoLexerError::UnrecognisedToken(0, 5, "1000_".to_string()))]
+        );
+    }
+
+    #[test]
+    fn can_parse_underscore_separated_numbers() {
+        let file = CairoFile::parse("const X = 1_000_000\n").unwrap();
+        match &file.0[0] {
+            Instruction::Const(c) => assert_eq!(c.init.as_u256(), Some(1_000_000.into())),
+            ins => panic!("unexpected instruction {:?}", ins),
+        }
+        let printed = file.to_string();
+        assert_eq!(printed, "const X = 1000000\n");
+        assert_eq!(CairoFile::parse(&printed).unwrap(), file);
+    }
+
+    #[test]
+    fn can_parse_types() {
+        assert_eq!(CairoType::parse("felt").unwrap(), CairoType::Felt);
+        assert_eq!(CairoType::parse("felt*").unwrap().pointer_depth(), 1);
+        let ty = CairoType::parse("felt**").unwrap();
+        assert_eq!(ty.pointer_depth(), 2);
+        assert_eq!(ty.to_string(), "felt**");
+        assert_eq!(
+            CairoType::parse("(felt, felt)").unwrap(),
+            CairoType::Tuple(vec![CairoType::Felt, CairoType::Felt])
+        );
+
+        match CairoType::parse("a.b.C").unwrap() {
+            CairoType::Id(ty) => {
+                assert_eq!(ty.name, ["a", "b", "C"]);
+                assert!(!ty.is_fully_resolved);
+                assert_eq!(ty.resolved_scope(), None);
+            }
+            ty => panic!("unexpected type {:?}", ty),
+        }
+        match CairoType::parse("(felt, MyStruct*)").unwrap() {
+            CairoType::Tuple(tys) => assert_eq!(tys[1].to_string(), "MyStruct*"),
+            ty => panic!("unexpected type {:?}", ty),
+        }
+
+        assert!(CairoType::parse("felt +").is_err());
+        assert!(CairoType::parse("").is_err());
+    }
+
+    #[test]
+    fn can_parse_typed_constants() {
+        let constant = |code: &str| match Instruction::parse(code).unwrap() {
+            Instruction::Const(c) => c,
+            ins => panic!("unexpected instruction {:?}", ins),
+        };
+        let c = constant("const X = 1");
+        assert_eq!(c.ty, None);
+        assert_eq!(c.to_string(), "const X = 1");
+
+        let c = constant("const X : felt = 1");
+        assert_eq!(c.ty, Some(CairoType::Felt));
+        assert_eq!(c.init.as_u256(), Some(1.into()));
+        assert_eq!(c.to_string(), "const X : felt = 1");
+
+        let c = constant("const X : MyStruct = 1");
+        assert_eq!(c.ty.unwrap().to_string(), "MyStruct");
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn can_serde_roundtrip_ast() {
+        let content = std::fs::read_to_string(
+            Path::new(&env!("CARGO_MANIFEST_DIR")).join("test-data/cairo-files/amm.cairo"),
+        )
+        .unwrap();
+        let file = CairoFile::parse(&content).unwrap();
+        let json = serde_json::to_string(&file).unwrap();
+        let de: CairoFile = serde_json::from_str(&json).unwrap();
+        assert_eq!(de, file);
+        // `Loc`s always compare equal in tests
+        assert_eq!(serde_json::to_string(&de).unwrap(), json);
+    }
+
+    #[test]
+    fn can_visit_nested_exprs() {
+        use crate::compiler::{VResult, Visitable, Visitor};
+
+        #[derive(Default)]
+        struct IntCounter(usize);
+
+        impl Visitor for IntCounter {
+            fn visit_expr(&mut self, expr: &mut Expr) -> VResult {
+                if let Expr::Int(_, _) = expr {
+                    self.0 += 1;
+                }
+                Ok(())
+            }
+        }
+
+        let mut file = CairoFile::parse("const X = (1 + 2) * 3\n").unwrap();
+        let mut counter = IntCounter::default();
+        file.visit(&mut counter).unwrap();
+        assert_eq!(counter.0, 3);
+
+        let mut file = CairoFile::parse("assert [ap] = foo(1, x=(2 + 3) * 4)\n").unwrap();
+        let mut counter = IntCounter::default();
+        file.visit(&mut counter).unwrap();
+        assert_eq!(counter.0, 4);
+    }
+
+    #[test]
+    fn can_pretty_print_nested_blocks() {
+        let code = r#"
+namespace a:
+    struct S:
+        member x : felt
+    end
+    namespace b:
+        with_attr error_message("oops"):
+            %{
+                x = 1
+            %}
+            assert [ap] = 1
+        end
+    end
+end
+"#;
+        let file = CairoFile::parse(code).unwrap();
+        let printed = file.to_string();
+        assert!(printed.contains("\n        member x : felt\n"), "{}", printed);
+        assert!(printed.contains("\n            assert [ap] = 1\n"), "{}", printed);
+        let reparsed = CairoFile::parse(&printed).unwrap();
+        assert_eq!(reparsed, file);
+        assert_eq!(reparsed.to_string(), printed);
+    }
+
+    #[test]
+    fn can_print_function_def() {
+        let file = CairoFile::parse("func foo(a, b) -> (c):\n    ret\nend\n").unwrap();
+        let printed = file.to_string();
+        assert_eq!(printed, "func foo(a, b) -> (c):\n    ret\nend\n");
+        assert_eq!(CairoFile::parse(&printed).unwrap(), file);
+
+        let code = r#"
+@external
+func bar{syscall_ptr : felt*}(x : felt, y : felt*) -> (res : felt):
+    return (res=x)
+end
+"#;
+        let file = CairoFile::parse(code).unwrap();
+        let printed = file.to_string();
+        assert!(
+            printed.contains("func bar{syscall_ptr : felt*}(x : felt, y : felt*) -> (res : felt):")
+        );
+        assert_eq!(CairoFile::parse(&printed).unwrap(), file);
+    }
+
+    #[test]
+    fn can_roundtrip_hints() {
+        let tokens =
+            tokenize("nondet %{ ids.a %}").into_iter().map(|t| t.unwrap().1).collect::<Vec<_>>();
+        assert_eq!(tokens, vec![CairoToken::Nondet, CairoToken::Hint(" ids.a ")]);
+
+        let code = "tempvar x = nondet %{ ids.a %}\n%{ memory[ap] = 1 %}\n";
+        let file = CairoFile::parse(code).unwrap();
+        match &file.0[0] {
+            Instruction::Tempvar(_, Some(Expr::Hint(hint, _)), _) => assert_eq!(hint, " ids.a "),
+            ins => panic!("unexpected instruction {:?}", ins),
+        }
+        let printed = file.to_string();
+        assert_eq!(printed, code);
+        assert_eq!(CairoFile::parse(&printed).unwrap(), file);
+        assert_eq!(CairoFile::parse(&printed).unwrap().to_string(), printed);
+    }
+
+    #[test]
+    fn parse_error_reports_expected_tokens() {
+        let err = CairoFile::parse("func foo(").unwrap_err();
+        match err.inner() {
+            CairoLexerError::UnexpectedEof { loc, expected } => {
+                assert_eq!(loc.0, 9);
+                assert!(!expected.is_empty());
+                assert!(expected.iter().any(|e| e == "\")\""), "{:?}", expected);
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+
+        let err = CairoFile::parse("func foo(]\n").unwrap_err();
+        match err.inner() {
+            CairoLexerError::UnexpectedToken { found, expected, .. } => {
+                assert_eq!(found, "CloseBracket");
+                assert!(!expected.is_empty());
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn can_walk_without_mutable_access() {
+        use crate::compiler::{RefVisitor, VResult, Walk};
+
+        #[derive(Default)]
+        struct FunctionCounter(usize);
+
+        impl RefVisitor for FunctionCounter {
+            fn enter_function(&mut self, _: &FunctionDef) -> VResult {
+                self.0 += 1;
+                Ok(())
+            }
+        }
+
+        let file = CairoFile::parse(
+            r#"
+func foo():
+    ret
+end
+namespace bar:
+    func baz():
+        func qux():
+            ret
+        end
+        ret
+    end
+end
+"#,
+        )
+        .unwrap();
+        let shared = &file;
+        let mut counter = FunctionCounter::default();
+        shared.walk(&mut counter).unwrap();
+        assert_eq!(counter.0, 3);
+    }
+
+    #[test]
+    fn can_parse_multiline_imports() {
+        for code in [
+            "from a import (\n    b,\n    c,\n)\n",
+            "from a import (\n    b,\n    c\n)\n",
+            "from a import (b, c as d)\n",
+            "from a import (\n    b as x, # comment\n    c\n)\n",
+        ] {
+            let file = CairoFile::parse(code).expect(code);
+            let import = match &file.0[0] {
+                Instruction::Import(import) => import,
+                ins => panic!("unexpected instruction {:?}", ins),
+            };
+            assert!(matches!(import.functions, FunctionImport::Parantheses(..)));
+            assert_eq!(import.aliased_identifier().len(), 2);
+            assert_eq!(CairoFile::parse(&file.to_string()).unwrap(), file);
+        }
+    }
+
+    #[test]
+    fn can_distinguish_import_source_and_alias() {
+        let file = CairoFile::parse("from a import x, y as z\n").unwrap();
+        let import = match &file.0[0] {
+            Instruction::Import(import) => import,
+            ins => panic!("unexpected instruction {:?}", ins),
+        };
+        let items = import.aliased_identifier();
+        assert_eq!((items[0].source(), items[0].identifier()), ("x", "x"));
+        assert_eq!((items[1].source(), items[1].identifier()), ("y", "z"));
+    }
+
+    #[test]
+    fn can_parse_decorators_on_their_own_line() {
+        let decorators = |code: &str| {
+            let file = CairoFile::parse(code).expect(code);
+            let f = file.functions().next().expect(code).clone();
+            assert_eq!(CairoFile::parse(&file.to_string()).unwrap(), file);
+            f.decorators
+        };
+        let expected = vec![Decorator::View, Decorator::Other("known_ap_change".to_string())];
+        for code in [
+            "@view @known_ap_change func foo():\n    ret\nend\n",
+            "@view\n@known_ap_change\nfunc foo():\n    ret\nend\n",
+            "@view\n\n@known_ap_change\n\n\nfunc foo():\n    ret\nend\n",
+            "@view # entry point\n# more comments\n@known_ap_change\n# here\nfunc foo():\n    ret\nend\n",
+        ] {
+            assert_eq!(decorators(code), expected, "{}", code);
+        }
+    }
+
+    #[test]
+    fn can_parse_with_trivia() {
+        let code = "# header\nconst A = 1 # one\n\n\n# about foo\nfunc foo():\n    # inside\n    ret\nend\n";
+        let (file, trivia) = parse_with_trivia(code).unwrap();
+        assert_eq!(file, CairoFile::parse(code).unwrap());
+
+        let comments = trivia
+            .iter()
+            .filter_map(|(loc, note)| match note {
+                Note::Comment(comment, _) => Some((comment.as_str(), loc.0, loc.1)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let offsets = |s: &str| (code.find(s).unwrap(), code.find(s).unwrap() + s.len());
+        assert_eq!(
+            comments,
+            ["# header", "# one", "# about foo", "# inside"]
+                .iter()
+                .map(|s| (&s[2..], offsets(s).0, offsets(s).1))
+                .collect::<Vec<_>>()
+        );
+
+        let blank_lines = trivia
+            .iter()
+            .filter(|(_, note)| matches!(note, Note::NewLine(_)))
+            .map(|(loc, _)| (loc.0, loc.1))
+            .collect::<Vec<_>>();
+        let start = code.find("\n\n").unwrap() + 1;
+        assert_eq!(blank_lines, [(start, start + 2)]);
+    }
+
+    /// Prints the tree shape of binary and unary operations, `a + b * c` is `(a + (b * c))`
+    fn shape(expr: &Expr) -> String {
+        match expr {
+            Expr::Add(lhs, rhs, _) => format!("({} + {})", shape(lhs), shape(rhs)),
+            Expr::Sub(lhs, rhs, _) => format!("({} - {})", shape(lhs), shape(rhs)),
+            Expr::Mul(lhs, rhs, _) => format!("({} * {})", shape(lhs), shape(rhs)),
+            Expr::Div(lhs, rhs, _) => format!("({} / {})", shape(lhs), shape(rhs)),
+            Expr::Pow(lhs, rhs, _) => format!("({} ** {})", shape(lhs), shape(rhs)),
+            Expr::Neg(expr, _) => format!("(-{})", shape(expr)),
+            Expr::Parentheses(exprs, _) if exprs.len() == 1 => shape(exprs[0].expr()),
+            expr => expr.to_string(),
+        }
+    }
+
+    #[test]
+    fn can_parse_operator_precedence() {
+        for (code, expected) in [
+            ("a + b * c", "(a + (b * c))"),
+            ("a * b + c", "((a * b) + c)"),
+            ("(a + b) * c", "((a + b) * c)"),
+            ("a - b - c", "((a - b) - c)"),
+            ("a / b / c", "((a / b) / c)"),
+            ("a ** b ** c", "(a ** (b ** c))"),
+            ("-a ** b", "(-(a ** b))"),
+            ("-a * b", "((-a) * b)"),
+            ("a - -b", "(a - (-b))"),
+            ("2 ** -1", "(2 ** (-1))"),
+            ("-2 ** 2", "(-(2 ** 2))"),
+            ("a ** -b ** c", "(a ** (-(b ** c)))"),
+            ("a ** -b * c", "((a ** (-b)) * c)"),
+            ("+5", "5"),
+            ("a + +b", "(a + b)"),
+            ("-+a", "(-a)"),
+        ] {
+            let expr = Expr::parse(code).unwrap();
+            assert_eq!(shape(&expr), expected, "{}", code);
+            // printing preserves the precedence
+            assert_eq!(shape(&Expr::parse(&expr.to_string()).unwrap()), expected, "{}", code);
+        }
+    }
+
+    #[test]
+    fn can_display_operator_precedence() {
+        let id = |name: &str| Box::new(Expr::Id(vec![name.to_string()], Loc::default()));
+        let loc = Loc::default();
+        for (expr, expected) in [
+            (Expr::Mul(Box::new(Expr::Add(id("a"), id("b"), loc)), id("c"), loc), "(a + b) * c"),
+            (Expr::Sub(id("a"), Box::new(Expr::Sub(id("b"), id("c"), loc)), loc), "a - (b - c)"),
+            (Expr::Pow(Box::new(Expr::Pow(id("a"), id("b"), loc)), id("c"), loc), "(a**b)**c"),
+            (Expr::Pow(id("a"), Box::new(Expr::Pow(id("b"), id("c"), loc)), loc), "a**b**c"),
+            (Expr::Neg(Box::new(Expr::Add(id("a"), id("b"), loc)), loc), "-(a + b)"),
+            (Expr::Add(id("a"), Box::new(Expr::Mul(id("b"), id("c"), loc)), loc), "a + b * c"),
+        ] {
+            let displayed = expr.to_string();
+            assert_eq!(displayed, expected);
+            assert_eq!(shape(&Expr::parse(&displayed).unwrap()), shape(&expr));
+        }
+    }
+
+    #[test]
+    fn can_classify_expressions() {
+        let expr = |s: &str| Expr::parse(s).unwrap();
+        assert!(expr("1 + 2").is_const());
+        assert!(!expr("1 + 2").contains_register());
+        assert!(expr("-(0x10 * 'a') ** 2").is_const());
+
+        assert!(!expr("[ap] + 1").is_const());
+        assert!(expr("[ap] + 1").contains_register());
+        assert!(expr("foo(x=[fp - 3])").contains_register());
+
+        assert!(!expr("foo + 1").is_const());
+        assert!(!expr("foo + 1").contains_register());
+        assert!(!expr("bar(1)").is_const());
+        assert!(!expr("(1, 2)").is_const());
+    }
+
+    #[test]
+    fn can_compute_short_string_values() {
+        let value = |s: &str| Expr::parse(s).unwrap().short_string_value();
+        assert_eq!(value("'a'").unwrap(), 0x61.into());
+        assert_eq!(value("'hello'").unwrap(), 0x68656c6c6f_u64.into());
+        assert_eq!(value(r"'a\nb'").unwrap(), 0x610a62.into());
+        assert_eq!(value(r"'\t\\\''").unwrap(), 0x095c27.into());
+        assert_eq!(value(r"'\x41B'").unwrap(), 0x4142.into());
+        assert_eq!(value("''").unwrap(), 0.into());
+
+        let max = "a".repeat(31);
+        assert!(value(&format!("'{}'", max)).is_ok());
+        let err = value(&format!("'{}a'", max)).unwrap_err();
+        assert!(err.to_string().contains("exceeds 31 characters"), "{}", err);
+        assert!(err.span().is_some());
+        // escapes count as a single byte
+        assert!(value(&format!(r"'{}\n'", &max[1..])).is_ok());
+
+        assert!(value(r"'\x4'").is_err());
+        assert!(value(r"'\q'").is_err());
+        assert!(value("5").is_err());
+    }
+
+    #[test]
+    fn can_parse_return_statements() {
+        let returned = |code: &str| match Instruction::parse(code).unwrap() {
+            Instruction::Return(exprs, _) => exprs
+                .iter()
+                .map(|expr| match expr {
+                    ExprAssignment::Expr(expr, _) => expr.to_string(),
+                    ExprAssignment::Id(name, expr, _) => format!("{}={}", name, expr),
+                })
+                .collect::<Vec<_>>(),
+            ins => panic!("unexpected instruction {:?}", ins),
+        };
+        assert!(returned("return ()").is_empty());
+        assert_eq!(returned("return (1)"), ["1"]);
+        assert_eq!(returned("return (x)"), ["x"]);
+        assert_eq!(returned("return (a=1)"), ["a=1"]);
+        assert_eq!(returned("return (a=1, b=2)"), ["a=1", "b=2"]);
+        assert_eq!(returned("return (1, b=2)"), ["1", "b=2"]);
+        // the parentheses belong to the statement, not to the value
+        assert_eq!(returned("return ((1))"), ["(1)"]);
+
+        match Instruction::parse("return foo(1, b=2)").unwrap() {
+            Instruction::ReturnFunctionCall(call, _) => {
+                assert_eq!(call.id, ["foo"]);
+                assert_eq!(call.args.len(), 2);
+            }
+            ins => panic!("unexpected instruction {:?}", ins),
+        }
+    }
+
+    #[test]
+    fn can_get_builtin_size() {
+        let felt_ptr = CairoType::Pointer(Box::new(PointerType::Single(CairoType::Felt)));
+        assert_eq!(felt_ptr.builtin_size(), Some(1));
+        assert_eq!(
+            CairoType::Tuple(vec![CairoType::Felt, CairoType::Felt]).builtin_size(),
+            Some(2)
+        );
+        assert_eq!(CairoType::Tuple(vec![CairoType::Felt, felt_ptr]).builtin_size(), Some(2));
+        assert_eq!(CairoType::Tuple(vec![]).builtin_size(), Some(0));
+
+        let named = CairoType::Id(TypeStruct {
+            name: vec!["Uint256".to_string()],
+            is_fully_resolved: false,
+            loc: Loc(0, 0),
+        });
+        assert_eq!(named.builtin_size(), None);
+        assert_eq!(CairoType::Tuple(vec![CairoType::Felt, named]).builtin_size(), None);
+    }
+
+    #[test]
+    fn can_render_function_signatures() {
+        let code = r#"
+@view
+func foo{z : felt}(a : felt, b : MyStruct) -> (c : felt):
+    let c = a + b.x
+    return (c=c)
+end
+func bar():
+    ret
+end
+"#;
+        let file = CairoFile::parse(code).unwrap();
+        let mut functions = file.functions();
+        assert_eq!(
+            functions.next().unwrap().signature_string(),
+            "@view foo{z : felt}(a : felt, b : MyStruct) -> (c : felt)"
+        );
+        assert_eq!(functions.next().unwrap().signature_string(), "bar()");
+    }
+
+    #[test]
+    fn can_convert_builtins() {
+        let builtin: Builtin = "bitwise".into();
+        assert_eq!(builtin, Builtin::Bitwise);
+        assert!(builtin.is_bitwise());
+        for name in crate::compiler::constants::SUPPORTED_BUILTINS {
+            let builtin = Builtin::from(*name);
+            assert!(!builtin.is_other(), "{}", name);
+            assert_eq!(builtin.to_string(), *name);
+        }
+        assert_eq!(Builtin::from("custom"), Builtin::Other("custom".to_string()));
+        assert_eq!(Builtin::from("custom").to_string(), "custom");
+    }
+
+    #[test]
+    fn can_collect_doc_comments() {
+        let code = r#"
+# Adds two numbers.
+# Returns the sum.
+@view
+func foo(a, b) -> (c):
+    ret
+end
+
+# not attached
+
+struct S:
+    member x : felt
+end
+
+namespace bar:
+    # The answer.
+    const X = 42
+end
+"#;
+        let file = CairoFile::parse(code).unwrap();
+        let doc = |instruction: &Instruction| match instruction {
+            Instruction::Function(f) => f.doc.clone(),
+            Instruction::Struct(s) => s.doc.clone(),
+            Instruction::Namespace(n) => match &n.instructions[0] {
+                Instruction::Const(c) => c.doc.clone(),
+                ins => panic!("unexpected instruction {:?}", ins),
+            },
+            ins => panic!("unexpected instruction {:?}", ins),
+        };
+        let comments = |notes: Vec<Note>| {
+            notes
+                .into_iter()
+                .map(|note| match note {
+                    Note::Comment(s, _) => s,
+                    note => panic!("unexpected note {:?}", note),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(comments(doc(&file.0[0])), vec!["Adds two numbers.", "Returns the sum."]);
+        assert!(doc(&file.0[1]).is_empty());
+        assert_eq!(comments(doc(&file.0[2])), vec!["The answer."]);
+
+        match &file.0[0] {
+            Instruction::Function(f) => {
+                let loc = match &f.doc[0] {
+                    Note::Comment(_, loc) => *loc,
+                    _ => unreachable!(),
+                };
+                assert_eq!(&code[loc.0..loc.1], "# Adds two numbers.");
+            }
+            _ => unreachable!(),
+        }
+
+        let printed = file.to_string();
+        assert!(printed.contains("# Adds two numbers.\n# Returns the sum.\n@view\nfunc foo"));
+        assert!(printed.contains("\n    # The answer.\n    const X = 42\n"), "{}", printed);
+        let reparsed = CairoFile::parse(&printed).unwrap();
+        assert_eq!(reparsed, file);
+        assert_eq!(reparsed.to_string(), printed);
+    }
+
+    #[test]
+    fn can_parse_large_numbers() {
+        let prime = "3618502788666131213697322783095070105623107215331596699973092056135872020481";
+        assert_eq!(prime.len(), 76);
+        let file = CairoFile::parse(&format!("const P = {}\n", prime)).unwrap();
+        match &file.0[0] {
+            Instruction::Const(c) => {
+                assert_eq!(c.init.as_u256(), Some(crate::compiler::constants::DEFAULT_PRIME))
+            }
+            ins => panic!("unexpected instruction {:?}", ins),
+        }
+        assert_eq!(file.to_string(), format!("const P = {}\n", prime));
+
+        let too_large = format!("const P = 1{}\n", "0".repeat(78));
+        let err = CairoFile::parse(&too_large).unwrap_err();
+        assert!(err.to_string().contains("exceeds 256 bits"), "{}", err);
+
+        CairoFile::parse("jmp rel 1 if [ap] != 1_000\n").unwrap();
+        let too_large = format!("jmp rel 1 if [ap] != 1{}\n", "0".repeat(39));
+        let err = CairoFile::parse(&too_large).unwrap_err();
+        assert!(err.to_string().contains("exceeds 128 bits"), "{}", err);
+    }
+
+    #[test]
+    fn can_parse_expr() {
+        let input = "1 + 2 * 3";
+        match Expr::parse(input).unwrap() {
+            Expr::Add(lhs, rhs, loc) => {
+                assert!(matches!(*lhs, Expr::Int(n, Loc(0, 1)) if n == 1.into()), "{:?}", lhs);
+                assert!(matches!(*rhs, Expr::Mul(_, _, Loc(4, 9))), "{:?}", rhs);
+                assert_eq!((loc.0, loc.1), (0, input.len()));
+            }
+            expr => panic!("unexpected expr {:?}", expr),
+        }
+
+        let input = "cast([ap], felt*)";
+        match Expr::parse(input).unwrap() {
+            Expr::Cast(expr, ty, loc) => {
+                assert!(matches!(*expr, Expr::Deref(_, Loc(5, 9))), "{:?}", expr);
+                assert!(ty.is_pointer());
+                assert_eq!((loc.0, loc.1), (0, input.len()));
+            }
+            expr => panic!("unexpected expr {:?}", expr),
+        }
+
+        let input = "foo.bar(1, x=2)";
+        match Expr::parse(input).unwrap() {
+            Expr::FunctionCall(call) => {
+                assert_eq!(call.id, vec!["foo".to_string(), "bar".to_string()]);
+                assert_eq!(call.args.len(), 2);
+                assert_eq!((call.loc.0, call.loc.1), (0, input.len()));
+            }
+            expr => panic!("unexpected expr {:?}", expr),
+        }
+
+        assert!(Expr::parse("1 +").is_err());
+    }
+
+    #[test]
+    fn can_iter_top_level_items() {
+        let file = CairoFile::parse(
+            r#"
+from a import b
+from c import (d, e)
+
+struct S:
+    member x : felt
+end
+
+func foo():
+    ret
+end
+
+namespace bar:
+    func baz():
+        ret
+    end
+end
+
+func qux():
+    ret
+end
+"#,
+        )
+        .unwrap();
+        assert_eq!(file.imports().count(), 2);
+        assert_eq!(file.structs().map(|s| s.name.as_str()).collect::<Vec<_>>(), ["S"]);
+        assert_eq!(file.functions().map(|f| f.name.as_str()).collect::<Vec<_>>(), ["foo", "qux"]);
+
+        let mut count = 0;
+        for ins in &file {
+            assert!(!matches!(ins, Instruction::Ret(_)));
+            count += 1;
+        }
+        assert_eq!(count, 6);
+        let namespaces = file.into_iter().filter(|ins| matches!(ins, Instruction::Namespace(_)));
+        assert_eq!(namespaces.count(), 1);
+    }
 }
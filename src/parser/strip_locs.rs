@@ -0,0 +1,435 @@
+//! Location insensitive comparison of AST nodes
+
+use crate::parser::ast::*;
+use alloc::{boxed::Box, vec::Vec};
+
+/// Resets all `Loc`s of a node and its children to `Loc::default()`
+///
+/// The derived `PartialEq` of the AST includes locations, so two trees parsed from differently
+/// formatted code only compare equal once their locations are stripped.
+pub trait StripLocs {
+    fn strip_locs(&mut self);
+
+    /// Returns the node with all locations stripped
+    fn without_locs(mut self) -> Self
+    where
+        Self: Sized,
+    {
+        self.strip_locs();
+        self
+    }
+}
+
+impl CairoFile {
+    /// Whether both files have the same structure, ignoring the locations of their nodes
+    pub fn structural_eq(&self, other: &Self) -> bool {
+        self.clone().without_locs() == other.clone().without_locs()
+    }
+}
+
+impl Instruction {
+    /// Whether both instructions have the same structure, ignoring the locations of their nodes
+    pub fn structural_eq(&self, other: &Self) -> bool {
+        self.clone().without_locs() == other.clone().without_locs()
+    }
+}
+
+impl Expr {
+    /// Whether both expressions have the same structure, ignoring the locations of their nodes
+    pub fn structural_eq(&self, other: &Self) -> bool {
+        self.clone().without_locs() == other.clone().without_locs()
+    }
+}
+
+impl StripLocs for Loc {
+    fn strip_locs(&mut self) {
+        *self = Loc::default();
+    }
+}
+
+impl<T: StripLocs> StripLocs for Vec<T> {
+    fn strip_locs(&mut self) {
+        self.iter_mut().for_each(StripLocs::strip_locs);
+    }
+}
+
+impl<T: StripLocs> StripLocs for Option<T> {
+    fn strip_locs(&mut self) {
+        if let Some(t) = self {
+            t.strip_locs();
+        }
+    }
+}
+
+impl<T: StripLocs> StripLocs for Box<T> {
+    fn strip_locs(&mut self) {
+        self.as_mut().strip_locs();
+    }
+}
+
+impl StripLocs for CairoFile {
+    fn strip_locs(&mut self) {
+        self.0.strip_locs();
+    }
+}
+
+impl StripLocs for Instruction {
+    fn strip_locs(&mut self) {
+        match self {
+            Instruction::Const(c) => c.strip_locs(),
+            Instruction::Member(id, loc) => {
+                id.strip_locs();
+                loc.strip_locs();
+            }
+            Instruction::Let(binding, rvalue, loc) => {
+                binding.strip_locs();
+                rvalue.strip_locs();
+                loc.strip_locs();
+            }
+            Instruction::Local(id, expr, loc) | Instruction::Tempvar(id, expr, loc) => {
+                id.strip_locs();
+                expr.strip_locs();
+                loc.strip_locs();
+            }
+            Instruction::Assert(lhs, rhs, loc) |
+            Instruction::StaticAssert(lhs, rhs, loc) |
+            Instruction::Assign(lhs, rhs, loc) => {
+                lhs.strip_locs();
+                rhs.strip_locs();
+                loc.strip_locs();
+            }
+            Instruction::Return(exprs, loc) => {
+                exprs.strip_locs();
+                loc.strip_locs();
+            }
+            Instruction::ReturnFunctionCall(call, loc) => {
+                call.strip_locs();
+                loc.strip_locs();
+            }
+            Instruction::If(stmt) => stmt.strip_locs(),
+            Instruction::Function(f) => f.strip_locs(),
+            Instruction::FunctionCall(call) => call.strip_locs(),
+            Instruction::Struct(s) => s.strip_locs(),
+            Instruction::Namespace(ns) => ns.strip_locs(),
+            Instruction::WithAttrStatement(w) => w.strip_locs(),
+            Instruction::WithStatement(w) => w.strip_locs(),
+            Instruction::Directive(d) => d.strip_locs(),
+            Instruction::Import(import) => import.strip_locs(),
+            Instruction::Label(_, loc) |
+            Instruction::Hint(_, loc) |
+            Instruction::AllocLocals(loc) |
+            Instruction::Ret(loc) => loc.strip_locs(),
+            Instruction::Jmp(jmp, loc) => {
+                jmp.strip_locs();
+                loc.strip_locs();
+            }
+            Instruction::CallInstruction(call, loc) => {
+                call.strip_locs();
+                loc.strip_locs();
+            }
+            Instruction::ApAddAssign(expr, loc) | Instruction::DataWord(expr, loc) => {
+                expr.strip_locs();
+                loc.strip_locs();
+            }
+            Instruction::ApAdd(ins, loc) => {
+                ins.strip_locs();
+                loc.strip_locs();
+            }
+        }
+    }
+}
+
+impl StripLocs for Expr {
+    fn strip_locs(&mut self) {
+        match self {
+            Expr::Int(_, loc) |
+            Expr::HexInt(_, loc) |
+            Expr::ShortString(_, loc) |
+            Expr::Hint(_, loc) |
+            Expr::Register(_, loc) |
+            Expr::Id(_, loc) => loc.strip_locs(),
+            Expr::FunctionCall(call) => call.strip_locs(),
+            Expr::Deref(expr, loc) |
+            Expr::Dot(expr, _, loc) |
+            Expr::Address(expr, loc) |
+            Expr::Neg(expr, loc) => {
+                expr.strip_locs();
+                loc.strip_locs();
+            }
+            Expr::Cast(expr, ty, loc) => {
+                expr.strip_locs();
+                ty.strip_locs();
+                loc.strip_locs();
+            }
+            Expr::Parentheses(exprs, loc) => {
+                exprs.strip_locs();
+                loc.strip_locs();
+            }
+            Expr::Subscript(lhs, rhs, loc) |
+            Expr::Pow(lhs, rhs, loc) |
+            Expr::Mul(lhs, rhs, loc) |
+            Expr::Div(lhs, rhs, loc) |
+            Expr::Add(lhs, rhs, loc) |
+            Expr::Sub(lhs, rhs, loc) => {
+                lhs.strip_locs();
+                rhs.strip_locs();
+                loc.strip_locs();
+            }
+        }
+    }
+}
+
+impl StripLocs for ExprAssignment {
+    fn strip_locs(&mut self) {
+        match self {
+            ExprAssignment::Expr(expr, loc) | ExprAssignment::Id(_, expr, loc) => {
+                expr.strip_locs();
+                loc.strip_locs();
+            }
+        }
+    }
+}
+
+impl StripLocs for BoolExpr {
+    fn strip_locs(&mut self) {
+        match self {
+            BoolExpr::Equal(lhs, rhs) | BoolExpr::NotEqual(lhs, rhs) => {
+                lhs.strip_locs();
+                rhs.strip_locs();
+            }
+        }
+    }
+}
+
+impl StripLocs for CairoType {
+    fn strip_locs(&mut self) {
+        match self {
+            CairoType::Felt => {}
+            CairoType::Id(ty) => ty.loc.strip_locs(),
+            CairoType::Tuple(tys) => tys.strip_locs(),
+            CairoType::Pointer(ptr) => match ptr.as_mut() {
+                PointerType::Single(ty) | PointerType::Double(ty) => ty.strip_locs(),
+            },
+        }
+    }
+}
+
+impl StripLocs for TypedIdentifier {
+    fn strip_locs(&mut self) {
+        self.ty.strip_locs();
+        self.loc.strip_locs();
+    }
+}
+
+impl StripLocs for RefBinding {
+    fn strip_locs(&mut self) {
+        match self {
+            RefBinding::Id(id) => id.strip_locs(),
+            RefBinding::List(ids) => ids.strip_locs(),
+        }
+    }
+}
+
+impl StripLocs for RValue {
+    fn strip_locs(&mut self) {
+        match self {
+            RValue::Call(call) => call.strip_locs(),
+            RValue::Expr(expr) => expr.strip_locs(),
+        }
+    }
+}
+
+impl StripLocs for Call {
+    fn strip_locs(&mut self) {
+        match self {
+            Call::Rel(expr) | Call::Abs(expr) => expr.strip_locs(),
+            Call::Id(_) => {}
+        }
+    }
+}
+
+impl StripLocs for Jmp {
+    fn strip_locs(&mut self) {
+        match self {
+            Jmp::Rel(expr) | Jmp::Abs(expr) => expr.strip_locs(),
+            Jmp::Id(_) => {}
+            Jmp::RelIf(lhs, rhs, _) => {
+                lhs.strip_locs();
+                rhs.strip_locs();
+            }
+            Jmp::IdIf(_, cond, _) => cond.strip_locs(),
+        }
+    }
+}
+
+impl StripLocs for FunctionCall {
+    fn strip_locs(&mut self) {
+        self.implicit_args.strip_locs();
+        self.args.strip_locs();
+        self.loc.strip_locs();
+    }
+}
+
+impl StripLocs for FunctionDef {
+    fn strip_locs(&mut self) {
+        self.doc.strip_locs();
+        self.implicit_args.strip_locs();
+        self.input_args.strip_locs();
+        self.return_values.strip_locs();
+        self.instructions.strip_locs();
+        self.loc.strip_locs();
+    }
+}
+
+impl StripLocs for ConstantDef {
+    fn strip_locs(&mut self) {
+        self.doc.strip_locs();
+        self.ty.strip_locs();
+        self.init.strip_locs();
+        self.loc.strip_locs();
+    }
+}
+
+impl StripLocs for StructDef {
+    fn strip_locs(&mut self) {
+        self.doc.strip_locs();
+        self.members.strip_locs();
+        self.loc.strip_locs();
+    }
+}
+
+impl StripLocs for MemberInfo {
+    fn strip_locs(&mut self) {
+        self.ty.strip_locs();
+        self.loc.strip_locs();
+    }
+}
+
+impl StripLocs for Namespace {
+    fn strip_locs(&mut self) {
+        self.doc.strip_locs();
+        self.instructions.strip_locs();
+        self.loc.strip_locs();
+    }
+}
+
+impl StripLocs for IfStatement {
+    fn strip_locs(&mut self) {
+        self.cond.strip_locs();
+        self.instructions.strip_locs();
+        self.else_branch.strip_locs();
+        self.loc.strip_locs();
+    }
+}
+
+impl StripLocs for WithStatement {
+    fn strip_locs(&mut self) {
+        self.ids.strip_locs();
+        self.instructions.strip_locs();
+        self.loc.strip_locs();
+    }
+}
+
+impl StripLocs for WithAttrStatement {
+    fn strip_locs(&mut self) {
+        self.instructions.strip_locs();
+        self.loc.strip_locs();
+    }
+}
+
+impl StripLocs for AliasedId {
+    fn strip_locs(&mut self) {
+        self.loc.strip_locs();
+    }
+}
+
+impl StripLocs for ImportDirective {
+    fn strip_locs(&mut self) {
+        match &mut self.functions {
+            FunctionImport::Direct(loc, ids) | FunctionImport::Parantheses(loc, ids) => {
+                loc.strip_locs();
+                ids.strip_locs();
+            }
+        }
+        self.loc.strip_locs();
+    }
+}
+
+impl StripLocs for Directive {
+    fn strip_locs(&mut self) {
+        match self {
+            Directive::Lang(loc, _) | Directive::Builtins(loc, _) => loc.strip_locs(),
+        }
+    }
+}
+
+impl StripLocs for Note {
+    fn strip_locs(&mut self) {
+        match self {
+            Note::NewLine(loc) | Note::Comment(_, loc) => loc.strip_locs(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    #[test]
+    fn can_compare_reformatted_files() {
+        let code = r#"
+struct Point:
+    member x : felt
+    member y : felt*
+end
+
+func foo{range_check_ptr}(a : Point, b) -> (res : felt):
+    let (c, d) = bar(a.x, b=b)
+    if c == 0:
+        [ap] = [fp - 3] + 1; ap++
+    end
+    return (res=c * (d + 2))
+end
+"#;
+        let reformatted = r#"
+struct   Point:
+  member x:felt
+  member y:felt*
+end
+func foo{ range_check_ptr }( a:Point,b )->( res:felt ):
+  let ( c,d ) = bar( a.x,b = b )
+  if c==0:
+     [ap]=[fp-3]+1;ap++
+  end
+  return ( res = c*(d+2) )
+end
+"#;
+        let lhs = CairoFile::parse(code).unwrap();
+        let rhs = CairoFile::parse(reformatted).unwrap();
+        assert!(lhs.structural_eq(&rhs));
+
+        let other = CairoFile::parse(&code.replace("d + 2", "d + 3")).unwrap();
+        assert!(!lhs.structural_eq(&other));
+
+        // `Loc` always compares equal in tests, so the locations are compared via `Debug`
+        let debug = |file: &CairoFile| format!("{:?}", file);
+        assert_ne!(debug(&lhs), debug(&rhs));
+        assert_eq!(debug(&lhs.clone().without_locs()), debug(&rhs.clone().without_locs()));
+
+        // files that only differ in the position of their nodes
+        let shifted = CairoFile::parse(&format!("\n\n{}", code)).unwrap();
+        assert_ne!(debug(&lhs), debug(&shifted));
+        assert_eq!(debug(&lhs.clone().without_locs()), debug(&shifted.clone().without_locs()));
+
+        // every location is reset
+        let func = lhs.functions().next().unwrap();
+        assert_ne!(format!("{:?}", func.loc), "Loc(0, 0)");
+        assert_eq!(format!("{:?}", func.clone().without_locs().loc), "Loc(0, 0)");
+        let stripped = debug(&lhs.without_locs());
+        assert_eq!(stripped.matches("Loc(0, 0)").count(), stripped.matches("Loc(").count());
+        assert!(Expr::parse("(a+1)*2")
+            .unwrap()
+            .structural_eq(&Expr::parse("( a + 1 ) * 2").unwrap()));
+    }
+}
@@ -0,0 +1,497 @@
+//! Traversal of the AST by the passes of the compiler
+
+use crate::{
+    compiler::{RefVisitor, VResult, Visitable, Visitor, Walk},
+    parser::ast::*,
+};
+
+impl Visitable for CairoFile {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        self.0.visit(v)
+    }
+}
+
+impl Walk for CairoFile {
+    fn walk(&self, v: &mut dyn RefVisitor) -> VResult {
+        self.0.walk(v)
+    }
+}
+
+impl Visitable for ImportDirective {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        v.visit_import(self)
+    }
+}
+
+impl Visitable for StructDef {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        v.visit_struct_def(self)
+    }
+}
+
+impl Visitable for Namespace {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        v.visit_namespace(self)?;
+        self.instructions.visit(v)
+    }
+}
+
+impl Visitable for CairoType {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        v.visit_type(self)
+    }
+}
+
+impl Walk for CairoType {
+    fn walk(&self, v: &mut dyn RefVisitor) -> VResult {
+        v.visit_type(self)
+    }
+}
+
+impl Visitable for Expr {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        v.visit_expr(self)?;
+        match self {
+            Expr::Int(_, _) => {}
+            Expr::HexInt(_, _) => {}
+            Expr::ShortString(_, _) => {}
+            Expr::Hint(_, _) => {}
+            Expr::Register(_, _) => {}
+            Expr::FunctionCall(call) => {
+                call.visit(v)?;
+            }
+            Expr::Id(id, loc) => {
+                v.visit_expr_identifier(id, *loc)?;
+            }
+            Expr::Dot(expr, id, loc) => {
+                v.visit_expr_dot(&mut **expr, id, *loc)?;
+                expr.visit(v)?;
+            }
+            Expr::Cast(expr, ty, loc) => {
+                v.visit_expr_cat(&mut **expr, ty, *loc)?;
+                expr.visit(v)?;
+                ty.visit(v)?;
+            }
+            Expr::Parentheses(exprs, _) => {
+                exprs.visit(v)?;
+            }
+            Expr::Deref(expr, _) | Expr::Address(expr, _) | Expr::Neg(expr, _) => {
+                expr.visit(v)?;
+            }
+            Expr::Subscript(lhs, rhs, _) |
+            Expr::Pow(lhs, rhs, _) |
+            Expr::Mul(lhs, rhs, _) |
+            Expr::Div(lhs, rhs, _) |
+            Expr::Add(lhs, rhs, _) |
+            Expr::Sub(lhs, rhs, _) => {
+                lhs.visit(v)?;
+                rhs.visit(v)?;
+            }
+        };
+        Ok(())
+    }
+}
+
+impl Walk for Expr {
+    fn walk(&self, v: &mut dyn RefVisitor) -> VResult {
+        match self {
+            Expr::Int(_, _) |
+            Expr::HexInt(_, _) |
+            Expr::ShortString(_, _) |
+            Expr::Hint(_, _) |
+            Expr::Register(_, _) => Ok(()),
+            Expr::FunctionCall(call) => call.walk(v),
+            Expr::Id(id, loc) => v.visit_expr_identifier(id, *loc),
+            Expr::Dot(expr, _, _) |
+            Expr::Deref(expr, _) |
+            Expr::Address(expr, _) |
+            Expr::Neg(expr, _) => expr.walk(v),
+            Expr::Cast(expr, ty, _) => {
+                expr.walk(v)?;
+                ty.walk(v)
+            }
+            Expr::Parentheses(exprs, _) => exprs.walk(v),
+            Expr::Subscript(lhs, rhs, _) |
+            Expr::Pow(lhs, rhs, _) |
+            Expr::Mul(lhs, rhs, _) |
+            Expr::Div(lhs, rhs, _) |
+            Expr::Add(lhs, rhs, _) |
+            Expr::Sub(lhs, rhs, _) => {
+                lhs.walk(v)?;
+                rhs.walk(v)
+            }
+        }
+    }
+}
+
+impl Visitable for ExprAssignment {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        v.visit_expr_assignment(self)?;
+        self.expr_mut().visit(v)
+    }
+}
+
+impl Walk for ExprAssignment {
+    fn walk(&self, v: &mut dyn RefVisitor) -> VResult {
+        self.expr().walk(v)
+    }
+}
+
+impl Visitable for BoolExpr {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        match self {
+            BoolExpr::Equal(lhs, rhs) | BoolExpr::NotEqual(lhs, rhs) => {
+                lhs.visit(v)?;
+                rhs.visit(v)
+            }
+        }
+    }
+}
+
+impl Walk for BoolExpr {
+    fn walk(&self, v: &mut dyn RefVisitor) -> VResult {
+        match self {
+            BoolExpr::Equal(lhs, rhs) | BoolExpr::NotEqual(lhs, rhs) => {
+                lhs.walk(v)?;
+                rhs.walk(v)
+            }
+        }
+    }
+}
+
+impl Visitable for ConstantDef {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        v.visit_const_def(self)?;
+        if let Some(ty) = self.ty.as_mut() {
+            ty.visit(v)?;
+        }
+        self.init.visit(v)
+    }
+}
+
+impl Visitable for TypedIdentifier {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        v.visit_typed_identifier(self)?;
+        if let Some(ty) = self.ty.as_mut() {
+            ty.visit(v)?;
+        }
+        Ok(())
+    }
+}
+
+impl Walk for TypedIdentifier {
+    fn walk(&self, v: &mut dyn RefVisitor) -> VResult {
+        if let Some(ty) = self.ty.as_ref() {
+            ty.walk(v)?;
+        }
+        Ok(())
+    }
+}
+
+impl Visitable for Instruction {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        match self {
+            Instruction::Const(i) => {
+                i.visit(v)?;
+            }
+            Instruction::Member(_, _) => {}
+            Instruction::Let(id, rvalue, _) => {
+                v.visit_reference(id, &mut **rvalue)?;
+                rvalue.visit(v)?;
+            }
+            Instruction::Local(id, expr, _) => {
+                v.visit_local_var(id, expr)?;
+                id.visit(v)?;
+                if let Some(expr) = expr {
+                    expr.visit(v)?;
+                }
+            }
+            Instruction::Tempvar(id, expr, _) => {
+                v.visit_temp_var(id, expr)?;
+                id.visit(v)?;
+                if let Some(expr) = expr {
+                    expr.visit(v)?;
+                }
+            }
+            Instruction::Assert(lhs, rhs, loc) => {
+                v.visit_assert(lhs, rhs, *loc)?;
+                lhs.visit(v)?;
+                rhs.visit(v)?;
+            }
+            Instruction::StaticAssert(lhs, rhs, loc) => {
+                v.visit_static_assert(lhs, rhs, *loc)?;
+                lhs.visit(v)?;
+                rhs.visit(v)?;
+            }
+            Instruction::Assign(lhs, rhs, _) => {
+                lhs.visit(v)?;
+                rhs.visit(v)?;
+            }
+            Instruction::Return(exprs, loc) => {
+                v.visit_return(exprs, *loc)?;
+                exprs.visit(v)?;
+            }
+            Instruction::ReturnFunctionCall(call, _) => {
+                call.visit(v)?;
+            }
+            Instruction::If(i) => {
+                i.visit(v)?;
+            }
+            Instruction::Label(i, loc) => {
+                v.visit_label(i, *loc)?;
+            }
+            Instruction::Function(i) => {
+                v.enter_function(i)?;
+                i.visit(v)?;
+                v.exit_function(i)?;
+            }
+            Instruction::FunctionCall(call) => {
+                call.visit(v)?;
+            }
+            Instruction::Struct(i) => {
+                i.visit(v)?;
+            }
+            Instruction::Namespace(i) => {
+                v.enter_namespace(i)?;
+                i.visit(v)?;
+                v.exit_namespace(i)?;
+            }
+            Instruction::WithAttrStatement(i) => {
+                i.visit(v)?;
+            }
+            Instruction::WithStatement(i) => {
+                i.visit(v)?;
+            }
+            Instruction::Hint(hint, loc) => {
+                v.visit_hint(hint, *loc)?;
+            }
+            Instruction::Directive(d) => {
+                d.visit(v)?;
+            }
+            Instruction::Import(i) => {
+                i.visit(v)?;
+            }
+            Instruction::AllocLocals(_) => {}
+            Instruction::Jmp(jmp, _) => {
+                jmp.visit(v)?;
+            }
+            Instruction::CallInstruction(call, _) => {
+                call.visit(v)?;
+            }
+            Instruction::Ret(_) => {}
+            Instruction::ApAddAssign(expr, loc) => {
+                v.visit_ap_add_assign(expr, *loc)?;
+                expr.visit(v)?;
+            }
+            Instruction::DataWord(expr, loc) => {
+                v.visit_data_word(expr, *loc)?;
+                expr.visit(v)?;
+            }
+            Instruction::ApAdd(ins, _) => {
+                ins.visit(v)?;
+            }
+        };
+        Ok(())
+    }
+}
+
+impl Walk for Instruction {
+    fn walk(&self, v: &mut dyn RefVisitor) -> VResult {
+        v.visit_instruction(self)?;
+        match self {
+            Instruction::Const(c) => {
+                v.visit_const_def(c)?;
+                if let Some(ty) = c.ty.as_ref() {
+                    ty.walk(v)?;
+                }
+                c.init.walk(v)
+            }
+            Instruction::Member(id, _) => id.walk(v),
+            Instruction::Struct(s) => {
+                v.visit_struct_def(s)?;
+                for member in s.members.iter() {
+                    member.ty.walk(v)?;
+                }
+                Ok(())
+            }
+            Instruction::Import(i) => v.visit_import(i),
+            Instruction::Let(binding, rvalue, _) => {
+                match binding {
+                    RefBinding::Id(id) => id.walk(v)?,
+                    RefBinding::List(ids) => ids.walk(v)?,
+                }
+                rvalue.walk(v)
+            }
+            Instruction::Local(id, expr, _) | Instruction::Tempvar(id, expr, _) => {
+                id.walk(v)?;
+                if let Some(expr) = expr {
+                    expr.walk(v)?;
+                }
+                Ok(())
+            }
+            Instruction::Assert(lhs, rhs, _) |
+            Instruction::StaticAssert(lhs, rhs, _) |
+            Instruction::Assign(lhs, rhs, _) => {
+                lhs.walk(v)?;
+                rhs.walk(v)
+            }
+            Instruction::Return(exprs, _) => exprs.walk(v),
+            Instruction::FunctionCall(call) | Instruction::ReturnFunctionCall(call, _) => {
+                call.walk(v)
+            }
+            Instruction::Function(f) => {
+                v.enter_function(f)?;
+                if let Some(args) = f.implicit_args.as_ref() {
+                    args.walk(v)?;
+                }
+                f.input_args.walk(v)?;
+                if let Some(args) = f.return_values.as_ref() {
+                    args.walk(v)?;
+                }
+                f.instructions.walk(v)?;
+                v.exit_function(f)
+            }
+            Instruction::Namespace(n) => {
+                v.enter_namespace(n)?;
+                n.instructions.walk(v)?;
+                v.exit_namespace(n)
+            }
+            Instruction::If(i) => {
+                i.cond.walk(v)?;
+                i.instructions.walk(v)?;
+                if let Some(e) = i.else_branch.as_ref() {
+                    e.walk(v)?;
+                }
+                Ok(())
+            }
+            Instruction::WithStatement(w) => w.instructions.walk(v),
+            Instruction::WithAttrStatement(w) => w.instructions.walk(v),
+            Instruction::Jmp(jmp, _) => jmp.walk(v),
+            Instruction::CallInstruction(call, _) => call.walk(v),
+            Instruction::ApAddAssign(expr, _) | Instruction::DataWord(expr, _) => expr.walk(v),
+            Instruction::ApAdd(ins, _) => ins.walk(v),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Visitable for Directive {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        v.visit_directive(self)
+    }
+}
+
+impl Visitable for RValue {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        match self {
+            RValue::Call(call) => call.visit(v),
+            RValue::Expr(expr) => expr.visit(v),
+        }
+    }
+}
+
+impl Walk for RValue {
+    fn walk(&self, v: &mut dyn RefVisitor) -> VResult {
+        match self {
+            RValue::Call(call) => call.walk(v),
+            RValue::Expr(expr) => expr.walk(v),
+        }
+    }
+}
+
+impl Visitable for Call {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        match self {
+            Call::Rel(expr) | Call::Abs(expr) => expr.visit(v),
+            Call::Id(_) => Ok(()),
+        }
+    }
+}
+
+impl Walk for Call {
+    fn walk(&self, v: &mut dyn RefVisitor) -> VResult {
+        match self {
+            Call::Rel(expr) | Call::Abs(expr) => expr.walk(v),
+            Call::Id(_) => Ok(()),
+        }
+    }
+}
+
+impl Visitable for Jmp {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        match self {
+            Jmp::Rel(expr) | Jmp::Abs(expr) | Jmp::IdIf(_, expr, _) => expr.visit(v),
+            Jmp::Id(_) => Ok(()),
+            Jmp::RelIf(lhs, rhs, _) => {
+                lhs.visit(v)?;
+                rhs.visit(v)
+            }
+        }
+    }
+}
+
+impl Walk for Jmp {
+    fn walk(&self, v: &mut dyn RefVisitor) -> VResult {
+        match self {
+            Jmp::Rel(expr) | Jmp::Abs(expr) | Jmp::IdIf(_, expr, _) => expr.walk(v),
+            Jmp::Id(_) => Ok(()),
+            Jmp::RelIf(lhs, rhs, _) => {
+                lhs.walk(v)?;
+                rhs.walk(v)
+            }
+        }
+    }
+}
+
+impl Visitable for WithStatement {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        v.visit_with(self)?;
+        self.instructions.visit(v)
+    }
+}
+
+impl Visitable for WithAttrStatement {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        v.visit_with_attr(self)?;
+        self.instructions.visit(v)
+    }
+}
+
+impl Visitable for FunctionDef {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        v.visit_function(self)?;
+        self.instructions.visit(v)
+    }
+}
+
+impl Visitable for FunctionCall {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        v.visit_function_call(self)?;
+        if let Some(args) = self.implicit_args.as_mut() {
+            args.visit(v)?;
+        }
+        self.args.visit(v)
+    }
+}
+
+impl Walk for FunctionCall {
+    fn walk(&self, v: &mut dyn RefVisitor) -> VResult {
+        v.visit_function_call(self)?;
+        if let Some(args) = self.implicit_args.as_ref() {
+            args.walk(v)?;
+        }
+        self.args.walk(v)
+    }
+}
+
+impl Visitable for IfStatement {
+    fn visit(&mut self, v: &mut dyn Visitor) -> VResult {
+        v.visit_if(self)?;
+        self.cond.visit(v)?;
+        self.instructions.visit(v)?;
+        if let Some(e) = self.else_branch.as_mut() {
+            e.visit(v)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1 @@
+const X = 2
@@ -0,0 +1 @@
+const RELATIVE = 1
@@ -0,0 +1,3 @@
+from sub.inner import INNER
+
+const OUTER = INNER
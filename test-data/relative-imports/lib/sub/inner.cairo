@@ -0,0 +1,3 @@
+from leaf import LEAF
+
+const INNER = LEAF
@@ -0,0 +1 @@
+const LEAF = 1
@@ -0,0 +1,5 @@
+from sibling import X
+from c import RELATIVE
+from lib.outer import OUTER
+
+const Y = X
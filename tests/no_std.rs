@@ -0,0 +1,39 @@
+//! Builds against the lexer, parser and AST from a `no_std` crate, run with
+//! `cargo test --no-default-features --test no_std`
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::string::ToString;
+use cairo_lang_rs::{parser::format::FormatOptions, CairoFile};
+
+const CODE: &str = r#"%builtins output
+
+from starkware.cairo.common.serialize import serialize_word
+
+func main{output_ptr : felt*}():
+    let x = 1 + 2
+    if x == 3:
+        serialize_word(x)
+    end
+    return ()
+end
+"#;
+
+#[test]
+fn can_parse_and_format_without_std() {
+    let formatted = CairoFile::parse(CODE).unwrap().to_string();
+    assert_eq!(CairoFile::parse(&formatted).unwrap().to_string(), formatted);
+
+    let opts = FormatOptions { indent: 2, ..Default::default() };
+    let indented = CairoFile::parse(CODE).unwrap().to_string_with(&opts);
+    assert!(indented.contains("\n  let x = 1 + 2\n"));
+    assert_eq!(CairoFile::parse(&indented).unwrap().to_string(), formatted);
+}
+
+#[test]
+fn can_report_errors_without_std() {
+    let err = CairoFile::parse("func main(:\nend\n").unwrap_err();
+    assert!(!err.to_string().is_empty());
+}